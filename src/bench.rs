@@ -0,0 +1,269 @@
+//! Script Benchmarking
+//!
+//! Runs a PostScript program repeatedly and reports timing statistics. This is the
+//! engine behind the `bench` subcommand of the CLI:
+//!
+//! - The source is tokenized and parsed once; only execution is timed
+//! - Each iteration runs in a fresh interpreter, unless `reuse` is set
+//! - Warmup iterations run first and are not measured
+//! - The script's output goes nowhere unless `show_output` is set
+//! - Executed values and peak stack depths come from the Context's `ExecutionStats`
+//!
+//! `bench_construction` measures interpreter construction instead: building many
+//! interpreters with `register_builtins` each, against building them from one
+//! `SharedSystemDict`.
+
+use crate::commands::register_builtins;
+use crate::interpreter::Interpreter;
+use crate::parser::{Tokenizer, parse_scanned};
+use crate::types::{CompatibilityMode, Context, ExecutionStats, PostScriptValue, SharedSystemDict, MAX_LANGUAGE_LEVEL};
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How a benchmark is run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    /// Number of measured runs
+    pub iterations: usize,
+    /// Number of unmeasured runs before the measured ones
+    pub warmup: usize,
+    /// Run every iteration in the same interpreter instead of a fresh one
+    pub reuse: bool,
+    /// Let the script write to standard output instead of discarding its output
+    pub show_output: bool,
+    /// Scoping mode of the interpreters
+    pub lexical_scoping: bool,
+    /// Language level of the interpreters
+    pub language_level: u8,
+    /// Compatibility mode of the interpreters
+    pub compatibility: CompatibilityMode,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            iterations: 10,
+            warmup: 1,
+            reuse: false,
+            show_output: false,
+            lexical_scoping: false,
+            language_level: MAX_LANGUAGE_LEVEL,
+            compatibility: CompatibilityMode::default(),
+        }
+    }
+}
+
+/// Statistics over the measured iterations of a benchmark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// Number of measured iterations
+    pub iterations: usize,
+    /// Number of warmup iterations that ran before them
+    pub warmup: usize,
+    /// Fastest iteration
+    pub min: Duration,
+    /// Median iteration
+    pub median: Duration,
+    /// Mean iteration time
+    pub mean: Duration,
+    /// 95th percentile (nearest rank)
+    pub p95: Duration,
+    /// Values executed per iteration (the mean, if iterations differ)
+    pub executed: u64,
+    /// Estimated peak memory used by the operand and execution stacks, in bytes
+    pub peak_stack_bytes: usize,
+}
+
+impl BenchReport {
+    /// Formats the report as a single JSON object, with times in nanoseconds.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"iterations\":{},\"warmup\":{},\"min_ns\":{},\"median_ns\":{},\"mean_ns\":{},\"p95_ns\":{},\"executed\":{},\"peak_stack_bytes\":{}}}",
+            self.iterations,
+            self.warmup,
+            self.min.as_nanos(),
+            self.median.as_nanos(),
+            self.mean.as_nanos(),
+            self.p95.as_nanos(),
+            self.executed,
+            self.peak_stack_bytes
+        )
+    }
+
+    /// Formats the report as human-readable lines.
+    pub fn to_text(&self) -> String {
+        format!(
+            "iterations: {} (after {} warmup)\nmin:        {:?}\nmedian:     {:?}\nmean:       {:?}\np95:        {:?}\nexecuted:   {} values per iteration\npeak stack: ~{} bytes\n",
+            self.iterations, self.warmup, self.min, self.median, self.mean, self.p95, self.executed, self.peak_stack_bytes
+        )
+    }
+}
+
+/// Time taken to construct a number of interpreters, with and without a shared
+/// system dictionary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstructionReport {
+    /// Number of interpreters built each way
+    pub count: usize,
+    /// Total time with `register_builtins` run for every interpreter
+    pub registered: Duration,
+    /// Total time with every interpreter built from one `SharedSystemDict`
+    /// (including building that dictionary)
+    pub shared: Duration,
+}
+
+impl ConstructionReport {
+    /// Formats the report as a single JSON object, with times in nanoseconds.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"count\":{},\"registered_ns\":{},\"shared_ns\":{}}}",
+            self.count,
+            self.registered.as_nanos(),
+            self.shared.as_nanos()
+        )
+    }
+
+    /// Formats the report as human-readable lines.
+    pub fn to_text(&self) -> String {
+        format!(
+            "interpreters: {}\nregistered:   {:?}\nshared:       {:?}\n",
+            self.count, self.registered, self.shared
+        )
+    }
+}
+
+/// Benchmarks a PostScript program.
+///
+/// The source is text or the bytes of a script file, scanned as `Tokenizer::from_bytes`
+/// does. Fails on tokenization or parse errors, if `iterations` is zero, or as soon as
+/// any iteration fails (the error names the iteration).
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::bench::{run_bench, BenchOptions};
+///
+/// let options = BenchOptions { iterations: 7, warmup: 2, ..BenchOptions::default() };
+/// let report = run_bench("0 1 1 100 { add } for =", &options).unwrap();
+/// assert_eq!(report.iterations, 7);
+/// assert_eq!(report.warmup, 2);
+/// assert!(report.min <= report.median && report.median <= report.p95);
+/// assert!(report.executed > 200);
+/// assert!(report.to_json().starts_with("{\"iterations\":7,"));
+///
+/// assert!(run_bench("1 undefinedname", &options).unwrap_err().contains("iteration 1"));
+/// ```
+pub fn run_bench(source: impl AsRef<[u8]>, options: &BenchOptions) -> Result<BenchReport, String> {
+    if options.iterations == 0 {
+        return Err("Benchmark needs at least one iteration".to_string());
+    }
+    let tokenizer = Tokenizer::from_bytes_with_language_level(source.as_ref(), options.language_level);
+    let values = parse_scanned(tokenizer, None)?;
+
+    let mut shared = if options.reuse { Some(new_interpreter(options)) } else { None };
+    let mut times = Vec::with_capacity(options.iterations);
+    let mut executed = 0;
+    let mut peak_stack_bytes = 0;
+    for run in 0..options.warmup + options.iterations {
+        let mut fresh;
+        let interpreter = match shared.as_mut() {
+            Some(interpreter) => interpreter,
+            None => {
+                fresh = new_interpreter(options);
+                &mut fresh
+            }
+        };
+        interpreter.get_context_mut().stats = ExecutionStats::default();
+
+        let start = Instant::now();
+        let result = interpreter.execute(values.clone());
+        let elapsed = start.elapsed();
+
+        if run < options.warmup {
+            result.map_err(|e| format!("Warmup iteration {} failed: {}", run + 1, e))?;
+            continue;
+        }
+        result.map_err(|e| format!("Benchmark iteration {} failed: {}", run - options.warmup + 1, e))?;
+        let stats = &interpreter.get_context().stats;
+        times.push(elapsed);
+        executed += stats.executed;
+        peak_stack_bytes = peak_stack_bytes.max(
+            (stats.peak_operand_stack + stats.peak_exec_stack) * std::mem::size_of::<PostScriptValue>()
+        );
+    }
+
+    times.sort();
+    let n = times.len();
+    let median = if n % 2 == 1 { times[n / 2] } else { (times[n / 2 - 1] + times[n / 2]) / 2 };
+    let p95 = times[(n * 95).div_ceil(100) - 1];
+    Ok(BenchReport {
+        iterations: n,
+        warmup: options.warmup,
+        min: times[0],
+        median,
+        mean: times.iter().sum::<Duration>() / n as u32,
+        p95,
+        executed: executed / n as u64,
+        peak_stack_bytes,
+    })
+}
+
+/// Times building `count` interpreters configured as the options say, first with
+/// `register_builtins` populating each one's system dictionary, then with each one
+/// built by `Context::with_shared_systemdict` from a single shared dictionary.
+///
+/// `iterations`, `warmup` and `reuse` are not used. Fails if `count` is zero.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::bench::{bench_construction, BenchOptions};
+///
+/// let report = bench_construction(10_000, &BenchOptions::default()).unwrap();
+/// assert_eq!(report.count, 10_000);
+/// assert!(report.shared < report.registered);
+/// assert!(report.to_json().starts_with("{\"count\":10000,"));
+///
+/// assert!(bench_construction(0, &BenchOptions::default()).is_err());
+/// ```
+pub fn bench_construction(count: usize, options: &BenchOptions) -> Result<ConstructionReport, String> {
+    if count == 0 {
+        return Err("Benchmark needs at least one interpreter".to_string());
+    }
+
+    let start = Instant::now();
+    for _ in 0..count {
+        std::hint::black_box(new_interpreter(options));
+    }
+    let registered = start.elapsed();
+
+    let start = Instant::now();
+    let shared = SharedSystemDict::from_context(new_context(options));
+    for _ in 0..count {
+        let mut context = Context::with_shared_systemdict(options.lexical_scoping, &shared);
+        if !options.show_output {
+            context.output = Box::new(io::sink());
+        }
+        std::hint::black_box(Interpreter::new(context));
+    }
+    let shared = start.elapsed();
+
+    Ok(ConstructionReport { count, registered, shared })
+}
+
+/// Builds an interpreter configured as the options say.
+fn new_interpreter(options: &BenchOptions) -> Interpreter {
+    Interpreter::new(new_context(options))
+}
+
+/// Builds a Context configured as the options say, with its built-in commands registered.
+fn new_context(options: &BenchOptions) -> Context {
+    let mut context = Context::new(options.lexical_scoping);
+    context.language_level = options.language_level;
+    context.compatibility = options.compatibility;
+    if !options.show_output {
+        context.output = Box::new(io::sink());
+    }
+    register_builtins(&mut context);
+    context
+}
@@ -6,12 +6,22 @@
 //! # Command Categories
 //!
 //! - **Stack Manipulation**: exch, pop, copy, dup, clear, count
-//! - **Arithmetic**: add, sub, mul, div, idiv, mod, abs, neg, ceiling, floor, round, sqrt
-//! - **Dictionary**: dict, length, maxlength, begin, end, def
-//! - **String**: get, getinterval, putinterval
+//! - **Arithmetic**: add, sub, mul, div, idiv, mod, abs, neg, ceiling, floor, round, truncate, sqrt, sin, cos, atan,
+//!   exp, ln, log, rand, srand, rrand
+//! - **Dictionary**: dict, length, maxlength, begin, end, def, known, where, load, store,
+//!   currentdict, countdictstack, dictstack, undef, cleardictstack, systemdict, userdict, globaldict,
+//!   <<, >>, dicttomark
+//! - **String**: get, put, getinterval, putinterval, string, search, anchorsearch
 //! - **Boolean/Bit**: eq, ne, ge, gt, le, lt, and, or, not
-//! - **Flow Control**: if, ifelse, for, repeat, quit
-//! - **I/O**: print, =, ==
+//! - **Flow Control**: if, ifelse, for, repeat, loop, exit, forall, exec, stop, stopped, countexecstack,
+//!   execstack, bind, quit
+//! - **Type Conversion**: type, cvx, cvlit, xcheck, readonly, executeonly, noaccess, rcheck, wcheck, cvn, cvs, cvrs, cvi, cvr
+//! - **I/O**: print, =, ==, stack, pstack, executive, prompt, file, currentfile, read, readline,
+//!   readstring, readhexstring, write, writestring, closefile, flush, flushfile, filter
+//! - **Extensions** (non-standard): deepcopy, sort, getenv
+//! - **Virtual Memory**: save, restore, vmstatus, setglobal, currentglobal
+//! - **Interpreter Information**: languagelevel, version, product, revision, usertime, realtime, currentsystemparams, setsystemparams, help
+//! - **Resources**: findfont, defineresource, undefineresource, findresource, resourcestatus, resourceforall
 //!
 //! # How Commands Work
 //!
@@ -23,10 +33,15 @@
 //!
 //! The interpreter calls these functions when it encounters a Name that maps to a NativeFn.
 
-use crate::types::{Context, PostScriptValue};
+use crate::filter::{DecodeFilter, Decoder, EncodeFilter, Encoder, FilterTarget};
+use crate::parser::{Tokenizer, parse};
+use crate::types::{latin1_bytes, Access, Allocation, Context, FileStream, MergeSort, PostScriptFile, PostScriptValue, PRODUCT, REVISION, STATEMENT_FILE, VERSION};
+use std::cmp::Ordering;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
 
 /// Registers all built-in PostScript commands in the given context.
 ///
@@ -34,6 +49,11 @@ use std::collections::HashMap;
 /// system dictionary with all native commands. Each command is registered as
 /// a NativeFn value that points to the corresponding Rust function.
 ///
+/// Commands are grouped by the language level that introduced them; groups above
+/// `context.language_level` are not registered. `languagelevel` is always available
+/// so scripts can query the emulated level. Non-standard extensions are not
+/// registered when the context is in strict compatibility mode.
+///
 /// # Example
 ///
 /// ```ignore
@@ -43,64 +63,457 @@ use std::collections::HashMap;
 /// ```
 pub fn register_builtins(context: &mut Context) {
     // Stack Manipulation
-    context.define("exch".to_string(), PostScriptValue::NativeFn(exch));
-    context.define("pop".to_string(), PostScriptValue::NativeFn(pop));
-    context.define("copy".to_string(), PostScriptValue::NativeFn(copy));
-    context.define("dup".to_string(), PostScriptValue::NativeFn(dup));
-    context.define("clear".to_string(), PostScriptValue::NativeFn(clear));
-    context.define("count".to_string(), PostScriptValue::NativeFn(count));
+    context.define_system("exch".to_string(), PostScriptValue::NativeFn(exch));
+    context.define_system("pop".to_string(), PostScriptValue::NativeFn(pop));
+    context.define_system("copy".to_string(), PostScriptValue::NativeFn(copy));
+    context.define_system("dup".to_string(), PostScriptValue::NativeFn(dup));
+    context.define_system("clear".to_string(), PostScriptValue::NativeFn(clear));
+    context.define_system("count".to_string(), PostScriptValue::NativeFn(count));
+    context.define_system("roll".to_string(), PostScriptValue::NativeFn(roll));
+    context.define_system("mark".to_string(), PostScriptValue::NativeFn(mark));
+    context.define_system("cleartomark".to_string(), PostScriptValue::NativeFn(cleartomark));
+    context.define_system("counttomark".to_string(), PostScriptValue::NativeFn(counttomark));
+    context.define_system("index".to_string(), PostScriptValue::NativeFn(index));
+
+    // Arithmetic
+    context.define_system("add".to_string(), PostScriptValue::NativeFn(add));
+    context.define_system("sub".to_string(), PostScriptValue::NativeFn(sub));
+    context.define_system("mul".to_string(), PostScriptValue::NativeFn(mul));
+    context.define_system("div".to_string(), PostScriptValue::NativeFn(div));
+    context.define_system("idiv".to_string(), PostScriptValue::NativeFn(idiv));
+    context.define_system("mod".to_string(), PostScriptValue::NativeFn(mod_op));
+    context.define_system("abs".to_string(), PostScriptValue::NativeFn(abs));
+    context.define_system("neg".to_string(), PostScriptValue::NativeFn(neg));
+    context.define_system("ceiling".to_string(), PostScriptValue::NativeFn(ceiling));
+    context.define_system("floor".to_string(), PostScriptValue::NativeFn(floor));
+    context.define_system("round".to_string(), PostScriptValue::NativeFn(round));
+    context.define_system("truncate".to_string(), PostScriptValue::NativeFn(truncate));
+    context.define_system("sqrt".to_string(), PostScriptValue::NativeFn(sqrt));
+    context.define_system("sin".to_string(), PostScriptValue::NativeFn(sin));
+    context.define_system("cos".to_string(), PostScriptValue::NativeFn(cos));
+    context.define_system("atan".to_string(), PostScriptValue::NativeFn(atan));
+    context.define_system("exp".to_string(), PostScriptValue::NativeFn(exp));
+    context.define_system("ln".to_string(), PostScriptValue::NativeFn(ln));
+    context.define_system("log".to_string(), PostScriptValue::NativeFn(log));
+    context.define_system("rand".to_string(), PostScriptValue::NativeFn(rand));
+    context.define_system("srand".to_string(), PostScriptValue::NativeFn(srand));
+    context.define_system("rrand".to_string(), PostScriptValue::NativeFn(rrand));
+
+    // Dictionary
+    context.define_system("dict".to_string(), PostScriptValue::NativeFn(dict));
+    context.define_system("length".to_string(), PostScriptValue::NativeFn(length));
+    context.define_system("maxlength".to_string(), PostScriptValue::NativeFn(maxlength));
+    context.define_system("begin".to_string(), PostScriptValue::NativeFn(begin));
+    context.define_system("end".to_string(), PostScriptValue::NativeFn(end));
+    context.define_system("def".to_string(), PostScriptValue::NativeFn(def));
+    context.define_system("known".to_string(), PostScriptValue::NativeFn(known));
+    context.define_system("where".to_string(), PostScriptValue::NativeFn(where_op));
+    context.define_system("load".to_string(), PostScriptValue::NativeFn(load));
+    context.define_system("store".to_string(), PostScriptValue::NativeFn(store));
+    context.define_system("currentdict".to_string(), PostScriptValue::NativeFn(currentdict));
+    context.define_system("countdictstack".to_string(), PostScriptValue::NativeFn(countdictstack));
+    context.define_system("dictstack".to_string(), PostScriptValue::NativeFn(dictstack));
+    context.define_system("systemdict".to_string(), PostScriptValue::NativeFn(systemdict));
+    context.define_system("userdict".to_string(), PostScriptValue::NativeFn(userdict));
+
+    // String
+    context.define_system("get".to_string(), PostScriptValue::NativeFn(get));
+    context.define_system("put".to_string(), PostScriptValue::NativeFn(put));
+    context.define_system("getinterval".to_string(), PostScriptValue::NativeFn(getinterval));
+    context.define_system("putinterval".to_string(), PostScriptValue::NativeFn(putinterval));
+    context.define_system("string".to_string(), PostScriptValue::NativeFn(string));
+    context.define_system("search".to_string(), PostScriptValue::NativeFn(search));
+    context.define_system("anchorsearch".to_string(), PostScriptValue::NativeFn(anchorsearch));
+
+    // Array
+    context.define_system("array".to_string(), PostScriptValue::NativeFn(array));
+    context.define_system("aload".to_string(), PostScriptValue::NativeFn(aload));
+    context.define_system("astore".to_string(), PostScriptValue::NativeFn(astore));
+    context.define_system("[".to_string(), PostScriptValue::NativeFn(mark));
+    context.define_system("]".to_string(), PostScriptValue::NativeFn(array_from_mark));
+
+    // Boolean/Bit
+    context.define_system("eq".to_string(), PostScriptValue::NativeFn(eq));
+    context.define_system("ne".to_string(), PostScriptValue::NativeFn(ne));
+    context.define_system("ge".to_string(), PostScriptValue::NativeFn(ge));
+    context.define_system("gt".to_string(), PostScriptValue::NativeFn(gt));
+    context.define_system("le".to_string(), PostScriptValue::NativeFn(le));
+    context.define_system("lt".to_string(), PostScriptValue::NativeFn(lt));
+    context.define_system("and".to_string(), PostScriptValue::NativeFn(and));
+    context.define_system("not".to_string(), PostScriptValue::NativeFn(not));
+    context.define_system("or".to_string(), PostScriptValue::NativeFn(or));
+    context.define_system("true".to_string(), PostScriptValue::Bool(true));
+    context.define_system("false".to_string(), PostScriptValue::Bool(false));
+    context.define_system("null".to_string(), PostScriptValue::Null);
+
+    // Flow Control
+    context.define_system("if".to_string(), PostScriptValue::NativeFn(if_op));
+    context.define_system("ifelse".to_string(), PostScriptValue::NativeFn(ifelse));
+    context.define_system("for".to_string(), PostScriptValue::NativeFn(for_op));
+    context.define_system("repeat".to_string(), PostScriptValue::NativeFn(repeat));
+    context.define_system("forall".to_string(), PostScriptValue::NativeFn(forall));
+    context.define_system("exec".to_string(), PostScriptValue::NativeFn(exec));
+    context.define_system("stop".to_string(), PostScriptValue::NativeFn(stop));
+    context.define_system("stopped".to_string(), PostScriptValue::NativeFn(stopped));
+    context.define_system("loop".to_string(), PostScriptValue::NativeFn(loop_op));
+    context.define_system("exit".to_string(), PostScriptValue::NativeFn(exit));
+    context.define_system("bind".to_string(), PostScriptValue::NativeFn(bind));
+    context.define_system("countexecstack".to_string(), PostScriptValue::NativeFn(countexecstack));
+    context.define_system("execstack".to_string(), PostScriptValue::NativeFn(execstack));
+    context.define_system("quit".to_string(), PostScriptValue::NativeFn(quit));
+
+    // Type Conversion
+    context.define_system("type".to_string(), PostScriptValue::NativeFn(type_op));
+    context.define_system("cvx".to_string(), PostScriptValue::NativeFn(cvx));
+    context.define_system("cvlit".to_string(), PostScriptValue::NativeFn(cvlit));
+    context.define_system("xcheck".to_string(), PostScriptValue::NativeFn(xcheck));
+    context.define_system("readonly".to_string(), PostScriptValue::NativeFn(readonly));
+    context.define_system("executeonly".to_string(), PostScriptValue::NativeFn(executeonly));
+    context.define_system("noaccess".to_string(), PostScriptValue::NativeFn(noaccess));
+    context.define_system("rcheck".to_string(), PostScriptValue::NativeFn(rcheck));
+    context.define_system("wcheck".to_string(), PostScriptValue::NativeFn(wcheck));
+    context.define_system("cvn".to_string(), PostScriptValue::NativeFn(cvn));
+    context.define_system("cvs".to_string(), PostScriptValue::NativeFn(cvs));
+    context.define_system("cvrs".to_string(), PostScriptValue::NativeFn(cvrs));
+    context.define_system("cvi".to_string(), PostScriptValue::NativeFn(cvi));
+    context.define_system("cvr".to_string(), PostScriptValue::NativeFn(cvr));
+
+    // I/O
+    context.define_system("print".to_string(), PostScriptValue::NativeFn(print));
+    context.define_system("=".to_string(), PostScriptValue::NativeFn(eq_print));
+    context.define_system("==".to_string(), PostScriptValue::NativeFn(eqeq_print));
+    context.define_system("stack".to_string(), PostScriptValue::NativeFn(stack));
+    context.define_system("pstack".to_string(), PostScriptValue::NativeFn(pstack));
+    context.define_system("executive".to_string(), PostScriptValue::NativeFn(executive));
+    context.define_system("prompt".to_string(), PostScriptValue::NativeFn(prompt));
+    context.define_system("file".to_string(), PostScriptValue::NativeFn(file));
+    context.define_system("read".to_string(), PostScriptValue::NativeFn(read));
+    context.define_system("currentfile".to_string(), PostScriptValue::NativeFn(currentfile));
+    context.define_system("readline".to_string(), PostScriptValue::NativeFn(readline));
+    context.define_system("readstring".to_string(), PostScriptValue::NativeFn(readstring));
+    context.define_system("readhexstring".to_string(), PostScriptValue::NativeFn(readhexstring));
+    context.define_system("write".to_string(), PostScriptValue::NativeFn(write));
+    context.define_system("writestring".to_string(), PostScriptValue::NativeFn(writestring));
+    context.define_system("closefile".to_string(), PostScriptValue::NativeFn(closefile));
+    context.define_system("flush".to_string(), PostScriptValue::NativeFn(flush));
+    context.define_system("flushfile".to_string(), PostScriptValue::NativeFn(flushfile));
+
+    // Virtual Memory
+    context.define_system("save".to_string(), PostScriptValue::NativeFn(save));
+    context.define_system("restore".to_string(), PostScriptValue::NativeFn(restore));
+    context.define_system("vmstatus".to_string(), PostScriptValue::NativeFn(vmstatus));
+
+    // Interpreter Information
+    context.define_system("languagelevel".to_string(), PostScriptValue::NativeFn(languagelevel));
+    context.define_system("version".to_string(), PostScriptValue::NativeFn(version));
+    context.define_system("product".to_string(), PostScriptValue::NativeFn(product));
+    context.define_system("revision".to_string(), PostScriptValue::NativeFn(revision));
+    context.define_system("usertime".to_string(), PostScriptValue::NativeFn(usertime));
+    context.define_system("realtime".to_string(), PostScriptValue::NativeFn(realtime));
+    context.define_system("help".to_string(), PostScriptValue::NativeFn(help));
+
+    // Resources (fonts are the one resource category Level 1 knows about)
+    context.define_system("findfont".to_string(), PostScriptValue::NativeFn(findfont));
+
+    // Extensions (non-standard), omitted in strict PLRM mode
+    if !context.is_strict() {
+        context.define_system("deepcopy".to_string(), PostScriptValue::NativeFn(deepcopy));
+        context.define_system("sort".to_string(), PostScriptValue::NativeFn(sort));
+        context.define_system("getenv".to_string(), PostScriptValue::NativeFn(getenv));
+    }
+
+    // Level 2: System Parameters
+    if context.language_level >= 2 {
+        context.define_system("currentsystemparams".to_string(), PostScriptValue::NativeFn(currentsystemparams));
+        context.define_system("setsystemparams".to_string(), PostScriptValue::NativeFn(setsystemparams));
+    }
+
+    // Level 2: Dictionaries and global VM
+    if context.language_level >= 2 {
+        context.define_system("<<".to_string(), PostScriptValue::NativeFn(mark));
+        context.define_system(">>".to_string(), PostScriptValue::NativeFn(dicttomark));
+        context.define_system("dicttomark".to_string(), PostScriptValue::NativeFn(dicttomark));
+        context.define_system("undef".to_string(), PostScriptValue::NativeFn(undef));
+        context.define_system("cleardictstack".to_string(), PostScriptValue::NativeFn(cleardictstack));
+        context.define_system("globaldict".to_string(), PostScriptValue::NativeFn(globaldict));
+        context.define_system("setglobal".to_string(), PostScriptValue::NativeFn(setglobal));
+        context.define_system("currentglobal".to_string(), PostScriptValue::NativeFn(currentglobal));
+    }
+
+    // Level 2: Filters
+    if context.language_level >= 2 {
+        context.define_system("filter".to_string(), PostScriptValue::NativeFn(filter));
+    }
+
+    // Level 2: Resources
+    if context.language_level >= 2 {
+        context.define_system("defineresource".to_string(), PostScriptValue::NativeFn(defineresource));
+        context.define_system("undefineresource".to_string(), PostScriptValue::NativeFn(undefineresource));
+        context.define_system("findresource".to_string(), PostScriptValue::NativeFn(findresource));
+        context.define_system("resourcestatus".to_string(), PostScriptValue::NativeFn(resourcestatus));
+        context.define_system("resourceforall".to_string(), PostScriptValue::NativeFn(resourceforall));
+    }
+
+    // Programs cannot change the system dictionary (define_system still can)
+    let system_dict = PostScriptValue::Dict(context.system_dict());
+    context.restrict_access(&system_dict, Access::ReadOnly);
+}
+
+/// Documentation for one built-in command, used by `help` and the REPL.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperatorDoc {
+    /// Name the command is registered under
+    pub name: &'static str,
+    /// Help category (see `HELP_CATEGORIES`)
+    pub category: &'static str,
+    /// Stack signature, e.g. "any1 any2 exch → any2 any1"
+    pub signature: &'static str,
+    /// One-paragraph description
+    pub description: &'static str,
+}
+
+/// Help categories in display order, with their titles.
+pub const HELP_CATEGORIES: &[(&str, &str)] = &[
+    ("stack", "Stack Manipulation"),
+    ("arithmetic", "Arithmetic"),
+    ("dictionary", "Dictionary"),
+    ("string", "String"),
+    ("array", "Array"),
+    ("boolean", "Boolean/Bit"),
+    ("control", "Flow Control"),
+    ("conversion", "Type Conversion"),
+    ("io", "Input/Output"),
+    ("vm", "Virtual Memory"),
+    ("info", "Interpreter Information"),
+    ("resource", "Resources"),
+    ("extension", "Extensions (non-standard)"),
+];
+
+/// Documentation table for every command installed by `register_builtins`.
+///
+/// Every registered name must have an entry here; new commands are added to
+/// this table at the same time as they are registered.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::types::Context;
+/// use postscript_interpreter::commands::{register_builtins, operator_doc};
+///
+/// let mut context = Context::new(false);
+/// register_builtins(&mut context);
+/// for name in context.system_dict().borrow().keys() {
+///     assert!(operator_doc(name).is_some(), "missing help entry for {}", name);
+/// }
+/// ```
+pub const OPERATOR_DOCS: &[OperatorDoc] = &[
+    // Stack Manipulation
+    OperatorDoc { name: "exch", category: "stack", signature: "any1 any2 exch → any2 any1", description: "Exchanges the top two items on the operand stack." },
+    OperatorDoc { name: "pop", category: "stack", signature: "any pop → –", description: "Removes the top item from the operand stack." },
+    OperatorDoc { name: "copy", category: "stack", signature: "any1 … anyn n copy → any1 … anyn any1 … anyn, array1 array2 copy → subarray2, string1 string2 copy → substring2, dict1 dict2 copy → dict2", description: "Duplicates the top n items on the operand stack, or copies the elements of an array, string or dictionary into the start of another and pushes the part filled." },
+    OperatorDoc { name: "dup", category: "stack", signature: "any dup → any any", description: "Duplicates the top item on the operand stack." },
+    OperatorDoc { name: "clear", category: "stack", signature: "any1 … anyn clear → –", description: "Removes all items from the operand stack." },
+    OperatorDoc { name: "count", category: "stack", signature: "any1 … anyn count → any1 … anyn n", description: "Pushes the number of items on the operand stack." },
+    OperatorDoc { name: "index", category: "stack", signature: "anyn … any0 n index → anyn … any0 anyn", description: "Pushes a copy of the item n positions below the top; 0 index is the same as dup." },
+    OperatorDoc { name: "roll", category: "stack", signature: "anyn-1 … any0 n j roll → any(j-1) mod n … any0 anyn-1 … anyj mod n", description: "Rotates the top n items by j positions: positive j towards the top, negative j away from it." },
+    OperatorDoc { name: "mark", category: "stack", signature: "– mark → mark", description: "Pushes a mark, which cleartomark, counttomark and ] look for." },
+    OperatorDoc { name: "cleartomark", category: "stack", signature: "mark obj1 … objn cleartomark → –", description: "Removes items from the operand stack down to and including the topmost mark." },
+    OperatorDoc { name: "counttomark", category: "stack", signature: "mark obj1 … objn counttomark → mark obj1 … objn n", description: "Pushes the number of items above the topmost mark." },
 
     // Arithmetic
-    context.define("add".to_string(), PostScriptValue::NativeFn(add));
-    context.define("sub".to_string(), PostScriptValue::NativeFn(sub));
-    context.define("mul".to_string(), PostScriptValue::NativeFn(mul));
-    context.define("div".to_string(), PostScriptValue::NativeFn(div));
-    context.define("idiv".to_string(), PostScriptValue::NativeFn(idiv));
-    context.define("mod".to_string(), PostScriptValue::NativeFn(mod_op));
-    context.define("abs".to_string(), PostScriptValue::NativeFn(abs));
-    context.define("neg".to_string(), PostScriptValue::NativeFn(neg));
-    context.define("ceiling".to_string(), PostScriptValue::NativeFn(ceiling));
-    context.define("floor".to_string(), PostScriptValue::NativeFn(floor));
-    context.define("round".to_string(), PostScriptValue::NativeFn(round));
-    context.define("sqrt".to_string(), PostScriptValue::NativeFn(sqrt));
+    OperatorDoc { name: "add", category: "arithmetic", signature: "num1 num2 add → sum", description: "Adds two numbers. The result is an integer if both operands are integers, otherwise a real." },
+    OperatorDoc { name: "sub", category: "arithmetic", signature: "num1 num2 sub → difference", description: "Subtracts num2 from num1." },
+    OperatorDoc { name: "mul", category: "arithmetic", signature: "num1 num2 mul → product", description: "Multiplies two numbers." },
+    OperatorDoc { name: "div", category: "arithmetic", signature: "num1 num2 div → quotient", description: "Divides num1 by num2. The result is always a real." },
+    OperatorDoc { name: "idiv", category: "arithmetic", signature: "int1 int2 idiv → quotient", description: "Divides int1 by int2 and truncates the result to an integer." },
+    OperatorDoc { name: "mod", category: "arithmetic", signature: "int1 int2 mod → remainder", description: "Pushes the remainder of dividing int1 by int2." },
+    OperatorDoc { name: "abs", category: "arithmetic", signature: "num abs → num", description: "Pushes the absolute value of num." },
+    OperatorDoc { name: "neg", category: "arithmetic", signature: "num neg → num", description: "Pushes the negation of num." },
+    OperatorDoc { name: "ceiling", category: "arithmetic", signature: "num ceiling → num", description: "Rounds num up to the nearest integer value." },
+    OperatorDoc { name: "floor", category: "arithmetic", signature: "num floor → num", description: "Rounds num down to the nearest integer value." },
+    OperatorDoc { name: "round", category: "arithmetic", signature: "num round → num", description: "Rounds num to the nearest integer value." },
+    OperatorDoc { name: "truncate", category: "arithmetic", signature: "num truncate → num", description: "Removes the fractional part of num, rounding toward zero. The result has the type of num." },
+    OperatorDoc { name: "sqrt", category: "arithmetic", signature: "num sqrt → real", description: "Pushes the square root of num." },
+    OperatorDoc { name: "sin", category: "arithmetic", signature: "angle sin → real", description: "Pushes the sine of angle, given in degrees." },
+    OperatorDoc { name: "cos", category: "arithmetic", signature: "angle cos → real", description: "Pushes the cosine of angle, given in degrees." },
+    OperatorDoc { name: "atan", category: "arithmetic", signature: "num den atan → angle", description: "Pushes the angle in degrees, in the range 0 to 360, whose tangent is num/den. The signs of num and den select the quadrant." },
+    OperatorDoc { name: "exp", category: "arithmetic", signature: "base exponent exp → real", description: "Raises base to the power exponent. A result that is not a real number (such as 0 to a negative power) is an undefined result." },
+    OperatorDoc { name: "ln", category: "arithmetic", signature: "num ln → real", description: "Pushes the natural logarithm of num, which must be positive." },
+    OperatorDoc { name: "log", category: "arithmetic", signature: "num log → real", description: "Pushes the base-10 logarithm of num, which must be positive." },
+    OperatorDoc { name: "rand", category: "arithmetic", signature: "– rand → int", description: "Pushes the next pseudo-random integer, between 0 and 2^31 - 1." },
+    OperatorDoc { name: "srand", category: "arithmetic", signature: "int srand → –", description: "Seeds the random number generator, so the sequence rand produces can be repeated." },
+    OperatorDoc { name: "rrand", category: "arithmetic", signature: "– rrand → int", description: "Pushes the random number generator's current state, which srand accepts to resume the sequence from there." },
 
     // Dictionary
-    context.define("dict".to_string(), PostScriptValue::NativeFn(dict));
-    context.define("length".to_string(), PostScriptValue::NativeFn(length));
-    context.define("maxlength".to_string(), PostScriptValue::NativeFn(maxlength));
-    context.define("begin".to_string(), PostScriptValue::NativeFn(begin));
-    context.define("end".to_string(), PostScriptValue::NativeFn(end));
-    context.define("def".to_string(), PostScriptValue::NativeFn(def));
+    OperatorDoc { name: "dict", category: "dictionary", signature: "int dict → dict", description: "Creates an empty dictionary with capacity for int entries." },
+    OperatorDoc { name: "length", category: "dictionary", signature: "dict|string|array|name length → int", description: "Pushes the number of entries, characters or elements in the object." },
+    OperatorDoc { name: "maxlength", category: "dictionary", signature: "dict maxlength → int", description: "Pushes the capacity of the dictionary: the size given to dict, or its length once it has grown beyond that. At language level 1 (and in strict mode) dictionaries do not grow, and adding a key to a full one is a dictfull error." },
+    OperatorDoc { name: "begin", category: "dictionary", signature: "dict begin → –", description: "Pushes the dictionary onto the dictionary stack, making it the current dictionary." },
+    OperatorDoc { name: "end", category: "dictionary", signature: "– end → –", description: "Pops the current dictionary off the dictionary stack." },
+    OperatorDoc { name: "def", category: "dictionary", signature: "key value def → –", description: "Associates key with value in the current dictionary." },
+    OperatorDoc { name: "known", category: "dictionary", signature: "dict key known → bool", description: "Pushes true if dict has an entry for key." },
+    OperatorDoc { name: "where", category: "dictionary", signature: "key where → dict true, key where → false", description: "Finds the topmost dictionary on the dictionary stack that defines key and pushes it and true, or pushes false if none does." },
+    OperatorDoc { name: "load", category: "dictionary", signature: "key load → value", description: "Looks up key on the dictionary stack and pushes its value without executing it." },
+    OperatorDoc { name: "store", category: "dictionary", signature: "key value store → –", description: "Replaces the value of key in the topmost dictionary that defines it, or defines it in the current dictionary if none does." },
+    OperatorDoc { name: "currentdict", category: "dictionary", signature: "– currentdict → dict", description: "Pushes the current dictionary (the top of the dictionary stack)." },
+    OperatorDoc { name: "countdictstack", category: "dictionary", signature: "– countdictstack → int", description: "Pushes the number of dictionaries on the dictionary stack." },
+    OperatorDoc { name: "dictstack", category: "dictionary", signature: "array dictstack → subarray", description: "Stores the dictionaries on the dictionary stack, bottom first, into the start of array and pushes an array of them." },
+    OperatorDoc { name: "undef", category: "dictionary", signature: "dict key undef → –", description: "Removes key from dict (Level 2). Nothing happens if dict has no such key." },
+    OperatorDoc { name: "cleardictstack", category: "dictionary", signature: "– cleardictstack → –", description: "Pops every dictionary pushed with begin (Level 2), leaving the permanent dictionaries (system, user and library dictionaries)." },
+    OperatorDoc { name: "systemdict", category: "dictionary", signature: "– systemdict → dict", description: "Pushes the system dictionary, which holds the built-in operators. It is read-only." },
+    OperatorDoc { name: "userdict", category: "dictionary", signature: "– userdict → dict", description: "Pushes the user dictionary, which receives definitions made outside any begin/end pair." },
+    OperatorDoc { name: "<<", category: "dictionary", signature: "– << → mark", description: "Pushes a mark to start a dictionary (Level 2): << /a 1 /b 2 >>." },
+    OperatorDoc { name: ">>", category: "dictionary", signature: "mark key1 value1 … keyn valuen >> → dict", description: "Builds a dictionary from the key/value pairs above the topmost mark, removing them and the mark (Level 2). Same as dicttomark." },
+    OperatorDoc { name: "dicttomark", category: "dictionary", signature: "mark key1 value1 … keyn valuen dicttomark → dict", description: "Builds a dictionary from the key/value pairs above the topmost mark (Level 2). An odd number of items is a rangecheck error; a later pair replaces an earlier one with the same key." },
+    OperatorDoc { name: "globaldict", category: "dictionary", signature: "– globaldict → dict", description: "Pushes the global dictionary (Level 2). It is not on the dictionary stack, but names are looked up in it right after userdict. restore does not roll it back." },
 
     // String
-    context.define("get".to_string(), PostScriptValue::NativeFn(get));
-    context.define("getinterval".to_string(), PostScriptValue::NativeFn(getinterval));
-    context.define("putinterval".to_string(), PostScriptValue::NativeFn(putinterval));
+    OperatorDoc { name: "get", category: "string", signature: "string|array index get → any, dict key get → any", description: "Pushes the element at index (for a string, its character code 0-255), or the value stored under key in a dictionary." },
+    OperatorDoc { name: "put", category: "string", signature: "string index int put → –, array index any put → –, dict key value put → –", description: "Stores a character code (0-255) into a string, an element into an array, or a value under key in a dictionary. A code outside 0-255 is a rangecheck error." },
+    OperatorDoc { name: "getinterval", category: "string", signature: "string index count getinterval → substring, array index count getinterval → subarray", description: "Pushes a new string or array holding the count characters or elements starting at index." },
+    OperatorDoc { name: "putinterval", category: "string", signature: "string1 index string2 putinterval → –, array1 index array2 putinterval → –", description: "Overwrites string1 (array1) starting at index with the characters (elements) of string2 (array2)." },
+    OperatorDoc { name: "string", category: "string", signature: "int string → string", description: "Creates a string of int zero bytes, up to MaxStringLength." },
+    OperatorDoc { name: "search", category: "string", signature: "string seek search → post match pre true, string seek search → string false", description: "Looks for the first occurrence of seek in string. If found, splits string into the part after it, the match and the part before it." },
+    OperatorDoc { name: "anchorsearch", category: "string", signature: "string seek anchorsearch → post match true, string seek anchorsearch → string false", description: "Tests whether string starts with seek. If it does, splits string into the part after it and the match." },
+
+    // Array
+    OperatorDoc { name: "array", category: "array", signature: "int array → array", description: "Creates an array of int null elements, up to MaxArrayLength." },
+    OperatorDoc { name: "aload", category: "array", signature: "array aload → any0 … anyn-1 array", description: "Pushes all elements of the array (or procedure), then the array itself." },
+    OperatorDoc { name: "astore", category: "array", signature: "any0 … anyn-1 array astore → array", description: "Stores the n items below the array into it, where n is its length, and pushes the array." },
+    OperatorDoc { name: "[", category: "array", signature: "– [ → mark", description: "Pushes a mark that starts an array literal." },
+    OperatorDoc { name: "]", category: "array", signature: "mark obj0 … objn-1 ] → array", description: "Builds an array from the items above the topmost mark, removing them and the mark." },
 
     // Boolean/Bit
-    context.define("eq".to_string(), PostScriptValue::NativeFn(eq));
-    context.define("ne".to_string(), PostScriptValue::NativeFn(ne));
-    context.define("ge".to_string(), PostScriptValue::NativeFn(ge));
-    context.define("gt".to_string(), PostScriptValue::NativeFn(gt));
-    context.define("le".to_string(), PostScriptValue::NativeFn(le));
-    context.define("lt".to_string(), PostScriptValue::NativeFn(lt));
-    context.define("and".to_string(), PostScriptValue::NativeFn(and));
-    context.define("not".to_string(), PostScriptValue::NativeFn(not));
-    context.define("or".to_string(), PostScriptValue::NativeFn(or));
-    context.define("true".to_string(), PostScriptValue::Bool(true));
-    context.define("false".to_string(), PostScriptValue::Bool(false));
+    OperatorDoc { name: "eq", category: "boolean", signature: "any1 any2 eq → bool", description: "Tests whether two values are equal." },
+    OperatorDoc { name: "ne", category: "boolean", signature: "any1 any2 ne → bool", description: "Tests whether two values are not equal." },
+    OperatorDoc { name: "ge", category: "boolean", signature: "num1|str1 num2|str2 ge → bool", description: "Tests whether the first operand is greater than or equal to the second." },
+    OperatorDoc { name: "gt", category: "boolean", signature: "num1|str1 num2|str2 gt → bool", description: "Tests whether the first operand is greater than the second." },
+    OperatorDoc { name: "le", category: "boolean", signature: "num1|str1 num2|str2 le → bool", description: "Tests whether the first operand is less than or equal to the second." },
+    OperatorDoc { name: "lt", category: "boolean", signature: "num1|str1 num2|str2 lt → bool", description: "Tests whether the first operand is less than the second." },
+    OperatorDoc { name: "and", category: "boolean", signature: "bool1|int1 bool2|int2 and → bool|int", description: "Logical AND of booleans or bitwise AND of integers." },
+    OperatorDoc { name: "not", category: "boolean", signature: "bool|int not → bool|int", description: "Logical NOT of a boolean or bitwise complement of an integer." },
+    OperatorDoc { name: "or", category: "boolean", signature: "bool1|int1 bool2|int2 or → bool|int", description: "Logical OR of booleans or bitwise OR of integers." },
+    OperatorDoc { name: "true", category: "boolean", signature: "– true → true", description: "Pushes the boolean value true." },
+    OperatorDoc { name: "false", category: "boolean", signature: "– false → false", description: "Pushes the boolean value false." },
+    OperatorDoc { name: "null", category: "boolean", signature: "– null → null", description: "Pushes the null object, which also fills newly created arrays." },
 
     // Flow Control
-    context.define("if".to_string(), PostScriptValue::NativeFn(if_op));
-    context.define("ifelse".to_string(), PostScriptValue::NativeFn(ifelse));
-    context.define("for".to_string(), PostScriptValue::NativeFn(for_op));
-    context.define("repeat".to_string(), PostScriptValue::NativeFn(repeat));
-    context.define("quit".to_string(), PostScriptValue::NativeFn(quit));
+    OperatorDoc { name: "if", category: "control", signature: "bool proc if → –", description: "Executes proc if bool is true." },
+    OperatorDoc { name: "ifelse", category: "control", signature: "bool proc1 proc2 ifelse → –", description: "Executes proc1 if bool is true, otherwise proc2." },
+    OperatorDoc { name: "for", category: "control", signature: "initial increment limit proc for → –", description: "Executes proc for each value from initial to limit in steps of increment, pushing the value before each execution." },
+    OperatorDoc { name: "forall", category: "control", signature: "array|string|dict proc forall → –", description: "Executes proc for each element of an array, each character code of a string, or each key and value of a dictionary." },
+    OperatorDoc { name: "repeat", category: "control", signature: "int proc repeat → –", description: "Executes proc int times." },
+    OperatorDoc { name: "exec", category: "control", signature: "any exec → –", description: "Executes any: procedures run, operators are called, names are looked up and executable strings and files are scanned and run. null does nothing; other literal values are pushed back." },
+    OperatorDoc { name: "stop", category: "control", signature: "– stop → –", description: "Terminates the innermost enclosing stopped context, which then pushes true. An error if no stopped context is active." },
+    OperatorDoc { name: "stopped", category: "control", signature: "proc stopped → bool", description: "Executes proc, then pushes false if it completed, or true if it executed stop or failed with an error. Interrupts and execution limits are not caught." },
+    OperatorDoc { name: "loop", category: "control", signature: "proc loop → –", description: "Executes proc over and over, until it executes exit." },
+    OperatorDoc { name: "exit", category: "control", signature: "– exit → –", description: "Leaves the innermost for, repeat, loop or forall, abandoning the rest of its procedure. Leaving a stopped context this way is an invalidexit error." },
+    OperatorDoc { name: "bind", category: "control", signature: "proc bind → proc", description: "Replaces every name in proc, and in the procedures nested in it, that currently refers to an operator by the operator itself, so later redefinitions do not affect it." },
+    OperatorDoc { name: "countexecstack", category: "control", signature: "– countexecstack → int", description: "Pushes the number of entries on the execution stack: what remains to be executed of every active procedure, loop and file." },
+    OperatorDoc { name: "execstack", category: "control", signature: "array execstack → subarray", description: "Stores the execution stack, bottom first, into the start of array and pushes an array of the entries. Loop states and other internal entries appear as names such as --for-loop--." },
+    OperatorDoc { name: "quit", category: "control", signature: "– quit → –", description: "Terminates the interpreter." },
+
+    // Type Conversion
+    OperatorDoc { name: "cvs", category: "conversion", signature: "any string cvs → substring", description: "Writes the text of a number, boolean, name or string into the start of string and pushes the part used. Other objects give --nostringval--." },
+    OperatorDoc { name: "cvrs", category: "conversion", signature: "num radix string cvrs → substring", description: "Writes num in radix 2-36 (digits 0-9, A-Z) into the start of string and pushes the part used. Radix 10 writes what cvs does; other radices truncate reals and write negative numbers as 64-bit two's complement." },
+    OperatorDoc { name: "cvi", category: "conversion", signature: "num|string cvi → int", description: "Converts a number, or a string holding one, to an integer, truncating toward zero." },
+    OperatorDoc { name: "cvr", category: "conversion", signature: "num|string cvr → real", description: "Converts a number, or a string holding one, to a real." },
+    OperatorDoc { name: "type", category: "conversion", signature: "any type → name", description: "Pushes the name of the type of any, such as /integertype, /arraytype or /nulltype." },
+    OperatorDoc { name: "cvx", category: "conversion", signature: "any cvx → any", description: "Makes any executable: an array becomes a procedure, a literal name an executable name, a string an executable string sharing the same characters and a file an executable file that runs its contents when executed." },
+    OperatorDoc { name: "cvlit", category: "conversion", signature: "any cvlit → any", description: "Makes any literal: a procedure becomes an array, an executable name a literal name, an executable string a string sharing the same characters and an executable file a file." },
+    OperatorDoc { name: "xcheck", category: "conversion", signature: "any xcheck → bool", description: "Pushes true if any is executable: an executable name, a procedure, an executable string or an operator." },
+    OperatorDoc { name: "readonly", category: "conversion", signature: "composite readonly → composite", description: "Makes a dictionary, array or string read-only: put, def and other changes to it fail with invalidaccess. Applies to every reference to the object." },
+    OperatorDoc { name: "executeonly", category: "conversion", signature: "array|string executeonly → array|string", description: "Makes an array or string execute-only: it can be executed but not read or changed." },
+    OperatorDoc { name: "noaccess", category: "conversion", signature: "composite noaccess → composite", description: "Makes a dictionary, array or string inaccessible: it can no longer be read, changed or executed." },
+    OperatorDoc { name: "rcheck", category: "conversion", signature: "composite rcheck → bool", description: "Pushes true if the dictionary, array or string can be read." },
+    OperatorDoc { name: "wcheck", category: "conversion", signature: "composite wcheck → bool", description: "Pushes true if the dictionary, array or string can be changed." },
+    OperatorDoc { name: "cvn", category: "conversion", signature: "string cvn → name", description: "Converts a string to a name with the same characters: literal for a string, executable for an executable string." },
 
     // I/O
-    context.define("print".to_string(), PostScriptValue::NativeFn(print));
-    context.define("=".to_string(), PostScriptValue::NativeFn(eq_print));
-    context.define("==".to_string(), PostScriptValue::NativeFn(eqeq_print));
+    OperatorDoc { name: "print", category: "io", signature: "string print → –", description: "Writes the characters of string to standard output." },
+    OperatorDoc { name: "=", category: "io", signature: "any = → –", description: "Prints a text representation of any followed by a newline: the text cvs gives (strings as their text, names without a slash, composite objects as --nostringval--)." },
+    OperatorDoc { name: "==", category: "io", signature: "any == → –", description: "Prints the PostScript representation of any followed by a newline: strings in parentheses with escapes, literal names with a slash, reals with a decimal point, arrays and procedures with their elements, dictionaries as -dict-." },
+    OperatorDoc { name: "stack", category: "io", signature: "any1 … anyn stack → any1 … anyn", description: "Prints every operand, top first, the way = does, leaving the stack unchanged." },
+    OperatorDoc { name: "pstack", category: "io", signature: "any1 … anyn pstack → any1 … anyn", description: "Prints every operand, top first, the way == does, leaving the stack unchanged." },
+    OperatorDoc { name: "executive", category: "io", signature: "– executive → –", description: "Runs an interactive loop: executes prompt, reads a line of input and executes it, printing errors instead of stopping. Returns at the end of the input." },
+    OperatorDoc { name: "prompt", category: "io", signature: "– prompt → –", description: "Prints the prompt of executive (PS> ). Define prompt in userdict to change it." },
+    OperatorDoc { name: "file", category: "io", signature: "name access file → file", description: "Opens the file name for reading (access (r)), writing (w) or appending (a). %stdin, %stdout and %stderr are the standard streams. A missing file is an undefinedfilename error." },
+    OperatorDoc { name: "read", category: "io", signature: "file read → int true, file read → false", description: "Reads one byte. Pushes false at the end of the file." },
+    OperatorDoc { name: "currentfile", category: "io", signature: "– currentfile → file", description: "Pushes the file being executed, positioned right after the currentfile operator, so a program can read data embedded in it (currentfile 16 string readhexstring). Outside of a script file this is %stdin." },
+    OperatorDoc { name: "readline", category: "io", signature: "file string readline → substring bool", description: "Reads a line into the start of string, without its newline, and pushes the part used. bool is false if the file ended before a newline. A line longer than string is a rangecheck error." },
+    OperatorDoc { name: "readstring", category: "io", signature: "file string readstring → substring bool", description: "Fills string with bytes from file and pushes the part filled. bool is false if the file ended before string was full." },
+    OperatorDoc { name: "readhexstring", category: "io", signature: "file string readhexstring → substring bool", description: "Fills string with bytes given as pairs of hexadecimal digits in file, skipping any other characters. bool is false if the file ended before string was full." },
+    OperatorDoc { name: "write", category: "io", signature: "file int write → –", description: "Writes one byte (0-255)." },
+    OperatorDoc { name: "writestring", category: "io", signature: "file string writestring → –", description: "Writes the characters of string, as print does." },
+    OperatorDoc { name: "closefile", category: "io", signature: "file closefile → –", description: "Flushes and closes the file. Reading a closed file gives end of file; writing to it is an ioerror." },
+    OperatorDoc { name: "flush", category: "io", signature: "– flush → –", description: "Writes out what has been printed but is still buffered." },
+    OperatorDoc { name: "flushfile", category: "io", signature: "file flushfile → –", description: "Writes out the buffered data of a file opened for writing. For a file opened for reading, reads and discards the rest of its data." },
+    OperatorDoc { name: "filter", category: "io", signature: "source name filter → file, target name filter → file", description: "Makes a file that decodes the data of source (a file or string) or encodes what is written to it into target (a file or string, filled from the start). Filters: ASCIIHexDecode, ASCIIHexEncode, ASCII85Decode, ASCII85Encode, RunLengthDecode and RunLengthEncode (target recordSize /RunLengthEncode filter, recordSize defaulting to 0). Decoding stops at the end-of-data marker; closing an encode filter ends its data." },
+
+    // Interpreter Information
+    OperatorDoc { name: "save", category: "vm", signature: "– save → save", description: "Takes a snapshot of the contents of every dictionary, array and string reachable from the stacks." },
+    OperatorDoc { name: "restore", category: "vm", signature: "save restore → –", description: "Rolls the objects of a snapshot back to their contents at save time, invalidating it and later snapshots. Fails if the operand or dictionary stack holds an object created after the save." },
+    OperatorDoc { name: "vmstatus", category: "vm", signature: "– vmstatus → level used maximum", description: "Pushes the save level, an estimate of the bytes taken by the strings, arrays and dictionaries allocated so far, and the most they may take (system parameter MaxLocalVM). Going over it is a VMerror." },
+    OperatorDoc { name: "setglobal", category: "vm", signature: "bool setglobal → –", description: "Selects global (true) or local (false) allocation (Level 2). In global mode, definitions that would go into userdict go into globaldict instead, so they survive restore." },
+    OperatorDoc { name: "currentglobal", category: "vm", signature: "– currentglobal → bool", description: "Pushes true if global allocation is selected (Level 2)." },
+    OperatorDoc { name: "languagelevel", category: "info", signature: "– languagelevel → int", description: "Pushes the PostScript language level being emulated." },
+    OperatorDoc { name: "version", category: "info", signature: "– version → string", description: "Pushes the interpreter's version as a string (the version shown by --version)." },
+    OperatorDoc { name: "product", category: "info", signature: "– product → string", description: "Pushes the interpreter's name as a string." },
+    OperatorDoc { name: "revision", category: "info", signature: "– revision → int", description: "Pushes the interpreter's revision number." },
+    OperatorDoc { name: "usertime", category: "info", signature: "– usertime → int", description: "Pushes the execution time in milliseconds. Only differences between two values are meaningful." },
+    OperatorDoc { name: "realtime", category: "info", signature: "– realtime → int", description: "Pushes the wall-clock time in milliseconds. Only differences between two values are meaningful." },
+    OperatorDoc { name: "currentsystemparams", category: "info", signature: "– currentsystemparams → dict", description: "Pushes a dictionary holding the interpreter limits MaxOpStack, MaxExecStack, MaxDictStack, MaxStringLength, MaxArrayLength, MaxDictLength and MaxLocalVM, the remaining execution budget Fuel (when there is one), and the read-only flags Strict and EnvironmentAccess." },
+    OperatorDoc { name: "setsystemparams", category: "info", signature: "dict setsystemparams → –", description: "Applies the interpreter limits found in dict. Values must be positive integers; Fuel can only be lowered, the read-only flags raise invalidaccess, and unknown keys are ignored." },
+    OperatorDoc { name: "help", category: "info", signature: "/name help → –, /category help → –, help → –", description: "Prints the description of an operator, the operators in a category, or the list of categories." },
+
+    // Resources
+    OperatorDoc { name: "findfont", category: "resource", signature: "key findfont → font", description: "Pushes the font dictionary defined as a Font resource under key. Same as key /Font findresource." },
+    OperatorDoc { name: "defineresource", category: "resource", signature: "key instance category defineresource → instance", description: "Registers instance under key in the resource category (Font, ProcSet, Generic, Category or a category defined in Category). Font and Category instances must be dictionaries." },
+    OperatorDoc { name: "undefineresource", category: "resource", signature: "key category undefineresource → –", description: "Removes the resource registered under key in the category, if any. Removing a Category instance removes that category." },
+    OperatorDoc { name: "findresource", category: "resource", signature: "key category findresource → instance", description: "Pushes the resource registered under key in the category, or raises undefinedresource." },
+    OperatorDoc { name: "resourcestatus", category: "resource", signature: "key category resourcestatus → status size true, key category resourcestatus → false", description: "Tests whether a resource is defined. Defined resources report status 1 (in VM) and size 0." },
+    OperatorDoc { name: "resourceforall", category: "resource", signature: "template proc scratch category resourceforall → –", description: "Executes proc with the name of each resource in the category that matches template (* and ? are wildcards), in sorted order. Names are passed as strings no longer than scratch." },
+
+    // Extensions (non-standard)
+    OperatorDoc { name: "deepcopy", category: "extension", signature: "any deepcopy → copy", description: "Recursively copies dictionaries, arrays and strings, preserving shared and self-referential structure." },
+    OperatorDoc { name: "sort", category: "extension", signature: "array sort → array, array proc sort → array", description: "Stable sort, in place for an array (a procedure is copied). Without proc, numbers sort by value, strings by bytes and names by text; mixing them is a type check error. With proc, a b proc must leave true if a has to come before b." },
+    OperatorDoc { name: "getenv", category: "extension", signature: "string getenv → value true, string getenv → false", description: "Looks up an environment variable, as in Ghostscript. Pushes its value and true, or false if it is not set. Embedders can replace or hide the environment (Context::set_env_provider)." },
+];
+
+/// Looks up the documentation for a built-in command.
+pub fn operator_doc(name: &str) -> Option<&'static OperatorDoc> {
+    OPERATOR_DOCS.iter().find(|doc| doc.name == name)
+}
+
+/// Produces the help text for a topic.
+///
+/// - None: the list of categories
+/// - A category name: the operators in that category
+/// - An operator name: its signature and description
+///
+/// Returns an error if the topic is neither a category nor a documented operator.
+pub fn help_text(topic: Option<&str>) -> Result<String, String> {
+    let topic = match topic {
+        None => {
+            let mut text = String::from("Categories (use /category help for the operators in one):\n");
+            for (id, title) in HELP_CATEGORIES {
+                text.push_str(&format!("  {:<12}{}\n", id, title));
+            }
+            return Ok(text);
+        }
+        Some(topic) => topic,
+    };
+    if let Some((id, title)) = HELP_CATEGORIES.iter().find(|(id, _)| id.eq_ignore_ascii_case(topic)) {
+        let names: Vec<&str> = OPERATOR_DOCS.iter()
+            .filter(|doc| doc.category == *id)
+            .map(|doc| doc.name)
+            .collect();
+        return Ok(format!("{}: {}\n", title, names.join(" ")));
+    }
+    match operator_doc(topic) {
+        Some(doc) => Ok(format!("{}: {}\n{}\n", doc.name, doc.signature, doc.description)),
+        None => Err(format!("No help for {}", topic)),
+    }
 }
 
 // ============================================================================
@@ -110,12 +523,7 @@ pub fn register_builtins(context: &mut Context) {
 /// exch: Exchange the top two items on the stack
 /// Stack: any1 any2 → any2 any1
 fn exch(ctx: &mut Context) -> Result<(), String> {
-    if ctx.operand_stack.len() < 2 {
-        return Err("Stack underflow".to_string());
-    }
-    let len = ctx.operand_stack.len();
-    ctx.operand_stack.swap(len - 1, len - 2);
-    Ok(())
+    ctx.swap()
 }
 
 /// pop: Remove the top item from the stack
@@ -125,40 +533,94 @@ fn pop(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
-/// copy: Copy the top n items on the stack
+/// copy: Copy the top n items on the stack, or the contents of a composite object
 /// Stack: any[0] ... any[n-1] n → any[0] ... any[n-1] any[0] ... any[n-1]
-/// 
-/// Note: Object copy forms (dict/array/string copy) are not implemented.
-/// Only stack copy (n items) is supported.
+///        array1 array2 → subarray2, string1 string2 → substring2, dict1 dict2 → dict2
+///
+/// The composite forms copy the elements of the first object into the start of the
+/// second (entries, for dictionaries) and push the part filled. As with getinterval,
+/// the subarray and substring are new objects holding the copied elements. The copy
+/// is shallow: nested composites are shared, not duplicated (see deepcopy).
+/// A failed copy leaves the stack untouched.
 fn copy(ctx: &mut Context) -> Result<(), String> {
-    let top = ctx.pop().ok_or("Stack underflow".to_string())?;
-    match top {
-        PostScriptValue::Int(n) => {
-            // Stack copy: duplicate the top n items
-            let n = n as usize;
-            if ctx.operand_stack.len() < n {
-                return Err("Stack underflow".to_string());
-            }
-            let len = ctx.operand_stack.len();
-            for i in 0..n {
-                let val = ctx.operand_stack[len - n + i].clone();
-                ctx.push(val);
-            }
+    let len = ctx.operand_count();
+    let top = ctx.peek().ok_or("Stack underflow".to_string())?.clone();
+    if let PostScriptValue::Int(n) = top {
+        // Stack copy: duplicate the top n items
+        if n < 0 {
+            return Err("Range check error: copy count must not be negative".to_string());
         }
-        _ => {
-            // Object copy forms (dict/array/string) are not implemented
-            match top {
-                PostScriptValue::Dict(_) | PostScriptValue::String(_) | PostScriptValue::Array(_) => {
-                    let _src = ctx.pop().ok_or("Stack underflow".to_string())?;
-                    return Err("Object copy not fully implemented".to_string());
-                }
-                _ => return Err("Type check error: copy expected int".to_string()),
-            }
+        let n = n as usize;
+        if len - 1 < n {
+            return Err("Stack underflow".to_string());
         }
+        ctx.pop();
+        for i in 0..n {
+            let val = ctx.operand_stack()[len - 1 - n + i].clone();
+            ctx.push(val);
+        }
+        return Ok(());
+    }
+
+    if len < 2 {
+        return Err("Stack underflow".to_string());
     }
+    let source = &ctx.operand_stack()[len - 2];
+    if matches!(top, PostScriptValue::Array(_) | PostScriptValue::String(_) | PostScriptValue::Dict(_)) {
+        ctx.check_access(source, Access::ReadOnly, "copy")?;
+        ctx.check_access(&top, Access::Unlimited, "copy")?;
+    }
+    let result = match (source, &top) {
+        (PostScriptValue::Array(src), PostScriptValue::Array(dest)) => {
+            let items = src.borrow().clone();
+            copy_elements(items, dest)?
+        }
+        (PostScriptValue::Block(items) | PostScriptValue::Closure { body: items, .. }, PostScriptValue::Array(dest)) => {
+            copy_elements(items.clone(), dest)?
+        }
+        (PostScriptValue::String(src), PostScriptValue::String(dest)) => {
+            let src_chars: Vec<char> = src.borrow().chars().collect();
+            let mut dest_chars: Vec<char> = dest.borrow().chars().collect();
+            if src_chars.len() > dest_chars.len() {
+                return Err(format!("Range check error: {} characters do not fit in a string of length {}", src_chars.len(), dest_chars.len()));
+            }
+            dest_chars[..src_chars.len()].copy_from_slice(&src_chars);
+            *dest.borrow_mut() = dest_chars.into_iter().collect();
+            PostScriptValue::String(Rc::new(RefCell::new(src_chars.into_iter().collect())))
+        }
+        (PostScriptValue::Dict(src), PostScriptValue::Dict(dest)) => {
+            // Collect first so that copying a dictionary into itself does not hold a
+            // shared and a mutable borrow of the same RefCell
+            let entries: Vec<(String, PostScriptValue)> = src.borrow().iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            dest.borrow_mut().extend(entries);
+            top.clone()
+        }
+        (a, b) => {
+            return Err(format!(
+                "Type check error: copy expected an int, or two arrays, strings or dicts, got {} and {}",
+                a.type_name(),
+                b.type_name()
+            ));
+        }
+    };
+    ctx.pop();
+    ctx.pop();
+    ctx.push(result);
     Ok(())
 }
 
+/// Copies items into the start of an array for copy, returning the subarray filled.
+fn copy_elements(items: Vec<PostScriptValue>, dest: &Rc<RefCell<Vec<PostScriptValue>>>) -> Result<PostScriptValue, String> {
+    let dest_len = dest.borrow().len();
+    if items.len() > dest_len {
+        return Err(format!("Range check error: {} elements do not fit in an array of length {}", items.len(), dest_len));
+    }
+    dest.borrow_mut()[..items.len()].clone_from_slice(&items);
+    Ok(PostScriptValue::Array(Rc::new(RefCell::new(items))))
+}
+
 /// dup: Duplicate the top item on the stack
 /// Stack: any → any any
 fn dup(ctx: &mut Context) -> Result<(), String> {
@@ -170,30 +632,69 @@ fn dup(ctx: &mut Context) -> Result<(), String> {
 /// clear: Remove all items from the operand stack
 /// Stack: any[1] ... any[n] → (empty)
 fn clear(ctx: &mut Context) -> Result<(), String> {
-    ctx.operand_stack.clear();
+    ctx.clear_operands();
     Ok(())
 }
 
 /// count: Push the number of items on the stack
 /// Stack: any[1] ... any[n] → any[1] ... any[n] n
 fn count(ctx: &mut Context) -> Result<(), String> {
-    let n = ctx.operand_stack.len() as i64;
+    let n = ctx.operand_count() as i64;
     ctx.push(PostScriptValue::Int(n));
     Ok(())
 }
 
+/// index: Copy the nth item from the top of the stack
+/// Stack: any[n] ... any[0] n → any[n] ... any[0] any[n]
+/// 0 index duplicates the top item
+fn index(ctx: &mut Context) -> Result<(), String> {
+    let n = match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Int(n) => n,
+        _ => return Err("Type check error: index expected int".to_string()),
+    };
+    let depth = ctx.operand_count();
+    if n < 0 || n as usize >= depth {
+        return Err(format!("Range check error: index {} out of range for stack depth {}", n, depth));
+    }
+    let val = ctx.operand_stack()[depth - 1 - n as usize].clone();
+    ctx.push(val);
+    Ok(())
+}
+
+/// roll: Rotate the top n items on the stack by j positions
+/// Stack: any[n-1] ... any[0] n j → any[(j-1) mod n] ... any[0] any[n-1] ... any[j mod n]
+/// Positive j rolls towards the top, negative j away from it; j is taken modulo n
+fn roll(ctx: &mut Context) -> Result<(), String> {
+    let j = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let n = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match (n, j) {
+        (PostScriptValue::Int(n), PostScriptValue::Int(_)) if n < 0 => {
+            Err(format!("Range check error: roll count {} is negative", n))
+        }
+        (PostScriptValue::Int(n), PostScriptValue::Int(j)) => ctx.roll_operands(n as usize, j),
+        _ => Err("Type check error: roll expected int int".to_string()),
+    }
+}
+
 // ============================================================================
 // Arithmetic Operations
 // ============================================================================
 
+/// The result of integer arithmetic: the integer, or the real result if it overflowed,
+/// as the PLRM specifies for `add`, `sub`, `mul`, `neg` and `abs`.
+fn int_or_real(exact: Option<i64>, real: f64) -> PostScriptValue {
+    exact.map_or(PostScriptValue::Real(real), PostScriptValue::Int)
+}
+
 /// add: Add two numbers
 /// Stack: num1 num2 → num1+num2
-/// Supports int+int, real+real, and mixed types (result is real if either operand is real)
+/// Supports int+int, real+real, and mixed types (result is real if either operand is real,
+/// or if the integer sum overflows)
 fn add(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match (a, b) {
-        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1 + i2)),
+        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(int_or_real(i1.checked_add(i2), i1 as f64 + i2 as f64)),
         (PostScriptValue::Real(f1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(f1 + f2)),
         (PostScriptValue::Int(i1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(i1 as f64 + f2)),
         (PostScriptValue::Real(f1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Real(f1 + i2 as f64)),
@@ -204,11 +705,12 @@ fn add(ctx: &mut Context) -> Result<(), String> {
 
 /// sub: Subtract two numbers
 /// Stack: num1 num2 → num1-num2
+/// An integer difference that overflows is pushed as a real
 fn sub(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match (a, b) {
-        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1 - i2)),
+        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(int_or_real(i1.checked_sub(i2), i1 as f64 - i2 as f64)),
         (PostScriptValue::Real(f1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(f1 - f2)),
         (PostScriptValue::Int(i1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(i1 as f64 - f2)),
         (PostScriptValue::Real(f1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Real(f1 - i2 as f64)),
@@ -219,11 +721,12 @@ fn sub(ctx: &mut Context) -> Result<(), String> {
 
 /// mul: Multiply two numbers
 /// Stack: num1 num2 → num1*num2
+/// An integer product that overflows is pushed as a real
 fn mul(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match (a, b) {
-        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1 * i2)),
+        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(int_or_real(i1.checked_mul(i2), i1 as f64 * i2 as f64)),
         (PostScriptValue::Real(f1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(f1 * f2)),
         (PostScriptValue::Int(i1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(i1 as f64 * f2)),
         (PostScriptValue::Real(f1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Real(f1 * i2 as f64)),
@@ -234,9 +737,13 @@ fn mul(ctx: &mut Context) -> Result<(), String> {
 
 /// div: Divide two numbers (always returns real)
 /// Stack: num1 num2 → num1/num2
+/// Division by zero pushes infinity, or raises undefinedresult in strict mode
 fn div(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    if ctx.is_strict() && matches!(b, PostScriptValue::Int(0) | PostScriptValue::Real(0.0)) {
+        return Err("Undefined result: division by zero".to_string());
+    }
     match (a, b) {
         (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Real(i1 as f64 / i2 as f64)),
         (PostScriptValue::Real(f1), PostScriptValue::Real(f2)) => ctx.push(PostScriptValue::Real(f1 / f2)),
@@ -249,11 +756,14 @@ fn div(ctx: &mut Context) -> Result<(), String> {
 
 /// idiv: Integer division
 /// Stack: int1 int2 → int1/int2 (truncated to integer)
+/// Division by zero raises undefinedresult; the one quotient that overflows (the most
+/// negative integer divided by -1) is pushed as a real
 fn idiv(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match (a, b) {
-        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1 / i2)),
+        (PostScriptValue::Int(_), PostScriptValue::Int(0)) => return Err("Undefined result: division by zero".to_string()),
+        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(int_or_real(i1.checked_div(i2), -(i1 as f64))),
         _ => return Err("Type check error".to_string()),
     }
     Ok(())
@@ -261,11 +771,13 @@ fn idiv(ctx: &mut Context) -> Result<(), String> {
 
 /// mod: Modulo operation
 /// Stack: int1 int2 → int1 mod int2
+/// A zero divisor raises undefinedresult
 fn mod_op(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match (a, b) {
-        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1 % i2)),
+        (PostScriptValue::Int(_), PostScriptValue::Int(0)) => return Err("Undefined result: division by zero".to_string()),
+        (PostScriptValue::Int(i1), PostScriptValue::Int(i2)) => ctx.push(PostScriptValue::Int(i1.wrapping_rem(i2))),
         _ => return Err("Type check error".to_string()),
     }
     Ok(())
@@ -273,10 +785,11 @@ fn mod_op(ctx: &mut Context) -> Result<(), String> {
 
 /// abs: Absolute value
 /// Stack: num → |num|
+/// The absolute value of the most negative integer is pushed as a real
 fn abs(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
-        PostScriptValue::Int(i) => ctx.push(PostScriptValue::Int(i.abs())),
+        PostScriptValue::Int(i) => ctx.push(int_or_real(i.checked_abs(), (i as f64).abs())),
         PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f.abs())),
         _ => return Err("Type check error".to_string()),
     }
@@ -285,10 +798,11 @@ fn abs(ctx: &mut Context) -> Result<(), String> {
 
 /// neg: Negation
 /// Stack: num → -num
+/// The negation of the most negative integer is pushed as a real
 fn neg(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
-        PostScriptValue::Int(i) => ctx.push(PostScriptValue::Int(-i)),
+        PostScriptValue::Int(i) => ctx.push(int_or_real(i.checked_neg(), -(i as f64))),
         PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(-f)),
         _ => return Err("Type check error".to_string()),
     }
@@ -297,9 +811,11 @@ fn neg(ctx: &mut Context) -> Result<(), String> {
 
 /// ceiling: Round up to nearest integer (returns real)
 /// Stack: num → ⌈num⌉
+/// In strict mode an integer operand stays an integer
 fn ceiling(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
+        PostScriptValue::Int(i) if ctx.is_strict() => ctx.push(PostScriptValue::Int(i)),
         PostScriptValue::Int(i) => ctx.push(PostScriptValue::Real(i as f64)), 
         PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f.ceil())),
         _ => return Err("Type check error".to_string()),
@@ -309,9 +825,11 @@ fn ceiling(ctx: &mut Context) -> Result<(), String> {
 
 /// floor: Round down to nearest integer (returns real)
 /// Stack: num → ⌊num⌋
+/// In strict mode an integer operand stays an integer
 fn floor(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
+        PostScriptValue::Int(i) if ctx.is_strict() => ctx.push(PostScriptValue::Int(i)),
         PostScriptValue::Int(i) => ctx.push(PostScriptValue::Real(i as f64)),
         PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f.floor())),
         _ => return Err("Type check error".to_string()),
@@ -331,10 +849,31 @@ fn round(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
+/// truncate: Round toward zero
+/// Stack: num → truncate(num)
+fn truncate(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::Int(i) => ctx.push(PostScriptValue::Int(i)),
+        PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f.trunc())),
+        _ => return Err("Type check error".to_string()),
+    }
+    Ok(())
+}
+
 /// sqrt: Square root
 /// Stack: num → √num
+/// A negative operand pushes NaN, or raises rangecheck in strict mode
 fn sqrt(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let negative = match a {
+        PostScriptValue::Int(i) => i < 0,
+        PostScriptValue::Real(f) => f < 0.0,
+        _ => false,
+    };
+    if negative && ctx.is_strict() {
+        return Err("Range check error: sqrt of negative number".to_string());
+    }
     match a {
         PostScriptValue::Int(i) => ctx.push(PostScriptValue::Real((i as f64).sqrt())),
         PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f.sqrt())),
@@ -343,18 +882,156 @@ fn sqrt(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
+/// Pops a number, converting an integer to a real.
+fn pop_real(ctx: &mut Context) -> Result<f64, String> {
+    match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Int(i) => Ok(i as f64),
+        PostScriptValue::Real(f) => Ok(f),
+        _ => Err("Type check error".to_string()),
+    }
+}
+
+/// Sine of an angle in degrees. Multiples of 90 degrees give exact results, so
+/// `180 sin` is 0 rather than a tiny residue of the conversion to radians.
+fn sin_degrees(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(360.0);
+    if angle % 90.0 == 0.0 {
+        return [0.0, 1.0, 0.0, -1.0][(angle / 90.0) as usize];
+    }
+    angle.to_radians().sin()
+}
+
+/// sin: Sine
+/// Stack: angle → real
+/// The angle is in degrees
+fn sin(ctx: &mut Context) -> Result<(), String> {
+    let angle = pop_real(ctx)?;
+    ctx.push(PostScriptValue::Real(sin_degrees(angle)));
+    Ok(())
+}
+
+/// cos: Cosine
+/// Stack: angle → real
+/// The angle is in degrees
+fn cos(ctx: &mut Context) -> Result<(), String> {
+    let angle = pop_real(ctx)?;
+    ctx.push(PostScriptValue::Real(sin_degrees(angle + 90.0)));
+    Ok(())
+}
+
+/// atan: Arc tangent
+/// Stack: num den → angle
+/// Pushes the angle in degrees, 0 <= angle < 360, of the vector (den, num);
+/// both operands zero raises undefinedresult
+fn atan(ctx: &mut Context) -> Result<(), String> {
+    let den = pop_real(ctx)?;
+    let num = pop_real(ctx)?;
+    if num == 0.0 && den == 0.0 {
+        return Err("Undefined result: atan of 0 0".to_string());
+    }
+    let angle = num.atan2(den).to_degrees();
+    ctx.push(PostScriptValue::Real(if angle < 0.0 { angle + 360.0 } else { angle }));
+    Ok(())
+}
+
+/// exp: Exponentiation
+/// Stack: base exponent → real
+/// Results that are not finite real numbers (0 to a negative power, a negative base
+/// to a fractional power, overflow) raise undefinedresult instead of pushing NaN or
+/// infinity
+fn exp(ctx: &mut Context) -> Result<(), String> {
+    let exponent = pop_real(ctx)?;
+    let base = pop_real(ctx)?;
+    let result = base.powf(exponent);
+    if !result.is_finite() {
+        return Err(format!("Undefined result: {} exp {}", base, exponent));
+    }
+    ctx.push(PostScriptValue::Real(result));
+    Ok(())
+}
+
+/// ln: Natural logarithm
+/// Stack: num → real
+/// A non-positive operand raises undefinedresult
+fn ln(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_real(ctx)?;
+    if a <= 0.0 {
+        return Err(format!("Undefined result: ln of {}", a));
+    }
+    ctx.push(PostScriptValue::Real(a.ln()));
+    Ok(())
+}
+
+/// log: Base-10 logarithm
+/// Stack: num → real
+/// A non-positive operand raises undefinedresult
+fn log(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_real(ctx)?;
+    if a <= 0.0 {
+        return Err(format!("Undefined result: log of {}", a));
+    }
+    ctx.push(PostScriptValue::Real(a.log10()));
+    Ok(())
+}
+
+/// rand: Next pseudo-random number
+/// Stack: (empty) → int
+/// Uses the Context's generator, so the sequence is the same on every run
+fn rand(ctx: &mut Context) -> Result<(), String> {
+    let n = ctx.random.next_value();
+    ctx.push(PostScriptValue::Int(n));
+    Ok(())
+}
+
+/// srand: Seed the random number generator
+/// Stack: int → (empty)
+fn srand(ctx: &mut Context) -> Result<(), String> {
+    match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Int(seed) => ctx.random.seed(seed),
+        other => return Err(format!("Type check error: srand expected int, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// rrand: Read the random number generator's state
+/// Stack: (empty) → int
+/// `rrand ... srand` replays the numbers rand produced in between
+fn rrand(ctx: &mut Context) -> Result<(), String> {
+    let state = ctx.random.state();
+    ctx.push(PostScriptValue::Int(state));
+    Ok(())
+}
+
 // ============================================================================
 // Dictionary Operations
 // ============================================================================
 
+/// Checks the size requested from an object-creating operator against its limit.
+///
+/// Negative sizes are a range check error; sizes above the limit are a limit check
+/// error naming the system parameter that holds the limit.
+fn check_size(size: i64, limit: usize, parameter: &str) -> Result<usize, String> {
+    if size < 0 {
+        return Err("Range check error".to_string());
+    }
+    if size as u64 > limit as u64 {
+        return Err(format!("Limit check error: size {} exceeds {} ({})", size, parameter, limit));
+    }
+    Ok(size as usize)
+}
+
 /// dict: Create a new dictionary
 /// Stack: int → dict
-/// Creates a dictionary with the specified initial capacity
+/// Creates a dictionary with the specified initial capacity, up to MaxDictLength
+/// A negative capacity raises rangecheck
 fn dict(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
-        PostScriptValue::Int(_) => {
+        PostScriptValue::Int(n) => {
+            let n = check_size(n, ctx.limits.max_dict_length, "MaxDictLength")?;
+            ctx.allocate(Allocation::Dict(n))?;
             let d = Rc::new(RefCell::new(HashMap::new()));
+            ctx.set_dict_capacity(&d, n);
             ctx.push(PostScriptValue::Dict(d));
         }
         _ => return Err("Type check error".to_string()),
@@ -362,28 +1039,35 @@ fn dict(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
-/// length: Get the length of a composite object
-/// Stack: dict|string|array → int
+/// length: Get the length of a composite object or name
+/// Stack: dict|string|array|name → int
 /// Returns the number of elements in the object
+///
+/// String length is counted in characters, the same unit used for the indices of
+/// get, put, getinterval and putinterval. Name length is the number of characters
+/// in the name. Any other type is a type check error that names the rejected type.
 fn length(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
         PostScriptValue::Dict(d) => ctx.push(PostScriptValue::Int(d.borrow().len() as i64)),
-        PostScriptValue::String(s) => ctx.push(PostScriptValue::Int(s.borrow().len() as i64)),
-        PostScriptValue::Array(arr) => ctx.push(PostScriptValue::Int(arr.len() as i64)),
+        PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => ctx.push(PostScriptValue::Int(s.borrow().chars().count() as i64)),
+        PostScriptValue::Array(arr) => ctx.push(PostScriptValue::Int(arr.borrow().len() as i64)),
         PostScriptValue::Block(arr) => ctx.push(PostScriptValue::Int(arr.len() as i64)),
         PostScriptValue::Closure { body, .. } => ctx.push(PostScriptValue::Int(body.len() as i64)),
-        _ => return Err("Type check error".to_string()),
+        PostScriptValue::Name(n) | PostScriptValue::LiteralName(n) => ctx.push(PostScriptValue::Int(n.chars().count() as i64)),
+        other => return Err(format!("Type check error: length not defined for {}", other.type_name())),
     }
     Ok(())
 }
 
 /// maxlength: Get the capacity of a dictionary
 /// Stack: dict → int
+/// The capacity given to dict, or the length once more entries were added (see
+/// `Context::dict_capacity`)
 fn maxlength(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
-        PostScriptValue::Dict(d) => ctx.push(PostScriptValue::Int(d.borrow().capacity() as i64)),
+        PostScriptValue::Dict(d) => ctx.push(PostScriptValue::Int(ctx.dict_capacity(&d) as i64)),
         _ => return Err("Type check error".to_string()),
     }
     Ok(())
@@ -395,21 +1079,16 @@ fn maxlength(ctx: &mut Context) -> Result<(), String> {
 fn begin(ctx: &mut Context) -> Result<(), String> {
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
     match a {
-        PostScriptValue::Dict(d) => ctx.dict_stack.push(d),
-        _ => return Err("Type check error".to_string()),
+        PostScriptValue::Dict(d) => ctx.begin(d),
+        _ => Err("Type check error".to_string()),
     }
-    Ok(())
 }
 
 /// end: Pop the dictionary stack
 /// Stack: (empty) → (empty)
 /// Removes the current dictionary from the lookup context
 fn end(ctx: &mut Context) -> Result<(), String> {
-    if ctx.dict_stack.len() <= 1 { // Don't pop system dict
-        return Err("Dict stack underflow".to_string());
-    }
-    ctx.dict_stack.pop();
-    Ok(())
+    ctx.end() // Never pops the system dict or user dict
 }
 
 /// def: Define a key-value pair in the current dictionary
@@ -420,6 +1099,8 @@ fn def(ctx: &mut Context) -> Result<(), String> {
     let key = ctx.pop().ok_or("Stack underflow".to_string())?;
     match key {
         PostScriptValue::Name(k) | PostScriptValue::LiteralName(k) => {
+            ctx.check_access(&PostScriptValue::Dict(ctx.current_dict()), Access::Unlimited, "def")?;
+            ctx.check_dict_room(&ctx.current_dict(), &k)?;
             ctx.define(k, value);
         }
         _ => return Err("Type check error: def expected name key".to_string()),
@@ -427,86 +1108,449 @@ fn def(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
+/// known: Test whether a dictionary has a key
+/// Stack: dict key → bool
+fn known(ctx: &mut Context) -> Result<(), String> {
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let d = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match d {
+        PostScriptValue::Dict(d) => {
+            ctx.check_access(&PostScriptValue::Dict(d.clone()), Access::ReadOnly, "known")?;
+            let k = dict_key(&key)?;
+            let found = d.borrow().contains_key(&k);
+            ctx.push(PostScriptValue::Bool(found));
+        }
+        other => return Err(format!("Type check error: known expected a dict, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// where: Find the dictionary that defines a key
+/// Stack: key → dict true, or key → false
+/// Searches the dictionary stack from the top, exactly as name lookup does, so the
+/// dictionary pushed is the one whose definition a name would currently resolve to
+/// (the system dictionary for built-in operators)
+fn where_op(ctx: &mut Context) -> Result<(), String> {
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let k = dict_key(&key)?;
+    match ctx.lookup_dict(&k) {
+        Some(d) => {
+            ctx.push(PostScriptValue::Dict(d));
+            ctx.push(PostScriptValue::Bool(true));
+        }
+        None => ctx.push(PostScriptValue::Bool(false)),
+    }
+    Ok(())
+}
+
+/// load: Look up a key without executing its value
+/// Stack: key → value
+/// Procedures and operators are pushed as data, e.g. to store them under another name
+fn load(ctx: &mut Context) -> Result<(), String> {
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let k = dict_key(&key)?;
+    let value = ctx.lookup(&k).ok_or(format!("Undefined name: {}", k))?;
+    ctx.push(value);
+    Ok(())
+}
+
+/// store: Replace a definition wherever it is
+/// Stack: key value → (empty)
+/// Unlike def, which always writes to the current dictionary (shadowing definitions
+/// further down), store updates the dictionary that where would find. A key that is
+/// not defined anywhere is defined where def would put it
+fn store(ctx: &mut Context) -> Result<(), String> {
+    let value = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let k = dict_key(&key)?;
+    match ctx.lookup_dict(&k) {
+        Some(d) => {
+            ctx.check_access(&PostScriptValue::Dict(d.clone()), Access::Unlimited, "store")?;
+            d.borrow_mut().insert(k, value);
+        }
+        None => {
+            ctx.check_access(&PostScriptValue::Dict(ctx.current_dict()), Access::Unlimited, "store")?;
+            ctx.define(k, value);
+        }
+    }
+    Ok(())
+}
+
+/// currentdict: Push the current dictionary
+/// Stack: (empty) → dict
+fn currentdict(ctx: &mut Context) -> Result<(), String> {
+    let d = ctx.current_dict();
+    ctx.push(PostScriptValue::Dict(d));
+    Ok(())
+}
+
+/// countdictstack: Count the dictionaries on the dictionary stack
+/// Stack: (empty) → int
+/// The permanent dictionaries (system, user and any library dictionaries) are included
+fn countdictstack(ctx: &mut Context) -> Result<(), String> {
+    let n = ctx.dict_count() as i64;
+    ctx.push(PostScriptValue::Int(n));
+    Ok(())
+}
+
+/// dictstack: Copy the dictionary stack into an array
+/// Stack: array → subarray
+/// Stores the dictionaries, bottom first, into the first elements of array and pushes
+/// a new array holding them. Like getinterval's, the result is a copy: arrays cannot
+/// share part of another array's elements. Raises rangecheck if array is too short.
+fn dictstack(ctx: &mut Context) -> Result<(), String> {
+    let arr = match ctx.peek().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Array(arr) => arr.clone(),
+        other => return Err(format!("Type check error: dictstack expected an array, got {}", other.type_name())),
+    };
+    ctx.check_access(&PostScriptValue::Array(arr.clone()), Access::Unlimited, "dictstack")?;
+    let dicts: Vec<PostScriptValue> = ctx.dict_stack().iter()
+        .map(|d| PostScriptValue::Dict(d.clone()))
+        .collect();
+    let len = arr.borrow().len();
+    if dicts.len() > len {
+        return Err(format!("Range check error: {} dictionaries do not fit in an array of length {}", dicts.len(), len));
+    }
+    ctx.pop();
+    arr.borrow_mut()[..dicts.len()].clone_from_slice(&dicts);
+    ctx.push(PostScriptValue::Array(Rc::new(RefCell::new(dicts))));
+    Ok(())
+}
+
+/// undef: Remove a key from a dictionary
+/// Stack: dict key → (empty)
+/// Only the given dictionary is changed; a definition of the same key further down
+/// the dictionary stack becomes visible again
+fn undef(ctx: &mut Context) -> Result<(), String> {
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let d = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match d {
+        PostScriptValue::Dict(d) => {
+            ctx.check_access(&PostScriptValue::Dict(d.clone()), Access::Unlimited, "undef")?;
+            let k = dict_key(&key)?;
+            d.borrow_mut().remove(&k);
+        }
+        other => return Err(format!("Type check error: undef expected a dict, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// systemdict: Push the system dictionary
+/// Stack: (empty) → dict
+/// An operator rather than an entry of the dictionary itself, which would make the
+/// system dictionary contain itself
+fn systemdict(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Dict(ctx.system_dict()));
+    Ok(())
+}
+
+/// userdict: Push the user dictionary
+/// Stack: (empty) → dict
+fn userdict(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Dict(ctx.user_dict()));
+    Ok(())
+}
+
+/// dicttomark (also >>): Build a dictionary from the pairs above the topmost mark
+/// Stack: mark key1 value1 ... keyn valuen → dict
+/// Keys are converted as by put; a later pair replaces an earlier one with the same
+/// key. The stack is left alone if there is no mark, the number of items is odd or
+/// a key is invalid
+fn dicttomark(ctx: &mut Context) -> Result<(), String> {
+    let position = mark_position(ctx)?;
+    let len = ctx.operand_count() - position - 1;
+    if len % 2 == 1 {
+        return Err(format!("Range check error: dicttomark expected key/value pairs, got {} items", len));
+    }
+    let entries = ctx.operand_stack()[position + 1..].chunks(2)
+        .map(|pair| Ok((dict_key(&pair[0])?, pair[1].clone())))
+        .collect::<Result<Vec<_>, String>>()?;
+    check_size((len / 2) as i64, ctx.limits.max_dict_length, "MaxDictLength")?;
+    ctx.allocate(Allocation::Dict(len / 2))?;
+    ctx.truncate_operands(position);
+    let dict = Rc::new(RefCell::new(entries.into_iter().collect::<HashMap<_, _>>()));
+    ctx.set_dict_capacity(&dict, len / 2);
+    ctx.push(PostScriptValue::Dict(dict));
+    Ok(())
+}
+
+/// globaldict: Push the global dictionary
+/// Stack: (empty) → dict
+fn globaldict(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Dict(ctx.global_dict()));
+    Ok(())
+}
+
+/// setglobal: Select global or local allocation
+/// Stack: bool → (empty)
+/// See `Context::set_global_allocation` for what global allocation does
+fn setglobal(ctx: &mut Context) -> Result<(), String> {
+    match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Bool(global) => ctx.set_global_allocation(global),
+        other => return Err(format!("Type check error: setglobal expected a bool, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// currentglobal: Test whether global allocation is selected
+/// Stack: (empty) → bool
+fn currentglobal(ctx: &mut Context) -> Result<(), String> {
+    let global = ctx.global_allocation();
+    ctx.push(PostScriptValue::Bool(global));
+    Ok(())
+}
+
+/// cleardictstack: Pop all non-permanent dictionaries
+/// Stack: (empty) → (empty)
+fn cleardictstack(ctx: &mut Context) -> Result<(), String> {
+    ctx.clear_dict_stack();
+    Ok(())
+}
+
 // ============================================================================
 // String Operations
 // ============================================================================
 
-/// get: Get an element from a string or array
-/// Stack: string|array index → int|any
-/// For strings, returns the ASCII value of the character at the index
+/// Converts a value used as a dictionary key into the string stored in the HashMap.
+///
+/// Names, literal names and strings with the same text are the same key, and
+/// numbers that compare equal (e.g., 1 and 1.0) are the same key.
+fn dict_key(key: &PostScriptValue) -> Result<String, String> {
+    match key {
+        PostScriptValue::Name(n) | PostScriptValue::LiteralName(n) => Ok(n.clone()),
+        PostScriptValue::String(s) => Ok(s.borrow().clone()),
+        PostScriptValue::Int(i) => Ok(i.to_string()),
+        PostScriptValue::Real(f) if f.fract() == 0.0 => Ok((*f as i64).to_string()),
+        PostScriptValue::Real(f) => Ok(f.to_string()),
+        PostScriptValue::Bool(b) => Ok(b.to_string()),
+        _ => Err("Type check error: invalid dictionary key".to_string()),
+    }
+}
+
+/// get: Get an element from a string, array or dictionary
+/// Stack: string|array index → int|any, dict key → any
+/// For strings, returns the character code at the index: a byte 0-255 for every
+/// character of a string literal or read from a file, since a string holds one
+/// character per byte (characters outside Latin-1 only come from converted names)
 /// For arrays, returns the element at the index
+/// For dictionaries, returns the value stored under the key
 fn get(ctx: &mut Context) -> Result<(), String> {
     let index = ctx.pop().ok_or("Stack underflow".to_string())?;
     let container = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.check_access(&container, Access::ReadOnly, "get")?;
     match (container, index) {
         (PostScriptValue::String(s), PostScriptValue::Int(i)) => {
-            let s_borrowed = s.borrow();
-            if i < 0 || i as usize >= s_borrowed.len() {
-                return Err("Range check error".to_string());
-            }
-            let c = s_borrowed.chars().nth(i as usize).unwrap();
+            let s = s.borrow();
+            let c = usize::try_from(i).ok()
+                .and_then(|i| s.chars().nth(i))
+                .ok_or_else(|| format!("Range check error: index {} out of range for string of length {}", i, s.chars().count()))?;
             ctx.push(PostScriptValue::Int(c as i64));
         }
         (PostScriptValue::Array(arr), PostScriptValue::Int(i)) => {
-             if i < 0 || i as usize >= arr.len() {
-                return Err("Range check error".to_string());
+            let val = usize::try_from(i).ok()
+                .and_then(|i| arr.borrow().get(i).cloned())
+                .ok_or(format!("Range check error: index {} out of range for array of length {}", i, arr.borrow().len()))?;
+            ctx.push(val);
+        }
+        (PostScriptValue::Dict(d), key) => {
+            let k = dict_key(&key)?;
+            let val = d.borrow().get(&k).cloned();
+            match val {
+                Some(v) => ctx.push(v),
+                None => return Err(format!("Undefined key: {}", k)),
             }
-            ctx.push(arr[i as usize].clone());
         }
         _ => return Err("Type check error".to_string()),
     }
     Ok(())
 }
 
-/// getinterval: Extract a substring or subarray
-/// Stack: string|array index count → substring|subarray
-fn getinterval(ctx: &mut Context) -> Result<(), String> {
-    let count = ctx.pop().ok_or("Stack underflow".to_string())?;
+/// put: Store an element into a string, array or dictionary
+/// Stack: string index int → (empty), array index any → (empty), dict key value → (empty)
+/// For strings, replaces the character at index with the character code int (0-255),
+/// which get then returns; codes outside 0-255 are a rangecheck error
+/// For arrays, replaces the element at index
+/// For dictionaries, inserts or replaces the value stored under the key
+///
+/// The container is shared through Rc<RefCell<>>, so the change is visible through
+/// every reference to it, including a dictionary that is on the dictionary stack.
+fn put(ctx: &mut Context) -> Result<(), String> {
+    let value = ctx.pop().ok_or("Stack underflow".to_string())?;
     let index = ctx.pop().ok_or("Stack underflow".to_string())?;
     let container = ctx.pop().ok_or("Stack underflow".to_string())?;
-    
-    match (container, index, count) {
-        (PostScriptValue::String(s), PostScriptValue::Int(i), PostScriptValue::Int(c)) => {
-            let i = i as usize;
-            let c = c as usize;
-            let s_borrowed = s.borrow();
-            if i + c > s_borrowed.len() {
-                return Err("Range check error".to_string());
+    ctx.check_access(&container, Access::Unlimited, "put")?;
+    match (container, index, value) {
+        (PostScriptValue::String(s), PostScriptValue::Int(i), PostScriptValue::Int(code)) => {
+            let mut chars: Vec<char> = s.borrow().chars().collect();
+            if i < 0 || i as usize >= chars.len() {
+                return Err(format!("Range check error: index {} out of range for string of length {}", i, chars.len()));
             }
-            let sub = s_borrowed[i..i+c].to_string();
-            ctx.push(PostScriptValue::String(Rc::new(RefCell::new(sub))));
+            if !(0..=255).contains(&code) {
+                return Err(format!("Range check error: character code {} outside 0-255", code));
+            }
+            chars[i as usize] = code as u8 as char;
+            *s.borrow_mut() = chars.into_iter().collect();
         }
-        _ => return Err("Type check error".to_string()),
+        (PostScriptValue::Array(arr), PostScriptValue::Int(i), value) => {
+            let len = arr.borrow().len();
+            if i < 0 || i as usize >= len {
+                return Err(format!("Range check error: index {} out of range for array of length {}", i, len));
+            }
+            arr.borrow_mut()[i as usize] = value;
+        }
+        (PostScriptValue::Dict(d), key, value) => {
+            let k = dict_key(&key)?;
+            ctx.check_dict_room(&d, &k)?;
+            d.borrow_mut().insert(k, value);
+        }
+        _ => return Err("Type check error: put expected string, array or dict".to_string()),
     }
     Ok(())
 }
 
-/// putinterval: Replace part of a string with another string
-/// Stack: string1 index string2 → (empty)
-/// 
+/// string: Create a string of zero bytes
+/// Stack: int → string
+/// Creates a string of int characters, each with code 0, up to MaxStringLength
+fn string(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::Int(n) => {
+            let n = check_size(n, ctx.limits.max_string_length, "MaxStringLength")?;
+            ctx.allocate(Allocation::String(n))?;
+            ctx.push(PostScriptValue::String(Rc::new(RefCell::new("\0".repeat(n)))));
+            Ok(())
+        }
+        _ => Err("Type check error: string expected int".to_string()),
+    }
+}
+
+/// Pops the operands of search and anchorsearch: the string (returned as is, to be
+/// pushed back when there is no match) and the texts of the string and seek.
+fn pop_search_operands(ctx: &mut Context, operator: &str) -> Result<(PostScriptValue, String, String), String> {
+    let seek = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let string = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.check_access(&string, Access::ReadOnly, operator)?;
+    ctx.check_access(&seek, Access::ReadOnly, operator)?;
+    let texts = match (&string, &seek) {
+        (PostScriptValue::String(s), PostScriptValue::String(k)) => (s.borrow().clone(), k.borrow().clone()),
+        (a, b) => {
+            return Err(format!("Type check error: {} expected two strings, got {} and {}", operator, a.type_name(), b.type_name()));
+        }
+    };
+    Ok((string, texts.0, texts.1))
+}
+
+/// Makes a new string object holding text.
+fn new_string(text: &str) -> PostScriptValue {
+    PostScriptValue::String(Rc::new(RefCell::new(text.to_string())))
+}
+
+/// search: Find a substring
+/// Stack: string seek → post match pre true, or string seek → string false
+/// The first occurrence wins; an empty seek matches at the start. The three pieces
+/// are new strings (copies, like getinterval's) that concatenate to the original
+fn search(ctx: &mut Context) -> Result<(), String> {
+    let (string, text, seek) = pop_search_operands(ctx, "search")?;
+    match text.find(&seek) {
+        Some(at) => {
+            let end = at + seek.len();
+            ctx.push(new_string(&text[end..]));
+            ctx.push(new_string(&text[at..end]));
+            ctx.push(new_string(&text[..at]));
+            ctx.push(PostScriptValue::Bool(true));
+        }
+        None => {
+            ctx.push(string);
+            ctx.push(PostScriptValue::Bool(false));
+        }
+    }
+    Ok(())
+}
+
+/// anchorsearch: Test for a prefix
+/// Stack: string seek → post match true, or string seek → string false
+/// The two pieces are new strings (copies, like getinterval's)
+fn anchorsearch(ctx: &mut Context) -> Result<(), String> {
+    let (string, text, seek) = pop_search_operands(ctx, "anchorsearch")?;
+    if text.starts_with(&seek) {
+        ctx.push(new_string(&text[seek.len()..]));
+        ctx.push(new_string(&seek));
+        ctx.push(PostScriptValue::Bool(true));
+    } else {
+        ctx.push(string);
+        ctx.push(PostScriptValue::Bool(false));
+    }
+    Ok(())
+}
+
+/// getinterval: Extract a substring or subarray
+/// Stack: string|array index count → substring|subarray
+/// Index and count are measured in characters, matching length and get
+/// The result is a new string or array holding copies of the elements: changing it
+/// does not change the original, and vice versa
+fn getinterval(ctx: &mut Context) -> Result<(), String> {
+    let count = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let index = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let container = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.check_access(&container, Access::ReadOnly, "getinterval")?;
+
+    match (container, index, count) {
+        (PostScriptValue::String(s), PostScriptValue::Int(i), PostScriptValue::Int(c)) => {
+            if i < 0 || c < 0 {
+                return Err("Range check error".to_string());
+            }
+            let i = i as usize;
+            let c = c as usize;
+            let s_borrowed = s.borrow();
+            if i + c > s_borrowed.chars().count() {
+                return Err("Range check error".to_string());
+            }
+            let sub: String = s_borrowed.chars().skip(i).take(c).collect();
+            ctx.push(PostScriptValue::String(Rc::new(RefCell::new(sub))));
+        }
+        (PostScriptValue::Array(arr), PostScriptValue::Int(i), PostScriptValue::Int(c)) => {
+            let range = array_interval(i, c, arr.borrow().len())?;
+            let sub = arr.borrow()[range].to_vec();
+            ctx.push(PostScriptValue::Array(Rc::new(RefCell::new(sub))));
+        }
+        _ => return Err("Type check error".to_string()),
+    }
+    Ok(())
+}
+
+/// putinterval: Replace part of a string or array with another
+/// Stack: string1 index string2 → (empty), array1 index array2 → (empty)
+/// 
 /// Modifies string1 in place by replacing characters starting at index with string2.
-/// This works because strings are now wrapped in Rc<RefCell<String>>.
+/// This works because strings are now wrapped in Rc<RefCell<String>>. Arrays work the
+/// same way; array2 may also be a procedure, or array1 itself (the intervals may overlap).
 fn putinterval(ctx: &mut Context) -> Result<(), String> {
     let source = ctx.pop().ok_or("Stack underflow".to_string())?;
     let index = ctx.pop().ok_or("Stack underflow".to_string())?;
     let dest = ctx.pop().ok_or("Stack underflow".to_string())?;
-    
+    ctx.check_access(&dest, Access::Unlimited, "putinterval")?;
+    ctx.check_access(&source, Access::ReadOnly, "putinterval")?;
+
     match (dest, index, source) {
         (PostScriptValue::String(dest_str), PostScriptValue::Int(idx), PostScriptValue::String(src_str)) => {
+            if idx < 0 {
+                return Err("Range check error".to_string());
+            }
             let idx = idx as usize;
-            let src_borrowed = src_str.borrow();
+            // Collect the source first so that putinterval of a string into itself
+            // does not hold a shared and a mutable borrow of the same RefCell
+            let src_chars: Vec<char> = src_str.borrow().chars().collect();
             let mut dest_borrowed = dest_str.borrow_mut();
             
+            // Replace characters in dest starting at idx with characters from src
+            // Indices are character positions, matching length and get
+            let mut dest_chars: Vec<char> = dest_borrowed.chars().collect();
+            
             // Check bounds
-            if idx + src_borrowed.len() > dest_borrowed.len() {
+            if idx + src_chars.len() > dest_chars.len() {
                 return Err("Range check error".to_string());
             }
             
-            // Replace characters in dest starting at idx with characters from src
-            // We need to work with byte indices for string slicing
-            let mut dest_chars: Vec<char> = dest_borrowed.chars().collect();
-            let src_chars: Vec<char> = src_borrowed.chars().collect();
-            
             for (i, &ch) in src_chars.iter().enumerate() {
                 dest_chars[idx + i] = ch;
             }
@@ -514,10 +1558,135 @@ fn putinterval(ctx: &mut Context) -> Result<(), String> {
             *dest_borrowed = dest_chars.into_iter().collect();
             Ok(())
         }
-        _ => Err("Type check error: putinterval expected string index string".to_string()),
+        (PostScriptValue::Array(dest_arr), PostScriptValue::Int(idx), source) => {
+            // Copy the source first, so an overlapping interval of the same array is
+            // read completely before it is overwritten
+            let items = match source {
+                PostScriptValue::Array(src_arr) => src_arr.borrow().clone(),
+                PostScriptValue::Block(items) | PostScriptValue::Closure { body: items, .. } => items,
+                other => {
+                    return Err(format!("Type check error: putinterval expected an array to copy from, got {}", other.type_name()));
+                }
+            };
+            let range = array_interval(idx, items.len() as i64, dest_arr.borrow().len())?;
+            dest_arr.borrow_mut()[range].clone_from_slice(&items);
+            Ok(())
+        }
+        _ => Err("Type check error: putinterval expected string index string or array index array".to_string()),
     }
 }
 
+// ============================================================================
+// Array Operations
+// ============================================================================
+
+/// Checks the interval of getinterval and putinterval on an array of length len.
+fn array_interval(index: i64, count: i64, len: usize) -> Result<std::ops::Range<usize>, String> {
+    if index < 0 || count < 0 || index as u64 + count as u64 > len as u64 {
+        return Err(format!("Range check error: interval {} to {} out of range for array of length {}", index, index.saturating_add(count), len));
+    }
+    Ok(index as usize..(index + count) as usize)
+}
+
+/// Returns the operand stack position of the topmost mark.
+fn mark_position(ctx: &Context) -> Result<usize, String> {
+    ctx.operand_stack().iter()
+        .rposition(|v| matches!(v, PostScriptValue::Mark))
+        .ok_or("Unmatched mark: no mark on the operand stack".to_string())
+}
+
+/// array: Create an array of nulls
+/// Stack: int → array
+/// Creates an array with int elements, each null, up to MaxArrayLength
+fn array(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::Int(n) => {
+            let n = check_size(n, ctx.limits.max_array_length, "MaxArrayLength")?;
+            ctx.allocate(Allocation::Array(n))?;
+            ctx.push(PostScriptValue::Array(Rc::new(RefCell::new(vec![PostScriptValue::Null; n]))));
+            Ok(())
+        }
+        _ => Err("Type check error: array expected int".to_string()),
+    }
+}
+
+/// aload: Push all elements of an array, then the array
+/// Stack: array → any0 ... anyn-1 array
+/// Procedures are arrays too and can be unpacked the same way
+fn aload(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.check_access(&a, Access::ReadOnly, "aload")?;
+    let items = match &a {
+        PostScriptValue::Array(arr) => arr.borrow().clone(),
+        PostScriptValue::Block(items) | PostScriptValue::Closure { body: items, .. } => items.clone(),
+        other => return Err(format!("Type check error: aload expected an array, got {}", other.type_name())),
+    };
+    for item in items {
+        ctx.push(item);
+    }
+    ctx.push(a);
+    Ok(())
+}
+
+/// astore: Store items from the stack into an array
+/// Stack: any0 ... anyn-1 array → array
+/// n is the length of the array; the stack is left alone if fewer items are available
+fn astore(ctx: &mut Context) -> Result<(), String> {
+    let arr = match ctx.peek().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Array(arr) => arr.clone(),
+        other => return Err(format!("Type check error: astore expected an array, got {}", other.type_name())),
+    };
+    ctx.check_access(&PostScriptValue::Array(arr.clone()), Access::Unlimited, "astore")?;
+    let n = arr.borrow().len();
+    if ctx.operand_count() <= n {
+        return Err("Stack underflow".to_string());
+    }
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let items = ctx.take_operands(n);
+    *arr.borrow_mut() = items;
+    ctx.push(a);
+    Ok(())
+}
+
+/// [ and mark: Push a mark
+/// Stack: (empty) → mark
+fn mark(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Mark);
+    Ok(())
+}
+
+/// ]: Build an array from the items above the topmost mark
+/// Stack: mark obj0 ... objn-1 → array
+/// The stack is left alone if there is no mark or the array would exceed MaxArrayLength
+fn array_from_mark(ctx: &mut Context) -> Result<(), String> {
+    let position = mark_position(ctx)?;
+    let len = ctx.operand_count() - position - 1;
+    check_size(len as i64, ctx.limits.max_array_length, "MaxArrayLength")?;
+    ctx.allocate(Allocation::Array(len))?;
+    let items = ctx.take_operands(len);
+    ctx.pop(); // The mark
+    ctx.push(PostScriptValue::Array(Rc::new(RefCell::new(items))));
+    Ok(())
+}
+
+/// cleartomark: Remove items down to and including the topmost mark
+/// Stack: mark obj1 ... objn → (empty)
+fn cleartomark(ctx: &mut Context) -> Result<(), String> {
+    let position = mark_position(ctx)?;
+    ctx.truncate_operands(position);
+    Ok(())
+}
+
+/// counttomark: Count the items above the topmost mark
+/// Stack: mark obj1 ... objn → mark obj1 ... objn n
+fn counttomark(ctx: &mut Context) -> Result<(), String> {
+    let position = mark_position(ctx)?;
+    let n = ctx.operand_count() - position - 1;
+    ctx.push(PostScriptValue::Int(n as i64));
+    Ok(())
+}
+
 // ============================================================================
 // Boolean and Bitwise Operations
 // ============================================================================
@@ -527,7 +1696,7 @@ fn putinterval(ctx: &mut Context) -> Result<(), String> {
 fn eq(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
-    ctx.push(PostScriptValue::Bool(a == b));
+    ctx.push(PostScriptValue::Bool(objects_equal(&a, &b)));
     Ok(())
 }
 
@@ -536,10 +1705,25 @@ fn eq(ctx: &mut Context) -> Result<(), String> {
 fn ne(ctx: &mut Context) -> Result<(), String> {
     let b = ctx.pop().ok_or("Stack underflow".to_string())?;
     let a = ctx.pop().ok_or("Stack underflow".to_string())?;
-    ctx.push(PostScriptValue::Bool(a != b));
+    ctx.push(PostScriptValue::Bool(!objects_equal(&a, &b)));
     Ok(())
 }
 
+/// Equality as `eq` and `ne` test it. Arrays and dictionaries are equal only when
+/// they are the same object (as in the PLRM), which also keeps an array that
+/// contains itself from being compared forever; strings compare by their characters,
+/// and procedures element by element.
+fn objects_equal(a: &PostScriptValue, b: &PostScriptValue) -> bool {
+    match (a, b) {
+        (PostScriptValue::Array(x), PostScriptValue::Array(y)) => Rc::ptr_eq(x, y),
+        (PostScriptValue::Dict(x), PostScriptValue::Dict(y)) => Rc::ptr_eq(x, y),
+        (PostScriptValue::Block(x), PostScriptValue::Block(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| objects_equal(x, y))
+        }
+        _ => a == b,
+    }
+}
+
 /// ge: Test greater than or equal
 /// Stack: num1|string1 num2|string2 → bool
 fn ge(ctx: &mut Context) -> Result<(), String> {
@@ -653,17 +1837,7 @@ fn if_op(ctx: &mut Context) -> Result<(), String> {
     let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
     let bool_val = ctx.pop().ok_or("Stack underflow".to_string())?;
     match bool_val {
-        PostScriptValue::Bool(true) => {
-            // Execute the procedure by pushing it to the execution stack
-            match proc {
-                PostScriptValue::Block(block) => {
-                    for item in block.iter().rev() {
-                        ctx.execution_stack.push(item.clone());
-                    }
-                }
-                _ => ctx.execution_stack.push(proc),
-            }
-        }
+        PostScriptValue::Bool(true) => ctx.schedule_procedure(&proc),
         PostScriptValue::Bool(false) => {}
         _ => return Err("Type check error: if expected bool".to_string()),
     }
@@ -678,26 +1852,8 @@ fn ifelse(ctx: &mut Context) -> Result<(), String> {
     let proc1 = ctx.pop().ok_or("Stack underflow".to_string())?;
     let bool_val = ctx.pop().ok_or("Stack underflow".to_string())?;
     match bool_val {
-        PostScriptValue::Bool(true) => {
-            match proc1 {
-                PostScriptValue::Block(block) => {
-                    for item in block.iter().rev() {
-                        ctx.execution_stack.push(item.clone());
-                    }
-                }
-                _ => ctx.execution_stack.push(proc1),
-            }
-        }
-        PostScriptValue::Bool(false) => {
-            match proc2 {
-                PostScriptValue::Block(block) => {
-                    for item in block.iter().rev() {
-                        ctx.execution_stack.push(item.clone());
-                    }
-                }
-                _ => ctx.execution_stack.push(proc2),
-            }
-        }
+        PostScriptValue::Bool(true) => ctx.schedule_procedure(&proc1),
+        PostScriptValue::Bool(false) => ctx.schedule_procedure(&proc2),
         _ => return Err("Type check error: ifelse expected bool".to_string()),
     }
     Ok(())
@@ -713,9 +1869,13 @@ fn for_op(ctx: &mut Context) -> Result<(), String> {
     let step = ctx.pop().ok_or("Stack underflow".to_string())?;
     let initial = ctx.pop().ok_or("Stack underflow".to_string())?;
     
-    // Convert all values to f64 for consistent handling
-    let (current, step_val, limit_val) = match (initial, step, limit) {
-        (PostScriptValue::Int(i), PostScriptValue::Int(s), PostScriptValue::Int(l)) => (i as f64, s as f64, l as f64),
+    // The control value is an integer, stepped exactly, when all three operands are
+    // integers; otherwise convert all values to f64
+    let (initial, step_val, limit_val) = match (initial, step, limit) {
+        (PostScriptValue::Int(i), PostScriptValue::Int(s), PostScriptValue::Int(l)) => {
+            ctx.schedule(PostScriptValue::IntForLoop { next: Some(i), step: s, limit: l, proc: Box::new(proc) });
+            return Ok(());
+        }
         (PostScriptValue::Real(i), PostScriptValue::Real(s), PostScriptValue::Real(l)) => (i, s, l),
         (i, s, l) => {
             let i = match i { PostScriptValue::Int(v) => v as f64, PostScriptValue::Real(v) => v, _ => return Err("Type error".to_string()) };
@@ -726,10 +1886,48 @@ fn for_op(ctx: &mut Context) -> Result<(), String> {
     };
 
     // Push ForLoop state to execution stack - the interpreter will handle the iteration
-    ctx.execution_stack.push(PostScriptValue::ForLoop {
-        current,
+    ctx.schedule(PostScriptValue::ForLoop {
+        initial,
         step: step_val,
         limit: limit_val,
+        index: 0,
+        proc: Box::new(proc),
+    });
+    Ok(())
+}
+
+/// forall: Execute a procedure for each element of a composite object
+/// Stack: array|string|dict proc → (empty)
+/// For arrays (and procedures), pushes each element; for strings, each character
+/// code; for dictionaries, each key (as a literal name) and its value, in key order.
+/// The elements are taken when the loop starts, so changes the procedure makes to
+/// the object do not affect which elements are visited.
+fn forall(ctx: &mut Context) -> Result<(), String> {
+    let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let composite = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.check_access(&composite, Access::ReadOnly, "forall")?;
+    let (items, width) = match composite {
+        PostScriptValue::Array(arr) => (arr.borrow().clone(), 1),
+        PostScriptValue::Block(items) | PostScriptValue::Closure { body: items, .. } => (items, 1),
+        PostScriptValue::String(s) => (s.borrow().chars().map(|c| PostScriptValue::Int(c as i64)).collect(), 1),
+        PostScriptValue::Dict(d) => {
+            let mut entries: Vec<(String, PostScriptValue)> = d.borrow().iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let items = entries.into_iter()
+                .flat_map(|(k, v)| [PostScriptValue::LiteralName(k), v])
+                .collect();
+            (items, 2)
+        }
+        other => return Err(format!("Type check error: forall expected array, string or dict, got {}", other.type_name())),
+    };
+
+    // Push the loop state to the execution stack - the interpreter will handle the iteration
+    ctx.schedule(PostScriptValue::ForAllLoop {
+        items,
+        index: 0,
+        width,
         proc: Box::new(proc),
     });
     Ok(())
@@ -747,7 +1945,7 @@ fn repeat(ctx: &mut Context) -> Result<(), String> {
                 return Err("Range check error".to_string());
             }
             // Push RepeatLoop state to execution stack - the interpreter will handle the iteration
-            ctx.execution_stack.push(PostScriptValue::RepeatLoop {
+            ctx.schedule(PostScriptValue::RepeatLoop {
                 count: n,
                 proc: Box::new(proc),
             });
@@ -757,22 +1955,476 @@ fn repeat(ctx: &mut Context) -> Result<(), String> {
     Ok(())
 }
 
+/// exec: Execute an object
+/// Stack: any → (results of executing any)
+/// Procedures, executable strings and executable files are scheduled on the execution
+/// stack, operators are called, names are looked up, null does nothing; any other
+/// literal object is pushed back unchanged
+fn exec(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::Block(_) | PostScriptValue::Closure { .. } => ctx.schedule_procedure(&a),
+        PostScriptValue::NativeFn(f) => f(ctx)?,
+        PostScriptValue::Name(_) | PostScriptValue::ExecutableString(_) | PostScriptValue::ExecutableFile(_) => ctx.schedule(a),
+        PostScriptValue::Null => {}
+        other => ctx.push(other),
+    }
+    Ok(())
+}
+
+/// stop: Terminate the innermost stopped context
+/// Stack: (empty) → (empty)
+/// Fails with an error that the interpreter unwinds to the enclosing `stopped`; with
+/// no context active the error reaches the caller
+fn stop(ctx: &mut Context) -> Result<(), String> {
+    if ctx.execution_stack().iter().any(|v| matches!(v, PostScriptValue::StopContext)) {
+        Err("Stop executed".to_string())
+    } else {
+        Err("Stop executed outside of a stopped context".to_string())
+    }
+}
+
+/// stopped: Execute a procedure, catching stop and errors
+/// Stack: proc → bool
+/// A StopContext marker below the procedure pushes false when it is reached; an error
+/// or `stop` inside the procedure unwinds to it and pushes true (see Interpreter)
+fn stopped(ctx: &mut Context) -> Result<(), String> {
+    let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match proc {
+        PostScriptValue::Block(_) | PostScriptValue::Closure { .. } => {
+            ctx.schedule(PostScriptValue::StopContext);
+            ctx.schedule_procedure(&proc);
+            Ok(())
+        }
+        other => Err(format!("Type check error: stopped expected a procedure, got {}", other.type_name())),
+    }
+}
+
+/// loop: Execute a procedure until exit
+/// Stack: proc → (empty)
+fn loop_op(ctx: &mut Context) -> Result<(), String> {
+    let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.schedule(PostScriptValue::Loop { proc: Box::new(proc) });
+    Ok(())
+}
+
+/// exit: Leave the innermost loop
+/// Stack: (empty) → (empty)
+/// Abandons the rest of the loop's procedure and the loop state itself, restoring
+/// the dictionary stack if a closure is abandoned. A stopped context or executive
+/// between exit and the loop makes it an invalidexit error, as does having no loop
+fn exit(ctx: &mut Context) -> Result<(), String> {
+    let stack = ctx.execution_stack();
+    let boundary = stack.iter().rposition(|v| matches!(
+        v,
+        PostScriptValue::ForLoop { .. }
+            | PostScriptValue::IntForLoop { .. }
+            | PostScriptValue::RepeatLoop { .. }
+            | PostScriptValue::Loop { .. }
+            | PostScriptValue::ForAllLoop { .. }
+            | PostScriptValue::StopContext
+            | PostScriptValue::Executive { .. }
+    ));
+    let at = match boundary.map(|at| (at, &stack[at])) {
+        Some((_, PostScriptValue::StopContext | PostScriptValue::Executive { .. })) | None => {
+            return Err("Invalid exit: no loop to exit".to_string());
+        }
+        Some((at, _)) => at,
+    };
+    let abandoned = ctx.take_scheduled(stack.len() - at);
+    if let Some(PostScriptValue::RestoreEnv(env)) = abandoned.into_iter()
+        .find(|v| matches!(v, PostScriptValue::RestoreEnv(_)))
+    {
+        ctx.replace_dict_stack(env)?;
+    }
+    Ok(())
+}
+
+/// bind: Resolve a procedure's operator names now
+/// Stack: proc → proc
+/// Executable names whose current value is an operator are replaced by the operator,
+/// in nested procedures too; the interpreter runs an operator met in a procedure
+/// directly, without a dictionary lookup. Other names are left for execution time.
+fn bind(ctx: &mut Context) -> Result<(), String> {
+    let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(match proc {
+        PostScriptValue::Block(body) => PostScriptValue::Block(bind_body(ctx, body)),
+        PostScriptValue::Closure { body, env } => PostScriptValue::Closure { body: bind_body(ctx, body), env },
+        other => return Err(format!("Type check error: bind expected a procedure, got {}", other.type_name())),
+    });
+    Ok(())
+}
+
+/// Binds the values of a procedure body (see bind).
+fn bind_body(ctx: &Context, body: Vec<PostScriptValue>) -> Vec<PostScriptValue> {
+    body.into_iter()
+        .map(|value| match value {
+            PostScriptValue::Name(ref name) => match ctx.lookup(name) {
+                Some(operator @ PostScriptValue::NativeFn(_)) => operator,
+                _ => value,
+            },
+            PostScriptValue::Block(inner) => PostScriptValue::Block(bind_body(ctx, inner)),
+            other => other,
+        })
+        .collect()
+}
+
+/// countexecstack: Count the entries on the execution stack
+/// Stack: (empty) → int
+/// The execution stack holds the values that remain to be executed of every active
+/// procedure (the rest of its body, one entry per value), loop states and files
+fn countexecstack(ctx: &mut Context) -> Result<(), String> {
+    let depth = ctx.execution_stack().len() as i64;
+    ctx.push(PostScriptValue::Int(depth));
+    Ok(())
+}
+
+/// execstack: Copy the execution stack into an array
+/// Stack: array → subarray
+/// Works like dictstack. Entries that are interpreter state rather than PostScript
+/// objects (loop states, stopped contexts, closure bookkeeping) are stored as
+/// executable names of their `--...--` text, so executing them again is an
+/// undefined error instead of corrupting the loop they came from.
+fn execstack(ctx: &mut Context) -> Result<(), String> {
+    let arr = match ctx.peek().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Array(arr) => arr.clone(),
+        other => return Err(format!("Type check error: execstack expected an array, got {}", other.type_name())),
+    };
+    ctx.check_access(&PostScriptValue::Array(arr.clone()), Access::Unlimited, "execstack")?;
+    let entries: Vec<PostScriptValue> = ctx.execution_stack().iter()
+        .map(|v| match v {
+            PostScriptValue::ForLoop { .. }
+            | PostScriptValue::IntForLoop { .. }
+            | PostScriptValue::RepeatLoop { .. }
+            | PostScriptValue::Loop { .. }
+            | PostScriptValue::ForAllLoop { .. }
+            | PostScriptValue::SortLoop(_)
+            | PostScriptValue::StopContext
+            | PostScriptValue::Executive { .. }
+            | PostScriptValue::RestoreEnv(_) => PostScriptValue::Name(v.to_string()),
+            other => other.clone(),
+        })
+        .collect();
+    let len = arr.borrow().len();
+    if entries.len() > len {
+        return Err(format!("Range check error: {} entries do not fit in an array of length {}", entries.len(), len));
+    }
+    ctx.pop();
+    arr.borrow_mut()[..entries.len()].clone_from_slice(&entries);
+    ctx.push(PostScriptValue::Array(Rc::new(RefCell::new(entries))));
+    Ok(())
+}
+
 /// quit: Terminate the interpreter
 /// Stack: (empty) → (exits program)
-fn quit(_ctx: &mut Context) -> Result<(), String> {
+/// Flushes the output sink first so buffered output is not lost
+fn quit(ctx: &mut Context) -> Result<(), String> {
+    let _ = ctx.flush_output();
     std::process::exit(0);
 }
 
+// ============================================================================
+// Type Conversion
+// ============================================================================
+
+/// type: Name the type of an object
+/// Stack: any → name
+/// Pushes a literal name such as /integertype (see PostScriptValue::type_name)
+fn type_op(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(PostScriptValue::LiteralName(a.type_name().to_string()));
+    Ok(())
+}
+
+/// cvx: Make an object executable
+/// Stack: any → any
+/// Arrays become procedures, literal names executable names, strings executable
+/// strings that share the original's characters, and files executable files that
+/// share the open file. Other objects are returned unchanged.
+fn cvx(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(match a {
+        PostScriptValue::Array(items) => PostScriptValue::Block(items.borrow().clone()),
+        PostScriptValue::LiteralName(n) => PostScriptValue::Name(n),
+        PostScriptValue::String(s) => PostScriptValue::ExecutableString(s),
+        PostScriptValue::File(f) => PostScriptValue::ExecutableFile(f),
+        other => other,
+    });
+    Ok(())
+}
+
+/// cvlit: Make an object literal
+/// Stack: any → any
+/// Procedures become arrays, executable names literal names, executable strings
+/// strings that share the original's characters, and executable files files. Other
+/// objects are returned unchanged.
+fn cvlit(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(match a {
+        PostScriptValue::Block(items) | PostScriptValue::Closure { body: items, .. } => {
+            PostScriptValue::Array(Rc::new(RefCell::new(items)))
+        }
+        PostScriptValue::Name(n) => PostScriptValue::LiteralName(n),
+        PostScriptValue::ExecutableString(s) => PostScriptValue::String(s),
+        PostScriptValue::ExecutableFile(f) => PostScriptValue::File(f),
+        other => other,
+    });
+    Ok(())
+}
+
+/// xcheck: Test whether an object is executable
+/// Stack: any → bool
+fn xcheck(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(PostScriptValue::Bool(a.is_executable()));
+    Ok(())
+}
+
+/// Pops the operand of the access operators, which must be a dictionary, array,
+/// string or procedure (dictionaries only if dicts_allowed).
+fn pop_composite(ctx: &mut Context, operator: &str, dicts_allowed: bool) -> Result<PostScriptValue, String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::Dict(_) if !dicts_allowed => {
+            Err(format!("Type check error: {} expected an array or string, got dicttype", operator))
+        }
+        PostScriptValue::Array(_)
+        | PostScriptValue::Dict(_)
+        | PostScriptValue::String(_)
+        | PostScriptValue::ExecutableString(_)
+        | PostScriptValue::Block(_)
+        | PostScriptValue::Closure { .. } => Ok(a),
+        other => Err(format!("Type check error: {} expected a dict, array or string, got {}", operator, other.type_name())),
+    }
+}
+
+/// readonly: Forbid changes to an object
+/// Stack: composite → composite
+/// Access belongs to the object, so every reference to it becomes read-only.
+/// Procedures are not shared objects and are returned unchanged
+fn readonly(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_composite(ctx, "readonly", true)?;
+    ctx.restrict_access(&a, Access::ReadOnly);
+    ctx.push(a);
+    Ok(())
+}
+
+/// executeonly: Forbid reading and changing an array or string
+/// Stack: array|string → array|string
+fn executeonly(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_composite(ctx, "executeonly", false)?;
+    ctx.restrict_access(&a, Access::ExecuteOnly);
+    ctx.push(a);
+    Ok(())
+}
+
+/// noaccess: Forbid all access to an object
+/// Stack: composite → composite
+fn noaccess(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_composite(ctx, "noaccess", true)?;
+    ctx.restrict_access(&a, Access::NoAccess);
+    ctx.push(a);
+    Ok(())
+}
+
+/// rcheck: Test whether an object can be read
+/// Stack: composite → bool
+fn rcheck(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_composite(ctx, "rcheck", true)?;
+    ctx.push(PostScriptValue::Bool(ctx.access(&a) >= Access::ReadOnly));
+    Ok(())
+}
+
+/// wcheck: Test whether an object can be changed
+/// Stack: composite → bool
+fn wcheck(ctx: &mut Context) -> Result<(), String> {
+    let a = pop_composite(ctx, "wcheck", true)?;
+    ctx.push(PostScriptValue::Bool(ctx.access(&a) == Access::Unlimited));
+    Ok(())
+}
+
+/// cvn: Convert a string to a name
+/// Stack: string → name
+/// The name is literal, or executable if the string was (made by cvx)
+fn cvn(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    match a {
+        PostScriptValue::String(s) => ctx.push(PostScriptValue::LiteralName(s.borrow().clone())),
+        PostScriptValue::ExecutableString(s) => ctx.push(PostScriptValue::Name(s.borrow().clone())),
+        other => return Err(format!("Type check error: cvn expected a string, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// cvs: Convert an object to its text
+/// Stack: any string → substring
+/// Writes the text of any into the start of string and pushes a string holding the
+/// characters written. The result is a copy, like getinterval's: later changes to
+/// the buffer do not show in it. Raises rangecheck if the buffer is too short.
+fn cvs(ctx: &mut Context) -> Result<(), String> {
+    let buffer = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let any = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let PostScriptValue::String(buffer) = buffer else {
+        return Err(format!("Type check error: cvs expected a string buffer, got {}", buffer.type_name()));
+    };
+    write_text(ctx, &buffer, text_form(&any), "cvs")
+}
+
+/// cvrs: Convert a number to its text in a radix
+/// Stack: num radix string → substring
+/// Radix 10 gives the same text as cvs. Other radices (2 to 36, with digits 0-9 and
+/// A-Z) write the number as an unsigned integer: reals are truncated toward zero, and
+/// negative numbers are written as their 64-bit two's complement, the width of
+/// PostScript integers here (-1 16 ... cvrs gives FFFFFFFFFFFFFFFF).
+fn cvrs(ctx: &mut Context) -> Result<(), String> {
+    let buffer = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let radix = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let num = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let PostScriptValue::String(buffer) = buffer else {
+        return Err(format!("Type check error: cvrs expected a string buffer, got {}", buffer.type_name()));
+    };
+    let radix = match radix {
+        PostScriptValue::Int(r) if (2..=36).contains(&r) => r as u64,
+        PostScriptValue::Int(r) => return Err(format!("Range check error: radix {} outside 2-36", r)),
+        other => return Err(format!("Type check error: cvrs expected an int radix, got {}", other.type_name())),
+    };
+    let n = match num {
+        PostScriptValue::Int(_) | PostScriptValue::Real(_) if radix == 10 => {
+            return write_text(ctx, &buffer, text_form(&num), "cvrs");
+        }
+        PostScriptValue::Int(i) => i,
+        PostScriptValue::Real(f) => real_to_int(f)?,
+        other => return Err(format!("Type check error: cvrs expected a number, got {}", other.type_name())),
+    };
+    const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut n = n as u64;
+    let mut digits = Vec::new();
+    loop {
+        digits.push(DIGITS[(n % radix) as usize] as char);
+        n /= radix;
+        if n == 0 {
+            break;
+        }
+    }
+    write_text(ctx, &buffer, digits.into_iter().rev().collect(), "cvrs")
+}
+
+/// Writes text into the start of a string buffer for cvs and cvrs and pushes a new
+/// string holding the characters written. Raises rangecheck if the buffer is too short.
+fn write_text(ctx: &mut Context, buffer: &Rc<RefCell<String>>, text: String, operator: &str) -> Result<(), String> {
+    ctx.check_access(&PostScriptValue::String(buffer.clone()), Access::Unlimited, operator)?;
+    let text_chars: Vec<char> = text.chars().collect();
+    let mut chars: Vec<char> = buffer.borrow().chars().collect();
+    if text_chars.len() > chars.len() {
+        return Err(format!("Range check error: {} characters do not fit in a string of length {}", text_chars.len(), chars.len()));
+    }
+    chars[..text_chars.len()].copy_from_slice(&text_chars);
+    *buffer.borrow_mut() = chars.into_iter().collect();
+    ctx.push(PostScriptValue::String(Rc::new(RefCell::new(text))));
+    Ok(())
+}
+
+/// cvi: Convert to integer
+/// Stack: num|string → int
+/// Reals are truncated toward zero; a real outside the integer range raises rangecheck.
+/// A string is scanned as a number token, and must hold exactly one number.
+fn cvi(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let a = scan_number(ctx, a, "cvi")?;
+    match a {
+        PostScriptValue::Int(i) => ctx.push(PostScriptValue::Int(i)),
+        PostScriptValue::Real(f) => ctx.push(PostScriptValue::Int(real_to_int(f)?)),
+        other => return Err(format!("Type check error: cvi expected a number or string, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// Truncates a real toward zero, raising rangecheck outside the integer range.
+fn real_to_int(f: f64) -> Result<i64, String> {
+    let t = f.trunc();
+    if !(i64::MIN as f64..-(i64::MIN as f64)).contains(&t) {
+        return Err(format!("Range check error: {} is outside the integer range", f));
+    }
+    Ok(t as i64)
+}
+
+/// cvr: Convert to real
+/// Stack: num|string → real
+/// A string is scanned as a number token, and must hold exactly one number.
+fn cvr(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let a = scan_number(ctx, a, "cvr")?;
+    match a {
+        PostScriptValue::Int(i) => ctx.push(PostScriptValue::Real(i as f64)),
+        PostScriptValue::Real(f) => ctx.push(PostScriptValue::Real(f)),
+        other => return Err(format!("Type check error: cvr expected a number or string, got {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// Replaces a string operand of `cvi` or `cvr` by the number it holds, scanned with the
+/// tokenizer so that any number syntax the language level accepts is understood.
+/// Other operands are returned unchanged.
+fn scan_number(ctx: &Context, value: PostScriptValue, operator: &str) -> Result<PostScriptValue, String> {
+    let PostScriptValue::String(s) = &value else {
+        return Ok(value);
+    };
+    let text = s.borrow().clone();
+    let values = Tokenizer::from_bytes_with_language_level(&latin1_bytes(&text), ctx.language_level)
+        .tokenize()
+        .and_then(parse);
+    match values.as_deref() {
+        Ok([number @ (PostScriptValue::Int(_) | PostScriptValue::Real(_))]) => Ok(number.clone()),
+        _ => Err(format!("Type check error: {} expected a number in the string, got ({})", operator, text)),
+    }
+}
+
+/// The text `cvs` produces for a value: numbers, booleans and names as written (names
+/// without a slash), strings as their characters, anything else --nostringval--.
+fn text_form(value: &PostScriptValue) -> String {
+    match value {
+        PostScriptValue::Int(i) => i.to_string(),
+        PostScriptValue::Real(r) => format_real(*r),
+        PostScriptValue::Bool(b) => b.to_string(),
+        PostScriptValue::Name(n) | PostScriptValue::LiteralName(n) => n.clone(),
+        PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => s.borrow().clone(),
+        _ => "--nostringval--".to_string(),
+    }
+}
+
+/// Formats a real the way PostScript writes it: always with a fraction or an exponent,
+/// so it reads back as a real (`1.0`, `0.5`, `1.0e+20`).
+fn format_real(r: f64) -> String {
+    if !r.is_finite() {
+        return r.to_string();
+    }
+    if r != 0.0 && !(1e-4..1e16).contains(&r.abs()) {
+        let text = format!("{:e}", r);
+        let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+        let mantissa = if mantissa.contains('.') { mantissa.to_string() } else { format!("{}.0", mantissa) };
+        let exponent = exponent.strip_prefix('-').map_or(format!("+{}", exponent), |e| format!("-{}", e));
+        return format!("{}e{}", mantissa, exponent);
+    }
+    let text = r.to_string();
+    if text.contains('.') { text } else { format!("{}.0", text) }
+}
+
 // ============================================================================
 // Input/Output Operations
 // ============================================================================
+//
+// All output goes through the Context's output sink (standard output by default).
 
-/// print: Print a string to stdout
+/// print: Print a string to the output sink
 /// Stack: string → (empty)
 fn print(ctx: &mut Context) -> Result<(), String> {
     let s = ctx.pop().ok_or("Stack underflow".to_string())?;
     match s {
-        PostScriptValue::String(s) => print!("{}", s.borrow()),
+        PostScriptValue::String(s) => {
+            ctx.check_access(&PostScriptValue::String(s.clone()), Access::ReadOnly, "print")?;
+            let bytes = latin1_bytes(&s.borrow());
+            ctx.write_output_bytes(&bytes)?;
+        }
         _ => return Err("Type check error".to_string()),
     }
     Ok(())
@@ -780,18 +2432,901 @@ fn print(ctx: &mut Context) -> Result<(), String> {
 
 /// =: Print text representation of a value
 /// Stack: any → (empty)
-/// Prints the value in human-readable form
+/// Prints the value in human-readable form (strings without parentheses, names
+/// without a slash)
 fn eq_print(ctx: &mut Context) -> Result<(), String> {
     let any = ctx.pop().ok_or("Stack underflow".to_string())?;
-    println!("{}", any);
-    Ok(())
+    ctx.write_output_bytes(&eq_bytes(&any))?;
+    ctx.write_output("\n")
 }
 
 /// ==: Print PostScript representation of a value
 /// Stack: any → (empty)
-/// Prints the value in PostScript syntax (e.g., strings with parentheses)
+/// Prints the value in PostScript syntax (strings in parentheses with escapes,
+/// literal names with a slash, dictionaries as -dict-)
 fn eqeq_print(ctx: &mut Context) -> Result<(), String> {
     let any = ctx.pop().ok_or("Stack underflow".to_string())?;
-    println!("{}", any);
+    ctx.write_output(&format!("{}\n", eqeq_text(&any)))?;
+    Ok(())
+}
+
+/// The bytes `=` prints for a value (also used by `stack`): the `cvs` text (see
+/// `text_form`), strings one byte per character and names as UTF-8; null prints
+/// as `null`.
+fn eq_bytes(value: &PostScriptValue) -> Vec<u8> {
+    match value {
+        PostScriptValue::Null => b"null".to_vec(),
+        PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => latin1_bytes(&s.borrow()),
+        PostScriptValue::Name(n) | PostScriptValue::LiteralName(n) => n.clone().into_bytes(),
+        other => text_form(other).into_bytes(),
+    }
+}
+
+/// The text `==` prints for a value (also used by `pstack`): PostScript syntax
+/// (see `PostScriptValue::to_ps_syntax`).
+fn eqeq_text(value: &PostScriptValue) -> String {
+    value.to_ps_syntax()
+}
+
+/// stack: Print the operand stack with = semantics
+/// Stack: any1 ... anyn → any1 ... anyn
+/// One operand per line, top first; the stack is not changed
+fn stack(ctx: &mut Context) -> Result<(), String> {
+    let mut bytes = Vec::new();
+    for value in ctx.operand_stack().iter().rev() {
+        bytes.extend(eq_bytes(value));
+        bytes.push(b'\n');
+    }
+    ctx.write_output_bytes(&bytes)
+}
+
+/// pstack: Print the operand stack with == semantics
+/// Stack: any1 ... anyn → any1 ... anyn
+/// One operand per line, top first; the stack is not changed
+fn pstack(ctx: &mut Context) -> Result<(), String> {
+    let text: String = ctx.operand_stack().iter().rev()
+        .map(|value| format!("{}\n", eqeq_text(value)))
+        .collect();
+    ctx.write_output(&text)
+}
+
+/// executive: Run an interactive loop on the Context's input
+/// Stack: (empty) → (empty)
+/// Each round executes `prompt` (looked up in the dictionary stack, so programs can
+/// redefine it), then reads a line and executes it. Errors are printed and the loop
+/// goes on; it returns at the end of the input (see Interpreter)
+fn executive(ctx: &mut Context) -> Result<(), String> {
+    ctx.schedule(PostScriptValue::Executive { prompted: false });
+    Ok(())
+}
+
+/// prompt: Print the prompt of executive
+/// Stack: (empty) → (empty)
+fn prompt(ctx: &mut Context) -> Result<(), String> {
+    ctx.write_output("PS> ")
+}
+
+/// file: Open a file
+/// Stack: name access → file
+/// The name is a string or name; access is (r), (w) (truncating) or (a) (appending).
+/// %stdin, %stdout and %stderr open the Context's input, the Context's output and
+/// error output
+fn file(ctx: &mut Context) -> Result<(), String> {
+    let access = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let name = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let (name, access) = match (&name, &access) {
+        (PostScriptValue::String(n), PostScriptValue::String(a)) => (n.borrow().clone(), a.borrow().clone()),
+        (PostScriptValue::LiteralName(n) | PostScriptValue::Name(n), PostScriptValue::String(a)) => (n.clone(), a.borrow().clone()),
+        (a, b) => {
+            return Err(format!("Type check error: file expected a string and an access string, got {} and {}", a.type_name(), b.type_name()));
+        }
+    };
+    let stream = match (name.as_str(), access.as_str()) {
+        ("%stdin", "r") => FileStream::Stdin,
+        ("%stdout", "w" | "a") => FileStream::Stdout,
+        ("%stderr", "w" | "a") => FileStream::Stderr,
+        ("%stdin" | "%stdout" | "%stderr", _) => {
+            return Err(format!("Invalid file access: {} cannot be opened with access ({})", name, access));
+        }
+        (path, "r") => FileStream::Reader(Box::new(BufReader::new(open_file(path, OpenOptions::new().read(true))?))),
+        (path, "w") => FileStream::Writer(Box::new(open_file(path, OpenOptions::new().write(true).create(true).truncate(true))?)),
+        (path, "a") => FileStream::Writer(Box::new(open_file(path, OpenOptions::new().append(true).create(true))?)),
+        (_, other) => return Err(format!("Invalid file access: unknown access ({})", other)),
+    };
+    ctx.push(PostScriptValue::File(Rc::new(RefCell::new(PostScriptFile { name, stream }))));
+    Ok(())
+}
+
+/// currentfile: Push the file being executed
+/// Stack: (empty) → file
+/// The file is positioned after the currentfile operator itself, so reading from it
+/// consumes the data that follows in the program, which then resumes after that
+/// data. For a line of interactive input (in the REPL, or executive; see
+/// `STATEMENT_FILE`), this is the Context's input, which holds the lines after it
+fn currentfile(ctx: &mut Context) -> Result<(), String> {
+    let executing = ctx.execution_stack().iter().rev().find_map(|v| match v {
+        PostScriptValue::ExecutableFile(file) if file.borrow().name == STATEMENT_FILE => Some(None),
+        PostScriptValue::ExecutableFile(file) => Some(Some(file.clone())),
+        PostScriptValue::Executive { .. } => Some(None),
+        _ => None,
+    });
+    let file = executing.flatten().unwrap_or_else(|| {
+        Rc::new(RefCell::new(PostScriptFile { name: "%stdin".to_string(), stream: FileStream::Stdin }))
+    });
+    ctx.push(PostScriptValue::File(file));
+    Ok(())
+}
+
+/// Opens a file for `file`; a missing file is an undefinedfilename error.
+fn open_file(path: &str, options: &OpenOptions) -> Result<File, String> {
+    options.open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!("Undefined filename: {}", path),
+        _ => format!("I/O error: {}: {}", path, e),
+    })
+}
+
+/// Pops a file object for the file operators.
+fn pop_file(ctx: &mut Context, operator: &str) -> Result<Rc<RefCell<PostScriptFile>>, String> {
+    match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::File(f) | PostScriptValue::ExecutableFile(f) => Ok(f),
+        other => Err(format!("Type check error: {} expected a file, got {}", operator, other.type_name())),
+    }
+}
+
+/// The reader behind a file, or None at the end of a closed file.
+fn file_reader<'a>(ctx: &'a mut Context, stream: &'a mut FileStream, name: &str) -> Result<Option<&'a mut dyn BufRead>, String> {
+    match stream {
+        FileStream::Stdin => Ok(Some(&mut *ctx.input)),
+        FileStream::Reader(reader) => Ok(Some(&mut **reader)),
+        FileStream::Source(tokenizer) => Ok(Some(tokenizer)),
+        FileStream::Decode(filter) => {
+            if filter.data.is_none() {
+                let mut source = filter.source.borrow_mut();
+                let PostScriptFile { name, stream } = &mut *source;
+                let data = match file_reader(ctx, stream, name)? {
+                    Some(reader) => filter.decoder.decode(reader)?,
+                    None => Vec::new(),
+                };
+                drop(source);
+                filter.data = Some(Cursor::new(data));
+            }
+            Ok(filter.data.as_mut().map(|data| data as &mut dyn BufRead))
+        }
+        FileStream::Closed => Ok(None),
+        _ => Err(format!("Invalid file access: {} is not open for reading", name)),
+    }
+}
+
+/// Writes bytes to a file.
+fn write_file(ctx: &mut Context, file: &Rc<RefCell<PostScriptFile>>, bytes: &[u8]) -> Result<(), String> {
+    let mut file = file.borrow_mut();
+    let name = file.name.clone();
+    let result = match &mut file.stream {
+        FileStream::Stdout => ctx.output.write_all(bytes),
+        FileStream::Stderr => ctx.error_output.write_all(bytes),
+        FileStream::Writer(writer) => writer.write_all(bytes),
+        FileStream::Encode(filter) => {
+            let mut encoded = Vec::new();
+            filter.encoder.encode(bytes, &mut encoded);
+            return write_target(ctx, &mut filter.target, &encoded);
+        }
+        FileStream::Closed => return Err(format!("I/O error: {} is closed", name)),
+        _ => return Err(format!("Invalid file access: {} is not open for writing", name)),
+    };
+    result.map_err(|e| format!("I/O error: {}: {}", name, e))
+}
+
+/// Writes encoded data to the target of an encode filter.
+fn write_target(ctx: &mut Context, target: &mut FilterTarget, bytes: &[u8]) -> Result<(), String> {
+    match target {
+        FilterTarget::File(file) => write_file(ctx, file, bytes),
+        FilterTarget::String { string, position } => {
+            let mut chars: Vec<char> = string.borrow().chars().collect();
+            if *position + bytes.len() > chars.len() {
+                return Err(format!("I/O error: filter target string of length {} is full", chars.len()));
+            }
+            for &byte in bytes {
+                chars[*position] = byte as char;
+                *position += 1;
+            }
+            *string.borrow_mut() = chars.into_iter().collect();
+            Ok(())
+        }
+    }
+}
+
+/// read: Read one byte from a file
+/// Stack: file → int true, or file → false at the end of the file
+fn read(ctx: &mut Context) -> Result<(), String> {
+    let file = pop_file(ctx, "read")?;
+    let mut file = file.borrow_mut();
+    let PostScriptFile { name, stream } = &mut *file;
+    let byte = match file_reader(ctx, stream, name)? {
+        Some(reader) => {
+            let buffer = reader.fill_buf().map_err(|e| format!("I/O error: {}: {}", name, e))?;
+            let byte = buffer.first().copied();
+            if byte.is_some() {
+                reader.consume(1);
+            }
+            byte
+        }
+        None => None,
+    };
+    drop(file);
+    match byte {
+        Some(b) => {
+            ctx.push(PostScriptValue::Int(b as i64));
+            ctx.push(PostScriptValue::Bool(true));
+        }
+        None => ctx.push(PostScriptValue::Bool(false)),
+    }
+    Ok(())
+}
+
+/// readline: Read a line from a file
+/// Stack: file string → substring bool
+/// The line is written into the start of string without its newline (LF or CR LF)
+/// and a new string holding it is pushed, like cvs does. bool is true if a newline
+/// ended the line, false if the file ended first. Each byte of the line becomes
+/// one character, as in string literals
+fn readline(ctx: &mut Context) -> Result<(), String> {
+    let buffer = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let PostScriptValue::String(buffer) = buffer else {
+        return Err(format!("Type check error: readline expected a string buffer, got {}", buffer.type_name()));
+    };
+    let file = pop_file(ctx, "readline")?;
+    let mut bytes = Vec::new();
+    {
+        let mut file = file.borrow_mut();
+        let PostScriptFile { name, stream } = &mut *file;
+        if let Some(reader) = file_reader(ctx, stream, name)? {
+            reader.read_until(b'\n', &mut bytes).map_err(|e| format!("I/O error: {}: {}", name, e))?;
+        }
+    }
+    let newline = bytes.ends_with(b"\n");
+    if newline {
+        bytes.pop();
+        if bytes.ends_with(b"\r") {
+            bytes.pop();
+        }
+    }
+    write_text(ctx, &buffer, bytes.iter().map(|&b| b as char).collect(), "readline")?;
+    ctx.push(PostScriptValue::Bool(newline));
+    Ok(())
+}
+
+/// readstring: Read bytes from a file into a string
+/// Stack: file string → substring bool
+/// Each byte becomes one character (as Latin-1). bool is true if the string was
+/// filled, false if the file ended first
+fn readstring(ctx: &mut Context) -> Result<(), String> {
+    read_bytes(ctx, "readstring", |reader, wanted| {
+        let mut bytes = Vec::with_capacity(wanted);
+        reader.take(wanted as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    })
+}
+
+/// readhexstring: Read hexadecimal data from a file into a string
+/// Stack: file string → substring bool
+/// Pairs of hexadecimal digits give one byte each; any other character, such as
+/// whitespace between lines of data, is skipped. bool is true if the string was
+/// filled, false if the file ended first (a last lone digit is then dropped)
+fn readhexstring(ctx: &mut Context) -> Result<(), String> {
+    read_bytes(ctx, "readhexstring", |reader, wanted| {
+        let mut bytes = Vec::with_capacity(wanted);
+        let mut high = None;
+        let mut digit = [0];
+        while bytes.len() < wanted && reader.read(&mut digit)? == 1 {
+            if let Some(low) = (digit[0] as char).to_digit(16) {
+                match high.take() {
+                    Some(high) => bytes.push((high * 16 + low) as u8),
+                    None => high = Some(low),
+                }
+            }
+        }
+        Ok(bytes)
+    })
+}
+
+/// Common part of readstring and readhexstring: reads as many bytes as the string
+/// buffer holds with the given function, then stores them like cvs does.
+fn read_bytes(
+    ctx: &mut Context,
+    operator: &str,
+    read: impl FnOnce(&mut dyn BufRead, usize) -> io::Result<Vec<u8>>,
+) -> Result<(), String> {
+    let buffer = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let PostScriptValue::String(buffer) = buffer else {
+        return Err(format!("Type check error: {} expected a string buffer, got {}", operator, buffer.type_name()));
+    };
+    let file = pop_file(ctx, operator)?;
+    let wanted = buffer.borrow().chars().count();
+    let bytes = {
+        let mut file = file.borrow_mut();
+        let PostScriptFile { name, stream } = &mut *file;
+        match file_reader(ctx, stream, name)? {
+            Some(reader) => read(reader, wanted).map_err(|e| format!("I/O error: {}: {}", name, e))?,
+            None => Vec::new(),
+        }
+    };
+    let filled = bytes.len() == wanted;
+    write_text(ctx, &buffer, bytes.iter().map(|&b| b as char).collect(), operator)?;
+    ctx.push(PostScriptValue::Bool(filled));
+    Ok(())
+}
+
+/// write: Write one byte to a file
+/// Stack: file int → (empty)
+fn write(ctx: &mut Context) -> Result<(), String> {
+    let byte = match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::Int(b) if (0..=255).contains(&b) => b as u8,
+        PostScriptValue::Int(b) => return Err(format!("Range check error: byte {} outside 0-255", b)),
+        other => return Err(format!("Type check error: write expected an int, got {}", other.type_name())),
+    };
+    let file = pop_file(ctx, "write")?;
+    write_file(ctx, &file, &[byte])
+}
+
+/// writestring: Write a string to a file
+/// Stack: file string → (empty)
+/// The string is written as print writes it, one byte per character
+fn writestring(ctx: &mut Context) -> Result<(), String> {
+    let text = match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::String(s) => s.borrow().clone(),
+        other => return Err(format!("Type check error: writestring expected a string, got {}", other.type_name())),
+    };
+    let file = pop_file(ctx, "writestring")?;
+    write_file(ctx, &file, &latin1_bytes(&text))
+}
+
+/// closefile: Close a file
+/// Stack: file → (empty)
+/// Written data is flushed first, and an encode filter ends its encoded data.
+/// Closing a standard stream only closes this file object; the stream itself stays
+/// open. Neither does closing a filter close its source or target
+fn closefile(ctx: &mut Context) -> Result<(), String> {
+    let file = pop_file(ctx, "closefile")?;
+    let mut file = file.borrow_mut();
+    let ended = match &mut file.stream {
+        FileStream::Encode(filter) => {
+            let mut encoded = Vec::new();
+            filter.encoder.finish(&mut encoded);
+            write_target(ctx, &mut filter.target, &encoded)
+        }
+        _ => Ok(()),
+    };
+    let flushed = ended.and_then(|()| flush_writer(ctx, &mut file));
+    file.stream = FileStream::Closed;
+    flushed
+}
+
+/// Flushes a file opened for writing; other files are left alone.
+fn flush_writer(ctx: &mut Context, file: &mut PostScriptFile) -> Result<(), String> {
+    let flushed = match &mut file.stream {
+        FileStream::Stdout => ctx.output.flush(),
+        FileStream::Stderr => ctx.error_output.flush(),
+        FileStream::Writer(writer) => writer.flush(),
+        FileStream::Encode(filter) => match &filter.target {
+            FilterTarget::File(target) => return flush_writer(ctx, &mut target.borrow_mut()),
+            FilterTarget::String { .. } => Ok(()),
+        },
+        _ => Ok(()),
+    };
+    flushed.map_err(|e| format!("I/O error: {}: {}", file.name, e))
+}
+
+/// flush: Write out buffered standard output
+/// Stack: (empty) → (empty)
+fn flush(ctx: &mut Context) -> Result<(), String> {
+    ctx.flush_output()
+}
+
+/// flushfile: Flush a file
+/// Stack: file → (empty)
+/// Buffered data of a file opened for writing is written out. For a file opened for
+/// reading, the rest of its data is read and discarded, as in the PLRM
+fn flushfile(ctx: &mut Context) -> Result<(), String> {
+    let file = pop_file(ctx, "flushfile")?;
+    let mut file = file.borrow_mut();
+    let PostScriptFile { name, stream } = &mut *file;
+    if matches!(stream, FileStream::Stdin | FileStream::Reader(_) | FileStream::Source(_) | FileStream::Decode(_)) {
+        if let Some(reader) = file_reader(ctx, stream, name)? {
+            io::copy(reader, &mut io::sink()).map_err(|e| format!("I/O error: {}: {}", name, e))?;
+        }
+        return Ok(());
+    }
+    flush_writer(ctx, &mut file)
+}
+
+/// filter: Make a filter file
+/// Stack: source name → file (decode filters), target name → file (encode filters),
+/// target recordSize /RunLengthEncode → file
+/// A decode filter reads the encoded data from a file or string source when it is
+/// first read, up to the end-of-data marker (see the filter module). An encode
+/// filter writes into a file or into a string target, filling the string from its
+/// start; writing past the end of the string is an ioerror. The record size of
+/// RunLengthEncode may be left out, and defaults to 0 (no records)
+fn filter(ctx: &mut Context) -> Result<(), String> {
+    let name = match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::LiteralName(n) | PostScriptValue::Name(n) => n,
+        other => return Err(format!("Type check error: filter expected a filter name, got {}", other.type_name())),
+    };
+    let stream = if let Some(decoder) = Decoder::by_name(&name) {
+        let operand = ctx.pop().ok_or("Stack underflow".to_string())?;
+        let source = match &operand {
+            PostScriptValue::File(f) | PostScriptValue::ExecutableFile(f) => f.clone(),
+            PostScriptValue::String(s) => {
+                ctx.check_access(&operand, Access::ReadOnly, "filter")?;
+                let bytes = latin1_bytes(&s.borrow());
+                Rc::new(RefCell::new(PostScriptFile { name: "string".to_string(), stream: FileStream::Reader(Box::new(Cursor::new(bytes))) }))
+            }
+            other => return Err(format!("Type check error: filter expected a file or string source, got {}", other.type_name())),
+        };
+        FileStream::Decode(Box::new(DecodeFilter { decoder, source, data: None }))
+    } else if let Some(mut encoder) = Encoder::by_name(&name) {
+        if let Encoder::RunLength { record_size, .. } = &mut encoder
+            && let Some(&PostScriptValue::Int(size)) = ctx.operand_stack().last()
+        {
+            if size < 0 {
+                return Err(format!("Range check error: negative record size {}", size));
+            }
+            *record_size = size as usize;
+            ctx.pop();
+        }
+        let operand = ctx.pop().ok_or("Stack underflow".to_string())?;
+        let target = match &operand {
+            PostScriptValue::File(f) | PostScriptValue::ExecutableFile(f) => FilterTarget::File(f.clone()),
+            PostScriptValue::String(s) => {
+                ctx.check_access(&operand, Access::Unlimited, "filter")?;
+                FilterTarget::String { string: s.clone(), position: 0 }
+            }
+            other => return Err(format!("Type check error: filter expected a file or string target, got {}", other.type_name())),
+        };
+        FileStream::Encode(Box::new(EncodeFilter { encoder, target }))
+    } else {
+        return Err(format!("Undefined name: {} is not a filter", name));
+    };
+    ctx.push(PostScriptValue::File(Rc::new(RefCell::new(PostScriptFile { name, stream }))));
+    Ok(())
+}
+
+// ============================================================================
+// Extensions (non-standard)
+// ============================================================================
+
+/// deepcopy: Recursively copy a composite value
+/// Stack: any → copy
+/// Dictionaries, arrays and strings are copied into new objects so that the copy
+/// and the original can be mutated independently. Shared and self-referential
+/// substructures are reproduced in the copy (see PostScriptValue::deep_clone).
+fn deepcopy(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.push(a.deep_clone());
+    Ok(())
+}
+
+/// getenv: Look up an environment variable
+/// Stack: string → value true, or string → false if the variable is not set
+/// Variables come from the Context's environment provider (see
+/// `Context::set_env_provider`), the process environment by default
+fn getenv(ctx: &mut Context) -> Result<(), String> {
+    let name = match ctx.pop().ok_or("Stack underflow".to_string())? {
+        PostScriptValue::String(s) => s.borrow().clone(),
+        PostScriptValue::LiteralName(n) | PostScriptValue::Name(n) => n,
+        other => return Err(format!("Type check error: getenv expected a string, got {}", other.type_name())),
+    };
+    match ctx.getenv(&name) {
+        Some(value) => {
+            ctx.allocate(Allocation::String(value.chars().count()))?;
+            ctx.push(new_string(&value));
+            ctx.push(PostScriptValue::Bool(true));
+        }
+        None => ctx.push(PostScriptValue::Bool(false)),
+    }
+    Ok(())
+}
+
+/// Default ordering used by `sort`: numbers by value (integers and reals compare
+/// with each other), strings by their bytes, names by their text.
+fn compare_for_sort(a: &PostScriptValue, b: &PostScriptValue) -> Result<Ordering, String> {
+    let number = |v: &PostScriptValue| match v {
+        PostScriptValue::Int(i) => Some(*i as f64),
+        PostScriptValue::Real(r) => Some(*r),
+        _ => None,
+    };
+    let ordering = match (a, b) {
+        (PostScriptValue::Int(x), PostScriptValue::Int(y)) => Some(x.cmp(y)),
+        (PostScriptValue::String(x), PostScriptValue::String(y)) => Some(x.borrow().as_bytes().cmp(y.borrow().as_bytes())),
+        (PostScriptValue::Name(x) | PostScriptValue::LiteralName(x), PostScriptValue::Name(y) | PostScriptValue::LiteralName(y)) => Some(x.cmp(y)),
+        _ => match (number(a), number(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y),
+            _ => None,
+        },
+    };
+    ordering.ok_or(format!("Type check error: sort cannot compare {} with {}", a.type_name(), b.type_name()))
+}
+
+/// sort: Sort the elements of an array
+/// Stack: array → array, or array proc → array
+/// Without a procedure, uses the default ordering (see compare_for_sort).
+/// With a procedure, `a b proc` must leave true if a has to come before b; the
+/// comparisons run through the execution stack (see MergeSort), so the procedure
+/// may do anything a procedure can. Both forms are stable.
+/// An array is sorted in place (it must be writable) and pushed back; a procedure
+/// is not shared, so a sorted copy of it is pushed. The array only changes once the
+/// sort completes: if it fails, the array is left as it was and nothing is pushed.
+/// A procedure on top of an array or procedure is always taken as the comparator,
+/// so to sort a procedure that sits on an array, make it a literal array first
+/// (`[1 2] {3 1 2} cvlit sort`).
+fn sort(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let is_proc = |v: &PostScriptValue| matches!(v, PostScriptValue::Block(_) | PostScriptValue::Closure { .. });
+    let elements = |v: &PostScriptValue| match v {
+        PostScriptValue::Array(items) => Some(items.borrow().clone()),
+        PostScriptValue::Block(items) => Some(items.clone()),
+        PostScriptValue::Closure { body, .. } => Some(body.clone()),
+        _ => None,
+    };
+
+    if is_proc(&a) && ctx.peek().and_then(elements).is_some() {
+        let array = ctx.pop().ok_or("Stack underflow".to_string())?;
+        ctx.check_access(&array, Access::Unlimited, "sort")?;
+        let items = elements(&array).ok_or("Type check error".to_string())?;
+        ctx.schedule(PostScriptValue::SortLoop(Box::new(MergeSort::new(items, a, array))));
+        return Ok(());
+    }
+
+    let mut items = elements(&a).ok_or(format!("Type check error: sort expected an array, got {}", a.type_name()))?;
+    ctx.check_access(&a, Access::Unlimited, "sort")?;
+    let mut error = None;
+    items.sort_by(|x, y| compare_for_sort(x, y).unwrap_or_else(|e| {
+        error.get_or_insert(e);
+        Ordering::Equal
+    }));
+    if let Some(e) = error {
+        return Err(e);
+    }
+    ctx.push(a.with_elements(items));
+    Ok(())
+}
+
+// ============================================================================
+// Virtual Memory
+// ============================================================================
+//
+// Objects are reference counted rather than allocated in a VM, so save and restore
+// work on snapshots of the objects' contents (see `SaveState`).
+
+/// save: Take a snapshot of all reachable composite objects
+/// Stack: (empty) → save
+fn save(ctx: &mut Context) -> Result<(), String> {
+    let snapshot = ctx.save();
+    ctx.push(PostScriptValue::Save(snapshot));
+    Ok(())
+}
+
+/// restore: Roll composite objects back to a snapshot
+/// Stack: save → (empty)
+/// The stack is left unchanged if the restore is invalid
+fn restore(ctx: &mut Context) -> Result<(), String> {
+    let snapshot = match ctx.peek() {
+        Some(PostScriptValue::Save(snapshot)) => snapshot.clone(),
+        Some(other) => return Err(format!("Type check error: restore expected a save object, got {}", other.type_name())),
+        None => return Err("Stack underflow".to_string()),
+    };
+    ctx.restore(&snapshot)?;
+    ctx.pop();
+    Ok(())
+}
+
+/// vmstatus: Report memory use
+/// Stack: (empty) → level used maximum
+/// used is the Context's estimate of the memory taken by the strings, arrays and
+/// dictionaries allocated so far (see `Context::allocate`); maximum is MaxLocalVM
+fn vmstatus(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Int(ctx.save_level() as i64));
+    ctx.push(PostScriptValue::Int(ctx.vm_used() as i64));
+    ctx.push(PostScriptValue::Int(ctx.limits.max_vm as i64));
+    Ok(())
+}
+
+// ============================================================================
+// Interpreter Information
+// ============================================================================
+
+/// version: Push the interpreter version
+/// Stack: (empty) → string
+/// Each call pushes a new string, so modifying it does not affect later calls
+fn version(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(new_string(VERSION));
+    Ok(())
+}
+
+/// product: Push the interpreter name
+/// Stack: (empty) → string
+fn product(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(new_string(PRODUCT));
+    Ok(())
+}
+
+/// revision: Push the interpreter revision number
+/// Stack: (empty) → int
+fn revision(ctx: &mut Context) -> Result<(), String> {
+    ctx.push(PostScriptValue::Int(REVISION));
+    Ok(())
+}
+
+/// usertime: Push the execution time in milliseconds
+/// Stack: (empty) → int
+/// Read from the Context's clock, so embedders can make it deterministic
+fn usertime(ctx: &mut Context) -> Result<(), String> {
+    let ms = ctx.clock.usertime();
+    ctx.push(PostScriptValue::Int(ms));
+    Ok(())
+}
+
+/// realtime: Push the wall-clock time in milliseconds
+/// Stack: (empty) → int
+/// Read from the Context's clock, so embedders can make it deterministic
+fn realtime(ctx: &mut Context) -> Result<(), String> {
+    let ms = ctx.clock.realtime();
+    ctx.push(PostScriptValue::Int(ms));
+    Ok(())
+}
+
+/// languagelevel: Push the emulated PostScript language level
+/// Stack: (empty) → int
+fn languagelevel(ctx: &mut Context) -> Result<(), String> {
+    let level = ctx.language_level as i64;
+    ctx.push(PostScriptValue::Int(level));
+    Ok(())
+}
+
+/// help: Print documentation for an operator or category
+/// Stack: /name → (empty), /category → (empty), (anything else) → (unchanged)
+/// If the top of the stack is a literal name it is consumed and used as the topic;
+/// otherwise the list of categories is printed and the stack is left alone.
+fn help(ctx: &mut Context) -> Result<(), String> {
+    let topic = match ctx.peek() {
+        Some(PostScriptValue::LiteralName(n)) => Some(n.clone()),
+        _ => None,
+    };
+    if topic.is_some() {
+        ctx.pop();
+    }
+    ctx.write_output(&help_text(topic.as_deref())?)?;
+    Ok(())
+}
+
+/// System parameters that scripts can read but not change: how the embedder
+/// sandboxed the interpreter.
+const READ_ONLY_SYSTEM_PARAMS: &[&str] = &["Strict", "EnvironmentAccess"];
+
+/// currentsystemparams: Push a dictionary of the interpreter limits
+/// Stack: (empty) → dict
+/// The dictionary contains MaxOpStack, MaxExecStack, MaxDictStack,
+/// MaxStringLength, MaxArrayLength, MaxDictLength and MaxLocalVM, Fuel (the
+/// remaining execution budget, only present when there is one), and the read-only
+/// flags Strict and EnvironmentAccess (whether getenv may read variables)
+fn currentsystemparams(ctx: &mut Context) -> Result<(), String> {
+    let mut params = HashMap::new();
+    params.insert("MaxOpStack".to_string(), PostScriptValue::Int(ctx.limits.max_operand_stack as i64));
+    params.insert("MaxExecStack".to_string(), PostScriptValue::Int(ctx.limits.max_exec_stack as i64));
+    params.insert("MaxDictStack".to_string(), PostScriptValue::Int(ctx.limits.max_dict_stack as i64));
+    params.insert("MaxStringLength".to_string(), PostScriptValue::Int(ctx.limits.max_string_length as i64));
+    params.insert("MaxArrayLength".to_string(), PostScriptValue::Int(ctx.limits.max_array_length as i64));
+    params.insert("MaxDictLength".to_string(), PostScriptValue::Int(ctx.limits.max_dict_length as i64));
+    params.insert("MaxLocalVM".to_string(), PostScriptValue::Int(ctx.limits.max_vm as i64));
+    if let Some(fuel) = ctx.fuel {
+        params.insert("Fuel".to_string(), PostScriptValue::Int(fuel.min(i64::MAX as u64) as i64));
+    }
+    params.insert("Strict".to_string(), PostScriptValue::Bool(ctx.is_strict()));
+    params.insert("EnvironmentAccess".to_string(), PostScriptValue::Bool(ctx.has_env_provider()));
+    ctx.push(PostScriptValue::Dict(Rc::new(RefCell::new(params))));
+    Ok(())
+}
+
+/// setsystemparams: Apply the interpreter limits found in a dictionary
+/// Stack: dict → (empty)
+/// Recognized keys are the ones reported by currentsystemparams; other keys are
+/// ignored, as in the PLRM. Each limit must be a positive integer, otherwise
+/// nothing is changed and a range check error is raised. Fuel can only be lowered
+/// (or set when the budget is unlimited), and the read-only flags cannot be set:
+/// both are invalid access errors.
+fn setsystemparams(ctx: &mut Context) -> Result<(), String> {
+    let a = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let params = match a {
+        PostScriptValue::Dict(d) => d,
+        _ => return Err("Type check error".to_string()),
+    };
+    let mut limits = ctx.limits.clone();
+    let mut fuel = ctx.fuel;
+    for (key, value) in params.borrow().iter() {
+        if READ_ONLY_SYSTEM_PARAMS.contains(&key.as_str()) {
+            return Err(format!("Invalid access: setsystemparams cannot change {}", key));
+        }
+        if key == "Fuel" {
+            match value {
+                PostScriptValue::Int(n) if *n < 0 => return Err(format!("Range check error: {} must not be negative", key)),
+                PostScriptValue::Int(n) if ctx.fuel.is_some_and(|left| *n as u64 > left) => {
+                    return Err(format!("Invalid access: setsystemparams cannot raise {}", key));
+                }
+                PostScriptValue::Int(n) => fuel = Some(*n as u64),
+                _ => return Err(format!("Type check error: {} expected int", key)),
+            }
+            continue;
+        }
+        let slot = match key.as_str() {
+            "MaxOpStack" => &mut limits.max_operand_stack,
+            "MaxExecStack" => &mut limits.max_exec_stack,
+            "MaxDictStack" => &mut limits.max_dict_stack,
+            "MaxStringLength" => &mut limits.max_string_length,
+            "MaxArrayLength" => &mut limits.max_array_length,
+            "MaxDictLength" => &mut limits.max_dict_length,
+            "MaxLocalVM" => &mut limits.max_vm,
+            _ => continue,
+        };
+        match value {
+            PostScriptValue::Int(n) if *n > 0 => *slot = *n as usize,
+            PostScriptValue::Int(_) => return Err(format!("Range check error: {} must be positive", key)),
+            _ => return Err(format!("Type check error: {} expected int", key)),
+        }
+    }
+    ctx.limits = limits;
+    ctx.fuel = fuel;
+    Ok(())
+}
+
+// ============================================================================
+// Resources
+// ============================================================================
+//
+// Resources live in the Context's registry (category → name → instance), not in
+// dictionaries, so scripts can only reach them through these operators.
+
+/// Looks up a resource, failing with an undefinedresource error if it is missing
+/// or an undefined error if the category does not exist.
+fn find_resource(ctx: &Context, key: &str, category: &str) -> Result<PostScriptValue, String> {
+    let instances = ctx.resources.get(category)
+        .ok_or(format!("Undefined resource category: {}", category))?;
+    instances.get(key).cloned()
+        .ok_or(format!("Undefined resource: {} ({})", key, category))
+}
+
+/// Tests a resource name against a resourceforall template.
+/// `*` matches any run of characters, `?` any single character, and `\` quotes the next one.
+/// Matching is iterative: on a mismatch it backtracks to the most recent `*` only, letting
+/// it swallow one more character, so time stays linear in the template times the name
+/// however many stars the template has.
+fn matches_template(template: &[char], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+    // Template position after the last `*` seen, and the name position it resumes from
+    let mut star = None;
+    while n < name.len() {
+        let step = match template.get(t) {
+            Some('*') => {
+                star = Some((t + 1, n));
+                t += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('\\') if t + 1 < template.len() => (template[t + 1] == name[n]).then_some(2),
+            Some(c) => (*c == name[n]).then_some(1),
+            None => None,
+        };
+        match (step, star) {
+            (Some(width), _) => {
+                t += width;
+                n += 1;
+            }
+            (None, Some((after_star, resume))) => {
+                star = Some((after_star, resume + 1));
+                t = after_star;
+                n = resume + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    template[t..].iter().all(|&c| c == '*')
+}
+
+/// findfont: Look up a font resource
+/// Stack: key → font
+/// Equivalent to key /Font findresource
+fn findfont(ctx: &mut Context) -> Result<(), String> {
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let font = find_resource(ctx, &dict_key(&key)?, "Font")?;
+    ctx.push(font);
+    Ok(())
+}
+
+/// defineresource: Register a resource instance
+/// Stack: key instance category → instance
+/// Replaces any instance already registered under key in the category (see
+/// `Context::define_resource`).
+fn defineresource(ctx: &mut Context) -> Result<(), String> {
+    let category = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let instance = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.define_resource(&dict_key(&category)?, &dict_key(&key)?, instance.clone())?;
+    ctx.push(instance);
+    Ok(())
+}
+
+/// undefineresource: Remove a resource instance
+/// Stack: key category → (empty)
+/// Removing an instance that does not exist is not an error
+fn undefineresource(ctx: &mut Context) -> Result<(), String> {
+    let category = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    ctx.undefine_resource(&dict_key(&category)?, &dict_key(&key)?)
+}
+
+/// findresource: Look up a resource instance
+/// Stack: key category → instance
+fn findresource(ctx: &mut Context) -> Result<(), String> {
+    let category = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let instance = find_resource(ctx, &dict_key(&key)?, &dict_key(&category)?)?;
+    ctx.push(instance);
+    Ok(())
+}
+
+/// resourcestatus: Test whether a resource is defined
+/// Stack: key category → status size true, or key category → false
+/// Every registered resource lives in VM, so status is 1 and size is 0
+fn resourcestatus(ctx: &mut Context) -> Result<(), String> {
+    let category = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let key = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let category = dict_key(&category)?;
+    let instances = ctx.resources.get(&category)
+        .ok_or(format!("Undefined resource category: {}", category))?;
+    if instances.contains_key(&dict_key(&key)?) {
+        ctx.push(PostScriptValue::Int(1));
+        ctx.push(PostScriptValue::Int(0));
+        ctx.push(PostScriptValue::Bool(true));
+    } else {
+        ctx.push(PostScriptValue::Bool(false));
+    }
+    Ok(())
+}
+
+/// resourceforall: Enumerate the resources of a category
+/// Stack: template proc scratch category → (empty)
+/// Executes proc once per resource name matching template, in sorted order, with the
+/// name on the operand stack as a string. Names must fit in the scratch string.
+fn resourceforall(ctx: &mut Context) -> Result<(), String> {
+    let category = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let scratch = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let proc = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let template = ctx.pop().ok_or("Stack underflow".to_string())?;
+    let scratch_len = match scratch {
+        PostScriptValue::String(s) => s.borrow().chars().count(),
+        _ => return Err("Type check error".to_string()),
+    };
+    let template: Vec<char> = dict_key(&template)?.chars().collect();
+    let category = dict_key(&category)?;
+    let instances = ctx.resources.get(&category)
+        .ok_or(format!("Undefined resource category: {}", category))?;
+
+    let mut names: Vec<&String> = instances.keys()
+        .filter(|name| matches_template(&template, &name.chars().collect::<Vec<char>>()))
+        .collect();
+    names.sort();
+    if names.iter().any(|name| name.chars().count() > scratch_len) {
+        return Err("Range check error: resource name longer than scratch string".to_string());
+    }
+    let items = names.into_iter()
+        .map(|name| PostScriptValue::String(Rc::new(RefCell::new(name.clone()))))
+        .collect();
+
+    // Push the loop state to the execution stack - the interpreter will handle the iteration
+    ctx.schedule(PostScriptValue::ForAllLoop {
+        items,
+        index: 0,
+        width: 1,
+        proc: Box::new(proc),
+    });
     Ok(())
 }
@@ -0,0 +1,241 @@
+//! JSON Event Mode
+//!
+//! Executes input line by line and describes each outcome as one JSON object, for
+//! editors and other tools that embed the interpreter. This is the engine behind the
+//! `--events` flag of the CLI, which reads stdin and writes one event per line to
+//! stdout with none of the REPL's prompts or banners.
+//!
+//! # Format (version 1)
+//!
+//! The first event announces the format version; a consumer should check it:
+//!
+//! - `{"type":"ready","version":1}`
+//!
+//! Every input line is then answered with exactly one of:
+//!
+//! - `{"type":"result","stack":[…],"output":"…"}`: the line executed. `stack` is
+//!   the whole operand stack, bottom first, each value in its `==` form; `output`
+//!   is everything the line printed
+//! - `{"type":"error","kind":"…","message":"…","line":N,"col":null,"backtrace":[…],"output":"…"}`:
+//!   the line failed. `kind` is the PostScript error name (see `error_kind`),
+//!   `message` the interpreter's message, `line` the 1-based number of the input
+//!   line the failing input started on, and `col` null. `backtrace` lists the last
+//!   executed values, oldest first, as recorded by the flight recorder; `output`
+//!   is what was printed before the error. The stacks are left as the error left
+//!   them. Input is executed as it is scanned, so what comes before a syntax error
+//!   has run; for the error, `line` and `col` give the position of the problem
+//!   (which `message` then leaves out) and `backtrace` is empty
+//! - `{"type":"incomplete"}`: the input so far is unbalanced (an open string,
+//!   hexadecimal or ASCII85 string, or procedure). It is kept and the next line is
+//!   appended to it
+//!
+//! Strings are JSON strings; new fields may be added to events without changing
+//! the version, while changes to existing fields increment it.
+
+use crate::interpreter::Interpreter;
+use crate::parser::{Tokenizer, is_unfinished};
+use crate::types::{FileStream, FlightRecorder, PostScriptFile, PostScriptValue, STATEMENT_FILE};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Version of the event format described in the module documentation.
+pub const EVENTS_VERSION: u32 = 1;
+
+/// Number of executed values kept for the backtrace of error events.
+pub const BACKTRACE_LENGTH: usize = 10;
+
+/// An interpreter session that answers input lines with JSON events.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::types::Context;
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::commands::register_builtins;
+/// use postscript_interpreter::events::EventSession;
+///
+/// let mut context = Context::new(false);
+/// register_builtins(&mut context);
+/// let mut session = EventSession::new(Interpreter::new(context));
+///
+/// assert_eq!(session.ready(), r#"{"type":"ready","version":1}"#);
+/// assert_eq!(session.handle_line("1 2 add (hi) dup ="), r#"{"type":"result","stack":["3","(hi)"],"output":"hi\n"}"#);
+/// assert_eq!(session.handle_line("/square { dup mul"), r#"{"type":"incomplete"}"#);
+/// assert_eq!(session.handle_line("} def 4 square"), r#"{"type":"result","stack":["3","(hi)","16"],"output":""}"#);
+///
+/// let error = session.handle_line("clear (x) 1 add");
+/// assert!(error.starts_with(r#"{"type":"error","kind":"typecheck","message":"Type check error"#));
+/// assert!(error.contains(r#""line":4,"col":null,"backtrace":["#));
+/// assert!(error.ends_with(r#""add"],"output":""}"#));
+///
+/// let error = session.handle_line("nosuchname");
+/// assert!(error.starts_with(r#"{"type":"error","kind":"undefined","message":"Undefined name: nosuchname","line":5,"#));
+///
+/// // Input runs as it is scanned, so //x sees the definition made before it
+/// assert_eq!(session.handle_line("clear /x 5 def { //x } exec"), r#"{"type":"result","stack":["5"],"output":""}"#);
+///
+/// assert_eq!(
+///     session.handle_line("{ 1 } }"),
+///     r#"{"type":"error","kind":"syntaxerror","message":"Unexpected }","line":7,"col":7,"backtrace":[],"output":""}"#
+/// );
+/// ```
+pub struct EventSession {
+    interpreter: Interpreter,
+    output: Rc<RefCell<Vec<u8>>>,
+    pending: String,
+    pending_line: usize,
+    lines: usize,
+}
+
+impl EventSession {
+    /// Wraps an interpreter, capturing its output and enabling its flight recorder.
+    pub fn new(mut interpreter: Interpreter) -> Self {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let context = interpreter.get_context_mut();
+        context.output = Box::new(CaptureWriter(output.clone()));
+        context.flight_recorder = Some(FlightRecorder::new(BACKTRACE_LENGTH));
+        EventSession { interpreter, output, pending: String::new(), pending_line: 0, lines: 0 }
+    }
+
+    /// The event announcing the format version, to send before any other.
+    pub fn ready(&self) -> String {
+        format!("{{\"type\":\"ready\",\"version\":{}}}", EVENTS_VERSION)
+    }
+
+    /// Executes one line of input (appended to earlier incomplete input, if any)
+    /// and returns the event describing the outcome.
+    pub fn handle_line(&mut self, line: &str) -> String {
+        self.lines += 1;
+        if self.pending.is_empty() {
+            self.pending_line = self.lines;
+        } else {
+            self.pending.push('\n');
+        }
+        self.pending.push_str(line);
+
+        let language_level = self.interpreter.get_context().language_level;
+        if is_unfinished(Tokenizer::from_bytes_with_language_level(self.pending.as_bytes(), language_level)) {
+            return "{\"type\":\"incomplete\"}".to_string();
+        }
+        let source = std::mem::take(&mut self.pending).into_bytes();
+
+        // Executed as it is scanned, as the REPL does (see `PostScriptFile`)
+        let file = Rc::new(RefCell::new(PostScriptFile {
+            name: STATEMENT_FILE.to_string(),
+            stream: FileStream::Source(Tokenizer::from_bytes_with_language_level(&source, language_level)),
+        }));
+        let result = self.interpreter.execute(vec![PostScriptValue::ExecutableFile(file.clone())]);
+        let syntax_error = match (&result, &file.borrow().stream) {
+            (Err(message), FileStream::Source(tokenizer)) => tokenizer.last_error()
+                .filter(|error| *message == error.to_string())
+                .cloned(),
+            _ => None,
+        };
+        let (line, col, result) = match syntax_error {
+            Some(e) => (self.pending_line + e.span().line - 1, Some(e.span().col), Err(e.message())),
+            None => (self.pending_line, None, result),
+        };
+        let output = String::from_utf8_lossy(&std::mem::take(&mut *self.output.borrow_mut())).into_owned();
+        match result {
+            Ok(()) => {
+                let stack: Vec<String> = self.interpreter.get_context().operand_stack().iter()
+                    .map(|value| json_string(&value.to_string()))
+                    .collect();
+                format!("{{\"type\":\"result\",\"stack\":[{}],\"output\":{}}}", stack.join(","), json_string(&output))
+            }
+            Err(message) => {
+                // Nothing was executed for a syntax error
+                let backtrace: Vec<String> = match col {
+                    Some(_) => Vec::new(),
+                    None => self.interpreter.last_trace().iter().map(|entry| json_string(&entry.value)).collect(),
+                };
+                format!(
+                    "{{\"type\":\"error\",\"kind\":\"{}\",\"message\":{},\"line\":{},\"col\":{},\"backtrace\":[{}],\"output\":{}}}",
+                    error_kind(&message),
+                    json_string(&message),
+                    line,
+                    col.map_or("null".to_string(), |col| col.to_string()),
+                    backtrace.join(","),
+                    json_string(&output)
+                )
+            }
+        }
+    }
+
+    /// The wrapped interpreter.
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+}
+
+/// Returns the PostScript error name for an interpreter error message.
+///
+/// Messages that match none of the PostScript errors are `unregistered`.
+pub fn error_kind(message: &str) -> &'static str {
+    const KINDS: &[(&str, &str)] = &[
+        ("Stack underflow", "stackunderflow"),
+        ("Stack overflow", "stackoverflow"),
+        ("Execution stack overflow", "execstackoverflow"),
+        ("Dict stack underflow", "dictstackunderflow"),
+        ("Dict stack overflow", "dictstackoverflow"),
+        ("Dict full", "dictfull"),
+        ("Type check error", "typecheck"),
+        ("Type error", "typecheck"),
+        ("Range check error", "rangecheck"),
+        ("Limit check error", "limitcheck"),
+        ("Execution limit exceeded", "limitcheck"),
+        ("Undefined name", "undefined"),
+        ("Undefined key", "undefined"),
+        ("Undefined result", "undefinedresult"),
+        ("Undefined resource", "undefinedresource"),
+        ("Unmatched mark", "unmatchedmark"),
+        ("Invalid restore", "invalidrestore"),
+        ("Invalid access", "invalidaccess"),
+        ("Invalid file access", "invalidfileaccess"),
+        ("Invalid exit", "invalidexit"),
+        ("Undefined filename", "undefinedfilename"),
+        ("Syntax error", "syntaxerror"),
+        ("Unterminated", "syntaxerror"),
+        ("Unexpected", "syntaxerror"),
+        ("I/O error", "ioerror"),
+        ("VM error", "VMerror"),
+        ("Interrupted", "interrupt"),
+    ];
+    KINDS.iter()
+        .find(|(prefix, _)| message.starts_with(prefix))
+        .map_or("unregistered", |&(_, kind)| kind)
+}
+
+/// Formats text as a JSON string literal.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Output sink that collects everything written to it in a shared buffer.
+struct CaptureWriter(Rc<RefCell<Vec<u8>>>);
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
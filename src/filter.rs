@@ -0,0 +1,381 @@
+//! Filters for the `filter` Operator
+//!
+//! A filter is a file object that reads its data from another file or a string
+//! through a decoder, or writes its data through an encoder into another file or a
+//! string. The file operators see filters as ordinary files (see
+//! `FileStream::Decode` and `FileStream::Encode`).
+//!
+//! Decoders take the source one byte at a time and stop at the end-of-data marker
+//! of their encoding, so the source is left right after the encoded data: a program
+//! can decode data embedded in it through `currentfile` and go on after that data.
+//! Encoders encode whatever is written to them and end their data when the filter
+//! is closed.
+//!
+//! # Supported Filters
+//!
+//! - **ASCIIHexDecode** / **ASCIIHexEncode**: pairs of hexadecimal digits, ended by `>`
+//! - **ASCII85Decode** / **ASCII85Encode**: base-85 groups, ended by `~>`
+//! - **RunLengthDecode** / **RunLengthEncode**: PackBits-style runs, ended by a
+//!   length byte of 128. A length byte of 0-127 is followed by 1-128 bytes taken
+//!   literally, one of 129-255 by a single byte repeated 257 - length times
+
+use std::cell::RefCell;
+use std::io::{BufRead, Cursor};
+use std::rc::Rc;
+
+use crate::types::PostScriptFile;
+
+/// Length at which encoders break their output into lines.
+pub const LINE_LENGTH: usize = 64;
+
+/// Decoding state of a decode filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoder {
+    /// ASCIIHexDecode, holding the first digit of an incomplete pair
+    AsciiHex { high: Option<u8> },
+    /// ASCII85Decode, holding the digits of an incomplete group, whether a `~` was
+    /// just read, and whether the data opened with `<` (None before the first byte)
+    Ascii85 { digits: Vec<u8>, tilde: bool, opening: Option<bool> },
+    /// RunLengthDecode, holding how many literal bytes are still to come, or how
+    /// many times the next byte is repeated
+    RunLength { literal: usize, repeat: usize },
+}
+
+impl Decoder {
+    /// The decoder of a decode filter name, or None if there is no such filter.
+    pub fn by_name(name: &str) -> Option<Decoder> {
+        match name {
+            "ASCIIHexDecode" => Some(Decoder::AsciiHex { high: None }),
+            "ASCII85Decode" => Some(Decoder::Ascii85 { digits: Vec::new(), tilde: false, opening: None }),
+            "RunLengthDecode" => Some(Decoder::RunLength { literal: 0, repeat: 0 }),
+            _ => None,
+        }
+    }
+
+    /// The name of the filter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Decoder::AsciiHex { .. } => "ASCIIHexDecode",
+            Decoder::Ascii85 { .. } => "ASCII85Decode",
+            Decoder::RunLength { .. } => "RunLengthDecode",
+        }
+    }
+
+    /// Decodes source up to and including the end-of-data marker, or up to the end
+    /// of source if the marker is missing. Nothing after the marker is read.
+    ///
+    /// ```
+    /// use postscript_interpreter::filter::Decoder;
+    ///
+    /// let mut source: &[u8] = b"48 65 6C 6C 6F> rest";
+    /// let mut decoder = Decoder::by_name("ASCIIHexDecode").unwrap();
+    /// assert_eq!(decoder.decode(&mut source).unwrap(), b"Hello");
+    /// assert_eq!(source, b" rest");
+    /// ```
+    pub fn decode(&mut self, source: &mut dyn BufRead) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        loop {
+            let buffer = source.fill_buf().map_err(|e| format!("I/O error: {}: {}", self.name(), e))?;
+            let Some(&byte) = buffer.first() else {
+                break;
+            };
+            source.consume(1);
+            if self.push(byte, &mut data)? {
+                return Ok(data);
+            }
+        }
+        self.finish(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decodes one byte into data; true at the end-of-data marker.
+    fn push(&mut self, byte: u8, data: &mut Vec<u8>) -> Result<bool, String> {
+        let name = self.name();
+        let invalid = || format!("I/O error: {}: invalid character {:?}", name, byte as char);
+        match self {
+            Decoder::AsciiHex { high } => {
+                if byte == b'>' {
+                    self.finish(data)?;
+                    return Ok(true);
+                }
+                if let Some(digit) = (byte as char).to_digit(16) {
+                    match high.take() {
+                        Some(high) => data.push(high * 16 + digit as u8),
+                        None => *high = Some(digit as u8),
+                    }
+                } else if !byte.is_ascii_whitespace() && byte != 0 {
+                    return Err(invalid());
+                }
+            }
+            Decoder::Ascii85 { digits, tilde, opening } => {
+                if *opening == Some(true) {
+                    if byte != b'~' {
+                        return Err(invalid());
+                    }
+                    *opening = Some(false);
+                    return Ok(false);
+                }
+                if *tilde {
+                    if byte != b'>' {
+                        return Err(invalid());
+                    }
+                    self.finish(data)?;
+                    return Ok(true);
+                }
+                if byte.is_ascii_whitespace() || byte == 0 {
+                    return Ok(false);
+                }
+                // An opening <~, as in ASCII85 string syntax, is skipped
+                if opening.is_none() {
+                    *opening = Some(byte == b'<');
+                    if byte == b'<' {
+                        return Ok(false);
+                    }
+                }
+                match byte {
+                    b'~' => *tilde = true,
+                    b'z' if digits.is_empty() => data.extend_from_slice(&[0; 4]),
+                    b'!'..=b'u' => {
+                        digits.push(byte - b'!');
+                        if digits.len() == 5 {
+                            data.extend_from_slice(&ascii85_group(digits, name)?);
+                            digits.clear();
+                        }
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            Decoder::RunLength { literal, repeat } => {
+                if *literal > 0 {
+                    data.push(byte);
+                    *literal -= 1;
+                } else if *repeat > 0 {
+                    data.resize(data.len() + *repeat, byte);
+                    *repeat = 0;
+                } else {
+                    match byte {
+                        128 => return Ok(true),
+                        0..=127 => *literal = byte as usize + 1,
+                        _ => *repeat = 257 - byte as usize,
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Decodes what is left of an incomplete group at the end of the data.
+    fn finish(&mut self, data: &mut Vec<u8>) -> Result<(), String> {
+        let name = self.name();
+        match self {
+            // An odd digit count is padded with 0
+            Decoder::AsciiHex { high } => {
+                if let Some(high) = high.take() {
+                    data.push(high * 16);
+                }
+            }
+            // A group of n < 5 digits is padded with u and gives n - 1 bytes
+            Decoder::Ascii85 { digits, .. } => {
+                match digits.len() {
+                    0 => {}
+                    1 => return Err(format!("I/O error: {}: incomplete final group", name)),
+                    n => {
+                        let mut group = digits.clone();
+                        group.resize(5, b'u' - b'!');
+                        data.extend_from_slice(&ascii85_group(&group, name)?[..n - 1]);
+                        digits.clear();
+                    }
+                }
+            }
+            // A run cut short by the end of the source gives what it has
+            Decoder::RunLength { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Decodes 5 base-85 digits into 4 bytes.
+fn ascii85_group(digits: &[u8], name: &str) -> Result<[u8; 4], String> {
+    let value = digits.iter().fold(0u64, |value, &digit| value * 85 + digit as u64);
+    let value = u32::try_from(value).map_err(|_| format!("I/O error: {}: group out of range", name))?;
+    Ok(value.to_be_bytes())
+}
+
+/// Encoding state of an encode filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Encoder {
+    /// ASCIIHexEncode, holding the length of the current line
+    AsciiHex { column: usize },
+    /// ASCII85Encode, holding the bytes of an incomplete group and the length of the
+    /// current line
+    Ascii85 { group: Vec<u8>, column: usize },
+    /// RunLengthEncode, holding the bytes not encoded yet. Runs do not cross the
+    /// boundaries of records of record_size bytes; with a record_size of 0 the
+    /// data is encoded as a whole when it ends
+    RunLength { record_size: usize, pending: Vec<u8> },
+}
+
+impl Encoder {
+    /// The encoder of an encode filter name, or None if there is no such filter.
+    pub fn by_name(name: &str) -> Option<Encoder> {
+        match name {
+            "ASCIIHexEncode" => Some(Encoder::AsciiHex { column: 0 }),
+            "ASCII85Encode" => Some(Encoder::Ascii85 { group: Vec::new(), column: 0 }),
+            "RunLengthEncode" => Some(Encoder::RunLength { record_size: 0, pending: Vec::new() }),
+            _ => None,
+        }
+    }
+
+    /// The name of the filter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoder::AsciiHex { .. } => "ASCIIHexEncode",
+            Encoder::Ascii85 { .. } => "ASCII85Encode",
+            Encoder::RunLength { .. } => "RunLengthEncode",
+        }
+    }
+
+    /// Encodes bytes, appending the encoded data to out. Bytes of an incomplete
+    /// group are kept until more bytes come or the data ends.
+    pub fn encode(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        match self {
+            Encoder::AsciiHex { column } => {
+                for byte in bytes {
+                    push_line(out, column, format!("{:02x}", byte).as_bytes());
+                }
+            }
+            Encoder::Ascii85 { group, column } => {
+                for &byte in bytes {
+                    group.push(byte);
+                    if group.len() == 4 {
+                        let chunk = if group == &[0; 4] { b"z".to_vec() } else { ascii85_digits(group) };
+                        push_line(out, column, &chunk);
+                        group.clear();
+                    }
+                }
+            }
+            Encoder::RunLength { record_size, pending } => {
+                pending.extend_from_slice(bytes);
+                if let Some(count) = pending.len().checked_div(*record_size) {
+                    let records = count * *record_size;
+                    for record in pending[..records].chunks(*record_size) {
+                        run_length_encode(record, out);
+                    }
+                    pending.drain(..records);
+                }
+            }
+        }
+    }
+
+    /// Ends the encoded data: encodes an incomplete group and appends the
+    /// end-of-data marker.
+    ///
+    /// ```
+    /// use postscript_interpreter::filter::Encoder;
+    ///
+    /// let mut encoder = Encoder::by_name("ASCII85Encode").unwrap();
+    /// let mut out = Vec::new();
+    /// encoder.encode(b"Hello, P", &mut out);
+    /// encoder.finish(&mut out);
+    /// assert_eq!(out, b"87cURD_*#-~>");
+    /// ```
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        match self {
+            Encoder::AsciiHex { .. } => out.push(b'>'),
+            Encoder::Ascii85 { group, column } => {
+                if !group.is_empty() {
+                    let n = group.len();
+                    group.resize(4, 0);
+                    push_line(out, column, &ascii85_digits(group)[..n + 1]);
+                    group.clear();
+                }
+                out.extend_from_slice(b"~>");
+            }
+            Encoder::RunLength { pending, .. } => {
+                run_length_encode(pending, out);
+                pending.clear();
+                out.push(128);
+            }
+        }
+    }
+}
+
+/// Encodes bytes as runs: a byte repeated 2-128 times becomes a repeat run, and
+/// bytes between such repeats become literal runs of up to 128 bytes.
+///
+/// ```
+/// use postscript_interpreter::filter::Encoder;
+///
+/// let mut encoder = Encoder::by_name("RunLengthEncode").unwrap();
+/// let mut out = Vec::new();
+/// encoder.encode(b"aaaabc", &mut out);
+/// encoder.finish(&mut out);
+/// assert_eq!(out, [253, b'a', 1, b'b', b'c', 128]);
+/// ```
+fn run_length_encode(bytes: &[u8], out: &mut Vec<u8>) {
+    let run = |start: usize| bytes[start..].iter().take(128).take_while(|&&b| b == bytes[start]).count();
+    let mut i = 0;
+    while i < bytes.len() {
+        let repeat = run(i);
+        if repeat >= 2 {
+            out.push((257 - repeat) as u8);
+            out.push(bytes[i]);
+            i += repeat;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && i - start < 128 && (i == start || run(i) < 2) {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&bytes[start..i]);
+    }
+}
+
+/// Encodes 4 bytes as 5 base-85 digits.
+fn ascii85_digits(group: &[u8]) -> Vec<u8> {
+    let mut value = u32::from_be_bytes([group[0], group[1], group[2], group[3]]);
+    let mut digits = vec![0; 5];
+    for digit in digits.iter_mut().rev() {
+        *digit = (value % 85) as u8 + b'!';
+        value /= 85;
+    }
+    digits
+}
+
+/// Appends a chunk of encoded data, starting a new line first if the chunk would
+/// make the current one longer than LINE_LENGTH.
+fn push_line(out: &mut Vec<u8>, column: &mut usize, chunk: &[u8]) {
+    if *column + chunk.len() > LINE_LENGTH {
+        out.push(b'\n');
+        *column = 0;
+    }
+    out.extend_from_slice(chunk);
+    *column += chunk.len();
+}
+
+/// A decode filter made by `filter`.
+pub struct DecodeFilter {
+    /// The decoding state
+    pub decoder: Decoder,
+    /// The file the encoded data is read from (a string source is wrapped in one)
+    pub source: Rc<RefCell<PostScriptFile>>,
+    /// The decoded data, filled when the filter is first read
+    pub data: Option<Cursor<Vec<u8>>>,
+}
+
+/// An encode filter made by `filter`.
+pub struct EncodeFilter {
+    /// The encoding state
+    pub encoder: Encoder,
+    /// Where the encoded data goes
+    pub target: FilterTarget,
+}
+
+/// Where an encode filter writes.
+pub enum FilterTarget {
+    /// Another file
+    File(Rc<RefCell<PostScriptFile>>),
+    /// A string, filled from the start; position is the next character to write
+    String { string: Rc<RefCell<String>>, position: usize },
+}
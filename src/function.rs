@@ -0,0 +1,135 @@
+//! PDF Type 4 (PostScript Calculator) Function Evaluation
+//!
+//! PDF Type 4 functions are written in a restricted subset of PostScript: a single
+//! procedure that may only use numbers, booleans, arithmetic, comparison, stack
+//! operators and `if`/`ifelse`. This module evaluates such functions with the
+//! regular interpreter, but in a locked-down Context:
+//!
+//! - Only the whitelisted operators are registered (see `TYPE4_OPERATORS`)
+//! - The procedure body is checked up front, so other operators are rejected with
+//!   a clear error before anything runs
+//! - Execution is fuel-limited, so a function can never run away
+//! - Inputs are clamped to the domain and outputs to the range, as the PDF
+//!   specification requires
+
+use crate::commands::register_builtins;
+use crate::interpreter::Interpreter;
+use crate::parser::{Tokenizer, parse};
+use crate::types::{Context, PostScriptValue};
+
+/// Operators allowed in a Type 4 function (PDF Reference, section 3.9.4).
+///
+/// Operators that the interpreter does not implement are simply not registered,
+/// so a function using them fails with an "Undefined name" error.
+pub const TYPE4_OPERATORS: &[&str] = &[
+    // Arithmetic
+    "abs", "add", "atan", "ceiling", "cos", "cvi", "cvr", "div", "exp", "floor",
+    "idiv", "ln", "log", "mod", "mul", "neg", "round", "sin", "sqrt", "sub", "truncate",
+    // Relational, boolean and bitwise
+    "and", "bitshift", "eq", "false", "ge", "gt", "le", "lt", "ne", "not", "or", "true", "xor",
+    // Conditional
+    "if", "ifelse",
+    // Stack
+    "copy", "dup", "exch", "index", "pop", "roll",
+];
+
+/// Maximum number of values a single function evaluation may execute.
+pub const FUNCTION_FUEL: u64 = 100_000;
+
+/// Evaluates a Type 4 function given as PostScript source.
+///
+/// `proc_source` must contain a single procedure (`{ ... }`). Each input is
+/// clamped to the corresponding `domain` interval and pushed onto the operand
+/// stack; after the procedure runs, the operand stack must hold exactly one
+/// number per `range` interval, and each is clamped to its interval.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::function::eval_function;
+///
+/// // Tint transform from a Separation colour space to CMYK
+/// let tint = "{ dup 0.84 mul exch 0.0 exch dup 0.44 mul exch 0.21 mul }";
+/// let cmyk = eval_function(tint, &[0.5], &[(0.0, 1.0)], &[(0.0, 1.0); 4]).unwrap();
+/// assert_eq!(cmyk, vec![0.42, 0.0, 0.22, 0.105]);
+///
+/// // Inputs and outputs are clamped
+/// let out = eval_function("{ 2 mul }", &[5.0], &[(0.0, 1.0)], &[(0.0, 1.5)]).unwrap();
+/// assert_eq!(out, vec![1.5]);
+///
+/// // Operators outside the Type 4 subset are rejected
+/// let err = eval_function("{ dup def }", &[0.5], &[(0.0, 1.0)], &[(0.0, 1.0)]).unwrap_err();
+/// assert!(err.contains("def"));
+/// ```
+pub fn eval_function(proc_source: &str, inputs: &[f64], domain: &[(f64, f64)], range: &[(f64, f64)]) -> Result<Vec<f64>, String> {
+    let tokens = Tokenizer::new(proc_source).tokenize()?;
+    let mut values = parse(tokens)?;
+    match (values.pop(), values.is_empty()) {
+        (Some(PostScriptValue::Block(body)), true) => eval_function_block(&body, inputs, domain, range),
+        _ => Err("Type 4 function must be a single procedure".to_string()),
+    }
+}
+
+/// Evaluates a Type 4 function whose procedure body has already been parsed.
+///
+/// Behaves exactly like `eval_function`, taking the contents of the procedure
+/// (the values between the braces) instead of source text.
+pub fn eval_function_block(body: &[PostScriptValue], inputs: &[f64], domain: &[(f64, f64)], range: &[(f64, f64)]) -> Result<Vec<f64>, String> {
+    if inputs.len() != domain.len() {
+        return Err(format!("Type 4 function expects {} inputs, got {}", domain.len(), inputs.len()));
+    }
+    check_body(body)?;
+
+    let mut interpreter = Interpreter::new(calculator_context());
+    let context = interpreter.get_context_mut();
+    for (&input, &(min, max)) in inputs.iter().zip(domain) {
+        context.push(PostScriptValue::Real(input.clamp(min, max)));
+    }
+    interpreter.execute(body.to_vec())?;
+
+    let stack = interpreter.get_context().operand_stack();
+    if stack.len() != range.len() {
+        return Err(format!("Type 4 function produced {} outputs, expected {}", stack.len(), range.len()));
+    }
+    stack.iter().zip(range).map(|(value, &(min, max))| {
+        match value {
+            PostScriptValue::Int(i) => Ok((*i as f64).clamp(min, max)),
+            PostScriptValue::Real(f) => Ok(f.clamp(min, max)),
+            other => Err(format!("Type 4 function output must be a number, got {}", other.type_name())),
+        }
+    }).collect()
+}
+
+/// Builds a Context containing only the Type 4 operators, with a fuel limit.
+fn calculator_context() -> Context {
+    let mut builtins = Context::new(false);
+    register_builtins(&mut builtins);
+
+    let mut context = Context::new(false);
+    for &name in TYPE4_OPERATORS {
+        if let Some(op) = builtins.lookup(name) {
+            context.define(name.to_string(), op);
+        }
+    }
+    context.fuel = Some(FUNCTION_FUEL);
+    context
+}
+
+/// Checks that a procedure body only uses constructs allowed in Type 4 functions:
+/// numbers, whitelisted operator names and nested procedures (for if/ifelse).
+fn check_body(body: &[PostScriptValue]) -> Result<(), String> {
+    for value in body {
+        match value {
+            PostScriptValue::Int(_) | PostScriptValue::Real(_) => {}
+            PostScriptValue::Name(name) if TYPE4_OPERATORS.contains(&name.as_str()) => {}
+            PostScriptValue::Name(name) => {
+                return Err(format!("Operator not allowed in Type 4 function: {}", name));
+            }
+            PostScriptValue::Block(inner) => check_body(inner)?,
+            other => {
+                return Err(format!("Value not allowed in Type 4 function: {}", other));
+            }
+        }
+    }
+    Ok(())
+}
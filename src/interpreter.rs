@@ -4,7 +4,19 @@
 //! It uses a stack-based execution model where values are popped from the execution
 //! stack and processed according to their type.
 
-use crate::types::{Context, PostScriptValue};
+use crate::commands::register_builtins;
+use crate::parser::{Tokenizer, is_unfinished, parse_scanned};
+use crate::types::{latin1_bytes, Allocation, Context, FileStream, PostScriptFile, PostScriptValue, TraceEntry, FOR_LOOP_TOLERANCE, STATEMENT_FILE};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+
+/// Error message returned by `Interpreter::execute` when the interrupt flag is set.
+pub const INTERRUPTED: &str = "Interrupted";
+
+/// Error message returned by `Interpreter::execute` when the Context's fuel runs out.
+pub const FUEL_EXHAUSTED: &str = "Execution limit exceeded";
 
 /// The interpreter executes PostScriptValue objects using a Context.
 ///
@@ -40,6 +52,10 @@ impl Interpreter {
     /// Values are pushed onto the execution stack in reverse order so that
     /// the first value in the input vector is executed first.
     ///
+    /// An error that no `stopped` context or `executive` loop catches ends the call:
+    /// everything still scheduled is abandoned (see `unwind`), so none of it runs
+    /// with the next call, and the operand stack is left as the error left it.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -51,14 +67,305 @@ impl Interpreter {
     /// ];
     /// interpreter.execute(values)?;
     /// ```
+    ///
+    /// After an error, the rest of the program is gone:
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// let program = parse(Tokenizer::new("1 { 2 nosuchname 3 } exec 4").tokenize().unwrap()).unwrap();
+    /// assert!(interpreter.execute(program).is_err());
+    /// assert!(interpreter.get_context().execution_stack().is_empty());
+    ///
+    /// interpreter.execute(vec![PostScriptValue::Int(5)]).unwrap();
+    /// let stack = interpreter.get_context().operand_stack();
+    /// assert_eq!(stack, &[PostScriptValue::Int(1), PostScriptValue::Int(2), PostScriptValue::Int(5)]);
+    /// ```
     pub fn execute(&mut self, values: Vec<PostScriptValue>) -> Result<(), String> {
-        // Push values to execution stack in reverse order so the first item is at the top
-        for value in values.into_iter().rev() {
-            self.context.execution_stack.push(value);
+        allocate_scanned(&mut self.context, &values)?;
+
+        // Schedule the values so the first item is executed first
+        self.context.schedule_block(&values);
+
+        if let Some(recorder) = self.context.flight_recorder.as_mut() {
+            recorder.clear();
         }
 
-        while let Some(value) = self.context.execution_stack.pop() {
-            self.execute_one(value)?;
+        while let Some(value) = self.context.next_scheduled() {
+            // An executable file traces the object it executes instead of itself
+            if !matches!(value, PostScriptValue::ExecutableFile(_)) {
+                self.context.record_trace(&value);
+            }
+            let result = self.check_interrupt()
+                .and_then(|_| self.consume_fuel())
+                .and_then(|_| self.execute_one(value))
+                .and_then(|_| self.check_limits());
+            if let Err(e) = result
+                && !self.unwind_to_stopped(&e)
+            {
+                self.unwind();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes values without letting them change the session's definitions or stacks.
+    ///
+    /// The values run with:
+    /// - A fresh, empty operand stack; whatever the snippet leaves on it is returned
+    ///   and the session's operand stack is put back untouched
+    /// - A temporary dictionary pushed on the dictionary stack, so `def` does not
+    ///   persist; `end` and `cleardictstack` cannot pop it (see
+    ///   `Context::set_pinned_dicts`), and the dictionary stack is restored
+    ///   afterwards, even on error
+    /// - The Context's limits, exactly as for `execute`, and its fuel budget, which
+    ///   is put back afterwards: every call has the same budget, and snippets do not
+    ///   use up the session's
+    /// - The session's allocation mode and save level, which are put back afterwards:
+    ///   `setglobal` only lasts for the call, and snapshots taken by `save` are
+    ///   discarded (without being restored)
+    ///
+    /// Objects that existed before the call (dictionaries, strings) are shared, not
+    /// copied, so a snippet that explicitly mutates one of them with `put` or
+    /// `putinterval` (or `def`s into it after `begin`) changes it for the session.
+    /// That is the one deliberate escape hatch.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let mut interpreter = Interpreter::new(context);
+    /// interpreter.execute(vec![PostScriptValue::Int(99)]).unwrap();
+    ///
+    /// let snippet = parse(Tokenizer::new("/x 5 def x x mul").tokenize().unwrap()).unwrap();
+    /// let results = interpreter.execute_isolated(snippet).unwrap();
+    /// assert_eq!(results, vec![PostScriptValue::Int(25)]);
+    ///
+    /// let context = interpreter.get_context();
+    /// assert_eq!(context.lookup("x"), None);
+    /// assert_eq!(context.operand_stack(), &[PostScriptValue::Int(99)]);
+    ///
+    /// // end cannot pop the temporary dictionary, so y stays out of userdict
+    /// let snippet = parse(Tokenizer::new("{ end } stopped /y 1 def").tokenize().unwrap()).unwrap();
+    /// assert_eq!(interpreter.execute_isolated(snippet).unwrap(), vec![PostScriptValue::Bool(true)]);
+    /// assert_eq!(interpreter.get_context().lookup("y"), None);
+    ///
+    /// // Every call has the same fuel budget
+    /// interpreter.get_context_mut().fuel = Some(100);
+    /// for _ in 0..5 {
+    ///     let snippet = parse(Tokenizer::new("0 0 1 10 { add } for").tokenize().unwrap()).unwrap();
+    ///     assert_eq!(interpreter.execute_isolated(snippet), Ok(vec![PostScriptValue::Int(55)]));
+    /// }
+    /// assert_eq!(interpreter.get_context().fuel, Some(100));
+    ///
+    /// // Neither the allocation mode nor the save level leaks into the session
+    /// interpreter.get_context_mut().fuel = None;
+    /// let snippet = parse(Tokenizer::new("true setglobal save pop").tokenize().unwrap()).unwrap();
+    /// assert_eq!(interpreter.execute_isolated(snippet), Ok(vec![]));
+    /// assert!(!interpreter.get_context().global_allocation());
+    /// assert_eq!(interpreter.get_context().save_level(), 0);
+    /// ```
+    pub fn execute_isolated(&mut self, values: Vec<PostScriptValue>) -> Result<Vec<PostScriptValue>, String> {
+        let mut scratch = self.context.dict_stack().to_vec();
+        scratch.push(Rc::new(RefCell::new(HashMap::new())));
+        let saved_dicts = self.context.replace_dict_stack(scratch)?;
+        let saved_operands = self.context.replace_operand_stack(Vec::new());
+        let saved_pinned = self.context.set_pinned_dicts(self.context.dict_count());
+        let saved_fuel = self.context.fuel;
+        let saved_global = self.context.global_allocation();
+        let saved_level = self.context.save_level();
+
+        let result = self.execute(values);
+
+        self.context.discard_saves(saved_level);
+        self.context.set_global_allocation(saved_global);
+        self.context.fuel = saved_fuel;
+        self.context.set_pinned_dicts(saved_pinned);
+        let produced = self.context.replace_operand_stack(saved_operands);
+        let restored = self.context.replace_dict_stack(saved_dicts);
+        result.and(restored).map(|_| produced)
+    }
+
+    /// Calls a procedure by name, as if `name` had been executed with `args` on the stack.
+    ///
+    /// The arguments are pushed onto the operand stack in order (so the last one ends
+    /// up on top), then the name is looked up in the dictionary stack and executed.
+    /// Fails without touching the operand stack if the name is undefined or is not
+    /// executable (a procedure or operator).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// interpreter.call("add", vec![PostScriptValue::Int(3), PostScriptValue::Int(4)]).unwrap();
+    /// assert_eq!(interpreter.get_context().peek(), Some(&PostScriptValue::Int(7)));
+    /// assert!(interpreter.call("missing", vec![]).is_err());
+    /// ```
+    pub fn call(&mut self, name: &str, args: Vec<PostScriptValue>) -> Result<(), String> {
+        match self.context.lookup(name) {
+            Some(PostScriptValue::Block(_))
+            | Some(PostScriptValue::Closure { .. })
+            | Some(PostScriptValue::NativeFn(_)) => {}
+            Some(other) => {
+                return Err(format!("{} is not a procedure (it is a {})", name, other.type_name()));
+            }
+            None => return Err(format!("Undefined name: {}", name)),
+        }
+        for arg in args {
+            self.context.push(arg);
+        }
+        self.execute(vec![PostScriptValue::Name(name.to_string())])
+    }
+
+    /// Abandons the rest of the execution stack after an error.
+    ///
+    /// The remaining values are discarded so they don't run with the next call to
+    /// `execute`. If the error happened inside a closure, the dictionary stack is
+    /// restored to the environment that was active before the outermost closure.
+    fn unwind(&mut self) {
+        let pending = self.context.clear_execution_stack();
+        if let Some(PostScriptValue::RestoreEnv(env)) = pending.into_iter()
+            .find(|v| matches!(v, PostScriptValue::RestoreEnv(_)))
+        {
+            let _ = self.context.replace_dict_stack(env);
+        }
+    }
+
+    /// Catches an error in the innermost `stopped` context or `executive` loop, if
+    /// there is one.
+    ///
+    /// Everything scheduled above the context's marker is abandoned and the dictionary
+    /// stack is restored if the error happened inside a closure. A `stopped` context
+    /// is abandoned too and `true` is pushed as its result; an `executive` loop stays
+    /// scheduled, so it prints the error and reads the next line. Returns false,
+    /// leaving the stacks alone, when no context is active or the error must reach
+    /// the caller: interrupts and fuel exhaustion are never caught, so a script
+    /// cannot defeat them.
+    fn unwind_to_stopped(&mut self, error: &str) -> bool {
+        if error == INTERRUPTED || error == FUEL_EXHAUSTED {
+            return false;
+        }
+        let stack = self.context.execution_stack();
+        let Some(marker) = stack.iter()
+            .rposition(|v| matches!(v, PostScriptValue::StopContext | PostScriptValue::Executive { .. }))
+        else {
+            return false;
+        };
+        let executive = matches!(stack[marker], PostScriptValue::Executive { .. });
+        let depth = stack.len() - marker - usize::from(executive);
+        let abandoned = self.context.take_scheduled(depth);
+        if let Some(PostScriptValue::RestoreEnv(env)) = abandoned.into_iter()
+            .find(|v| matches!(v, PostScriptValue::RestoreEnv(_)))
+        {
+            let _ = self.context.replace_dict_stack(env);
+        }
+        if executive {
+            let _ = self.context.write_output(&format!("Error: {}\n", error));
+        } else {
+            self.context.push(PostScriptValue::Bool(true));
+        }
+        true
+    }
+
+    /// Fails with `INTERRUPTED` if an interrupt was requested, clearing the request.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::{Interpreter, INTERRUPTED};
+    /// use postscript_interpreter::commands::register_builtins;
+    /// use std::sync::atomic::Ordering;
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let flag = context.interrupt.clone();
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// // Interrupt a (practically) endless loop from another thread
+    /// let setter = thread::spawn(move || {
+    ///     thread::sleep(Duration::from_millis(50));
+    ///     flag.store(true, Ordering::SeqCst);
+    /// });
+    /// let looping = vec![
+    ///     PostScriptValue::Int(i64::MAX),
+    ///     PostScriptValue::Block(vec![]),
+    ///     PostScriptValue::Name("repeat".to_string()),
+    /// ];
+    /// assert_eq!(interpreter.execute(looping), Err(INTERRUPTED.to_string()));
+    /// setter.join().unwrap();
+    ///
+    /// // The session stays usable afterwards
+    /// interpreter.execute(vec![PostScriptValue::Int(1)]).unwrap();
+    /// ```
+    fn check_interrupt(&self) -> Result<(), String> {
+        if self.context.interrupt.swap(false, Ordering::Relaxed) {
+            return Err(INTERRUPTED.to_string());
+        }
+        Ok(())
+    }
+
+    /// Deducts one unit from the execution budget, if the Context has one.
+    fn consume_fuel(&mut self) -> Result<(), String> {
+        match self.context.fuel {
+            Some(0) => Err(FUEL_EXHAUSTED.to_string()),
+            Some(ref mut n) => {
+                *n -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the stack depths against the limits configured in the Context.
+    ///
+    /// Called after every executed value, so a runaway procedure fails with an
+    /// overflow error instead of exhausting memory. Also counts the executed value,
+    /// records peak stack depths in the Context's stats, and reports progress.
+    fn check_limits(&mut self) -> Result<(), String> {
+        let operands = self.context.operand_count();
+        let scheduled = self.context.execution_stack().len();
+        let stats = &mut self.context.stats;
+        stats.executed += 1;
+        stats.peak_operand_stack = stats.peak_operand_stack.max(operands);
+        stats.peak_exec_stack = stats.peak_exec_stack.max(scheduled);
+        let executed = stats.executed;
+
+        if let Some(progress) = self.context.progress.as_mut()
+            && progress.interval > 0
+            && executed.is_multiple_of(progress.interval)
+        {
+            (progress.callback)(executed, operands);
+        }
+
+        let limits = &self.context.limits;
+        if operands > limits.max_operand_stack {
+            return Err("Stack overflow".to_string());
+        }
+        if scheduled > limits.max_exec_stack {
+            return Err("Execution stack overflow".to_string());
         }
         Ok(())
     }
@@ -69,10 +376,16 @@ impl Interpreter {
     ///
     /// - **Name**: Look up in dictionary stack and execute the result
     /// - **Block**: Push to operand stack (or convert to Closure in lexical mode)
-    /// - **NativeFn**: Call the function with mutable Context
-    /// - **ForLoop/RepeatLoop**: Manage loop iteration on execution stack
+    /// - **NativeFn**: Call the function with mutable Context (met directly in procedures
+    ///   processed by `bind`)
+    /// - **ForLoop/IntForLoop/RepeatLoop/Loop/ForAllLoop/SortLoop**: Manage loop iteration on execution stack
     /// - **Closure**: Execute with captured environment
+    /// - **ExecutableString**: Scan the string and execute the resulting values
+    /// - **ExecutableFile**: Read and execute the file's next object, then come back for more
     /// - **RestoreEnv**: Restore dictionary stack after closure execution
+    /// - **StopContext**: Push false, as the procedure of `stopped` completed
+    /// - **Executive**: Run `prompt`, then read and execute a line of input (see
+    ///   `execute_line`)
     /// - **Literals**: Push directly to operand stack
     fn execute_one(&mut self, value: PostScriptValue) -> Result<(), String> {
         match value {
@@ -83,25 +396,15 @@ impl Interpreter {
                         // Native function: call it immediately
                         PostScriptValue::NativeFn(f) => f(&mut self.context)?,
                         
-                        // Block: push contents to execution stack for execution
-                        PostScriptValue::Block(block) => {
-                            for item in block.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
-                        }
-                        
-                        // Closure: execute with captured environment
-                        PostScriptValue::Closure { body, env } => {
-                            // Save current environment for restoration
-                            self.context.execution_stack.push(PostScriptValue::RestoreEnv(self.context.dict_stack.clone()));
-                            // Switch to closure's captured environment
-                            self.context.dict_stack = env;
-                            // Push closure body to execution stack
-                            for item in body.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
+                        // Block: schedule its contents for execution
+                        // Closure: the same, with its captured environment
+                        PostScriptValue::Block(_) | PostScriptValue::Closure { .. } => {
+                            self.context.schedule_procedure(&val);
                         }
                         
+                        // Executable string or file: scanned when it is executed
+                        PostScriptValue::ExecutableString(_) | PostScriptValue::ExecutableFile(_) => self.context.schedule(val),
+
                         // Other values: push to operand stack
                         _ => self.context.push(val),
                     }
@@ -115,24 +418,29 @@ impl Interpreter {
                     // In lexical scoping mode, capture current environment as a closure
                     self.context.push(PostScriptValue::Closure {
                         body: block,
-                        env: self.context.dict_stack.clone(),
+                        env: self.context.dict_stack().to_vec(),
                     });
                 } else {
                     // In dynamic scoping mode, just push the block
                     self.context.push(PostScriptValue::Block(block));
                 }
             }
-            PostScriptValue::ForLoop { current, step, limit, proc } => {
+            PostScriptValue::ForLoop { initial, step, limit, index, proc } => {
                 // For-loop execution: "initial step limit proc for"
-                // Continues while: (step > 0 && current <= limit) || (step < 0 && current >= limit)
-                let continue_loop = if step > 0.0 { current <= limit } else { current >= limit };
+                // The control value is computed from the index so rounding errors don't accumulate
+                // Continues while: (step > 0 && current <= limit) || (step < 0 && current >= limit),
+                // give or take FOR_LOOP_TOLERANCE
+                let current = initial + index as f64 * step;
+                let tolerance = FOR_LOOP_TOLERANCE * step.abs();
+                let continue_loop = if step > 0.0 { current <= limit + tolerance } else { current >= limit - tolerance };
                 
                 if continue_loop {
                     // Push next iteration state back onto execution stack
-                    self.context.execution_stack.push(PostScriptValue::ForLoop {
-                        current: current + step,
+                    self.context.schedule(PostScriptValue::ForLoop {
+                        initial,
                         step,
                         limit,
+                        index: index + 1,
                         proc: proc.clone(),
                     });
                     
@@ -140,21 +448,22 @@ impl Interpreter {
                     self.context.push(PostScriptValue::Real(current));
                     
                     // Execute the procedure with the current index on the stack
-                    match *proc {
-                        PostScriptValue::Block(ref block) => {
-                            for item in block.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
-                        }
-                        PostScriptValue::Closure { ref body, ref env } => {
-                            self.context.execution_stack.push(PostScriptValue::RestoreEnv(self.context.dict_stack.clone()));
-                            self.context.dict_stack = env.clone();
-                            for item in body.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
-                        }
-                        _ => self.context.execution_stack.push(*proc),
-                    }
+                    self.context.schedule_procedure(&proc);
+                }
+            }
+            PostScriptValue::IntForLoop { next, step, limit, proc } => {
+                // Integer for-loop: exact, and ends instead of wrapping when the next
+                // control value would not fit in an integer
+                let in_range = |current: &i64| if step > 0 { *current <= limit } else { *current >= limit };
+                if let Some(current) = next.filter(in_range) {
+                    self.context.schedule(PostScriptValue::IntForLoop {
+                        next: current.checked_add(step),
+                        step,
+                        limit,
+                        proc: proc.clone(),
+                    });
+                    self.context.push(PostScriptValue::Int(current));
+                    self.context.schedule_procedure(&proc);
                 }
             }
             PostScriptValue::RepeatLoop { count, proc } => {
@@ -162,33 +471,116 @@ impl Interpreter {
                 // Executes proc n times
                 if count > 0 {
                     // Push next iteration state back onto execution stack
-                    self.context.execution_stack.push(PostScriptValue::RepeatLoop {
+                    self.context.schedule(PostScriptValue::RepeatLoop {
                         count: count - 1,
                         proc: proc.clone(),
                     });
                     
                     // Execute the procedure
-                    match *proc {
-                        PostScriptValue::Block(ref block) => {
-                            for item in block.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
-                        }
-                        PostScriptValue::Closure { ref body, ref env } => {
-                            self.context.execution_stack.push(PostScriptValue::RestoreEnv(self.context.dict_stack.clone()));
-                            self.context.dict_stack = env.clone();
-                            for item in body.iter().rev() {
-                                self.context.execution_stack.push(item.clone());
-                            }
+                    self.context.schedule_procedure(&proc);
+                }
+            }
+            PostScriptValue::Loop { proc } => {
+                // Endless loop: "proc loop", ended by exit
+                self.context.schedule(PostScriptValue::Loop { proc: proc.clone() });
+                self.context.schedule_procedure(&proc);
+            }
+            PostScriptValue::ForAllLoop { items, index, width, proc } => {
+                // Forall-style execution: pushes each group of items in turn and executes proc
+                if let Some(group) = items.get(index..index + width) {
+                    // Push the current items onto operand stack (available to procedure)
+                    for item in group {
+                        self.context.push(item.clone());
+                    }
+
+                    // Push next iteration state back onto execution stack
+                    self.context.schedule(PostScriptValue::ForAllLoop {
+                        items,
+                        index: index + width,
+                        width,
+                        proc: proc.clone(),
+                    });
+
+                    // Execute the procedure with the current item on the stack
+                    self.context.schedule_procedure(&proc);
+                }
+            }
+            PostScriptValue::SortLoop(mut state) => {
+                // Comparator-driven sort: "array proc sort"
+                // Consumes the previous comparison's result, then asks for the next one
+                if state.pending {
+                    let right_first = match self.context.pop() {
+                        Some(PostScriptValue::Bool(b)) => b,
+                        Some(other) => {
+                            return Err(format!("Type check error: sort comparator must return a bool, got {}", other.type_name()));
                         }
-                        _ => self.context.execution_stack.push(*proc),
+                        None => return Err("Stack underflow".to_string()),
+                    };
+                    state.take(right_first);
+                    state.pending = false;
+                }
+
+                match state.next_comparison() {
+                    Some((a, b)) => {
+                        let proc = state.proc.clone();
+                        state.pending = true;
+                        // Push next sort state back onto execution stack
+                        self.context.schedule(PostScriptValue::SortLoop(state));
+
+                        // Push the pair onto operand stack (available to procedure)
+                        self.context.push(a);
+                        self.context.push(b);
+
+                        // Execute the comparator with the pair on the stack
+                        self.context.schedule_procedure(&proc);
+                    }
+                    None => {
+                        // Sorted: store the sorted elements in the array
+                        let sorted = std::mem::take(&mut state.items);
+                        self.context.push(state.original.with_elements(sorted));
                     }
                 }
             }
+            PostScriptValue::ExecutableString(source) => {
+                // Executable string: scan its contents now and execute the resulting values
+                // Errors in the contents surface here, at execution time
+                let source = latin1_bytes(&source.borrow());
+                let tokenizer = Tokenizer::from_bytes_with_language_level(&source, self.context.language_level);
+                let values = parse_scanned(tokenizer, Some(&self.context))
+                    .map_err(|e| format!("Syntax error in executable string: {}", e))?;
+                allocate_scanned(&mut self.context, &values)?;
+                self.context.schedule_block(&values);
+            }
+            PostScriptValue::ExecutableFile(file) => {
+                // Executable file: scan one object and execute it in the same step, with
+                // the file scheduled below it so operators can read the data that follows
+                // them (see currentfile)
+                let object = file.borrow_mut().next_object(&self.context)?;
+                if let Some(object) = object {
+                    allocate_scanned(&mut self.context, std::slice::from_ref(&object))?;
+                    self.context.schedule(PostScriptValue::ExecutableFile(file));
+                    self.context.record_trace(&object);
+                    self.execute_one(object)?;
+                }
+            }
             PostScriptValue::RestoreEnv(env) => {
                 // Restore dictionary stack after closure execution
-                self.context.dict_stack = env;
+                self.context.replace_dict_stack(env)?;
+            }
+            PostScriptValue::NativeFn(f) => {
+                // Operator placed in a procedure by bind: call it without a lookup
+                f(&mut self.context)?;
             }
+            PostScriptValue::StopContext => {
+                // The procedure of `stopped` ran to completion
+                self.context.push(PostScriptValue::Bool(false));
+            }
+            PostScriptValue::Executive { prompted: false } => {
+                // Next round of executive: prompt first, then read a line
+                self.context.schedule(PostScriptValue::Executive { prompted: true });
+                self.context.schedule(PostScriptValue::Name("prompt".to_string()));
+            }
+            PostScriptValue::Executive { prompted: true } => self.execute_line()?,
             
             // All other values (literals) are pushed to the operand stack
             _ => {
@@ -198,6 +590,152 @@ impl Interpreter {
         Ok(())
     }
     
+    /// Reads one line of the Context's input for `executive` and schedules it as a
+    /// statement file (see `STATEMENT_FILE`), executed as it is scanned, with the
+    /// next round of the loop below it. A line that stops inside a procedure or
+    /// string is continued by the lines after it, until the input completes.
+    ///
+    /// Scanning errors are printed like errors in the line itself; the loop ends at
+    /// the end of the input.
+    fn execute_line(&mut self) -> Result<(), String> {
+        self.context.flush_output()?;
+        let mut line = String::new();
+        loop {
+            let read = self.context.input.read_line(&mut line).map_err(|e| format!("I/O error: {}", e))?;
+            if read == 0 {
+                if line.is_empty() {
+                    return Ok(());
+                }
+                break;
+            }
+            let tokenizer = Tokenizer::with_language_level(&line, self.context.language_level);
+            if !is_unfinished(tokenizer) {
+                break;
+            }
+        }
+        self.context.schedule(PostScriptValue::Executive { prompted: false });
+        let tokenizer = Tokenizer::with_language_level(&line, self.context.language_level);
+        self.context.schedule(PostScriptValue::ExecutableFile(Rc::new(RefCell::new(PostScriptFile {
+            name: STATEMENT_FILE.to_string(),
+            stream: FileStream::Source(tokenizer),
+        }))));
+        Ok(())
+    }
+
+    /// Clears the operand and execution stacks, leaving the dictionaries alone.
+    ///
+    /// Definitions and dictionaries pushed with `begin` survive, so the next
+    /// `execute` starts from empty stacks in the same namespace.
+    pub fn reset_stacks(&mut self) {
+        self.context.clear_operands();
+        self.context.clear_execution_stack();
+    }
+
+    /// Removes every definition made by the program.
+    ///
+    /// Pops all dictionaries pushed with `begin` and empties the user dictionary.
+    /// The system dictionary, including native commands an embedder added with
+    /// `Context::define_system`, other permanent dictionaries (such as the
+    /// standard library's) and the global dictionary, which receives the definitions
+    /// made after `true setglobal`, are left intact. Stacks are not touched; combine
+    /// with `reset_stacks` for a fresh session.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// let program = "true setglobal /g 3 def false setglobal /x 1 def /add { pop pop 0 } def 5 dict begin /y 2 def 7";
+    /// interpreter.execute(parse(Tokenizer::new(program).tokenize().unwrap()).unwrap()).unwrap();
+    /// interpreter.clear_user_definitions();
+    ///
+    /// let context = interpreter.get_context();
+    /// assert_eq!(context.lookup("x"), None);
+    /// assert_eq!(context.lookup("y"), None);
+    /// assert_eq!(context.lookup("g"), Some(PostScriptValue::Int(3)));
+    /// assert!(matches!(context.lookup("add"), Some(PostScriptValue::NativeFn(_))));
+    /// assert_eq!(context.dict_count(), context.permanent_dicts());
+    /// assert_eq!(context.operand_stack(), &[PostScriptValue::Int(7)]);
+    /// ```
+    pub fn clear_user_definitions(&mut self) {
+        self.context.clear_dict_stack();
+        self.context.user_dict().borrow_mut().clear();
+    }
+
+    /// Re-registers the built-in commands in the system dictionary.
+    ///
+    /// Repairs a system dictionary whose operators were overwritten or removed,
+    /// using the Context's current language level and compatibility mode. Other
+    /// system dictionary entries and all user definitions are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// context.define_system("add".to_string(), PostScriptValue::Int(0));
+    /// context.system_dict().borrow_mut().remove("sub");
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// interpreter.reinstall_builtins();
+    /// interpreter.call("add", vec![PostScriptValue::Int(3), PostScriptValue::Int(4)]).unwrap();
+    /// interpreter.call("sub", vec![PostScriptValue::Int(1)]).unwrap();
+    /// assert_eq!(interpreter.get_context().peek(), Some(&PostScriptValue::Int(6)));
+    /// ```
+    pub fn reinstall_builtins(&mut self) {
+        register_builtins(&mut self.context);
+    }
+
+    /// Returns the values executed most recently, oldest first.
+    ///
+    /// Empty unless a `FlightRecorder` is installed in the Context. The recorder is
+    /// cleared at the start of every `execute` call, so after an error the trace
+    /// ends with the value that failed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, FlightRecorder};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// context.flight_recorder = Some(FlightRecorder::new(4));
+    /// let mut interpreter = Interpreter::new(context);
+    ///
+    /// let program = "0 1 1 1000 { add } for oops";
+    /// assert!(interpreter.execute(parse(Tokenizer::new(program).tokenize().unwrap()).unwrap()).is_err());
+    ///
+    /// let trace: Vec<(String, usize)> = interpreter.last_trace().into_iter()
+    ///     .map(|entry| (entry.value, entry.depth))
+    ///     .collect();
+    /// assert_eq!(trace, vec![
+    ///     ("--for-loop--".to_string(), 1),
+    ///     ("add".to_string(), 2),
+    ///     ("--for-loop--".to_string(), 1),
+    ///     ("oops".to_string(), 1),
+    /// ]);
+    /// assert_eq!(interpreter.last_trace()[1].top, vec!["499500".to_string(), "1000".to_string()]);
+    /// ```
+    pub fn last_trace(&self) -> Vec<TraceEntry> {
+        self.context.flight_recorder.as_ref()
+            .map(|recorder| recorder.entries().cloned().collect())
+            .unwrap_or_default()
+    }
+
     pub fn get_context(&self) -> &Context {
         &self.context
     }
@@ -206,3 +744,18 @@ impl Interpreter {
         &mut self.context
     }
 }
+
+/// Accounts for the strings and procedures the scanner created (see `Context::allocate`).
+fn allocate_scanned(context: &mut Context, values: &[PostScriptValue]) -> Result<(), String> {
+    for value in values {
+        match value {
+            PostScriptValue::String(s) => context.allocate(Allocation::String(s.borrow().chars().count()))?,
+            PostScriptValue::Block(items) => {
+                context.allocate(Allocation::Array(items.len()))?;
+                allocate_scanned(context, items)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! Background Jobs
+//!
+//! Runs a PostScript program on a worker thread while its output and progress are
+//! streamed back over a channel, for front ends (such as GUIs) that must stay
+//! responsive while a script runs.
+//!
+//! An `Interpreter` cannot itself be moved to another thread: its values share
+//! state through `Rc` and `RefCell`. `spawn_job` therefore takes the program text
+//! and `JobOptions`, which are `Send`, and builds the interpreter on the worker:
+//!
+//! - Everything the script prints is sent, as bytes, as `JobEvent::Output`
+//! - Every `progress_interval` executed values, a `JobEvent::Progress` is sent
+//! - `JobHandle::cancel` interrupts the script through the Context's interrupt flag
+//! - `JobHandle::join` returns a `JobSnapshot` of the final state, or the error
+//!   the script (or a panic in the worker) ended with
+
+use crate::commands::register_builtins;
+use crate::interpreter::Interpreter;
+use crate::parser::{Tokenizer, parse_scanned};
+use crate::types::{CompatibilityMode, Context, ExecutionStats, Limits, ProgressReporter, MAX_LANGUAGE_LEVEL};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// How a background job is run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobOptions {
+    /// Scoping mode of the interpreter
+    pub lexical_scoping: bool,
+    /// Language level of the interpreter
+    pub language_level: u8,
+    /// Compatibility mode of the interpreter
+    pub compatibility: CompatibilityMode,
+    /// Resource limits of the interpreter
+    pub limits: Limits,
+    /// Number of executed values between two progress events (0 for none)
+    pub progress_interval: u64,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        JobOptions {
+            lexical_scoping: false,
+            language_level: MAX_LANGUAGE_LEVEL,
+            compatibility: CompatibilityMode::default(),
+            limits: Limits::default(),
+            progress_interval: 10_000,
+        }
+    }
+}
+
+/// Something a running job reports.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobEvent {
+    /// Bytes the script printed, as the output sink received them (strings are
+    /// written one byte per character, see `types::latin1_bytes`)
+    Output(Vec<u8>),
+    /// Periodic progress report
+    Progress {
+        /// Values executed so far
+        executed: u64,
+        /// Current operand stack depth
+        operand_stack: usize,
+    },
+}
+
+/// The state a job finished in.
+///
+/// Values are given in their `==` form, since PostScript objects cannot leave the
+/// worker thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSnapshot {
+    /// The operand stack, bottom first
+    pub operand_stack: Vec<String>,
+    /// Number of dictionaries on the dictionary stack
+    pub dict_stack: usize,
+    /// Execution counters of the run
+    pub stats: ExecutionStats,
+}
+
+/// A job running on a worker thread.
+pub struct JobHandle {
+    events: Receiver<JobEvent>,
+    interrupt: Arc<AtomicBool>,
+    thread: JoinHandle<Result<JobSnapshot, String>>,
+}
+
+impl JobHandle {
+    /// The job's events, in the order they happened.
+    ///
+    /// The channel disconnects once the job has finished, so iterating over it
+    /// ends when the job does.
+    pub fn events(&self) -> &Receiver<JobEvent> {
+        &self.events
+    }
+
+    /// Asks the job to stop; it fails with `interpreter::INTERRUPTED` at the next
+    /// executed value. Has no effect on a job that already finished.
+    pub fn cancel(&self) {
+        self.interrupt.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true once the job has finished.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the job to finish and returns its final state.
+    ///
+    /// Fails with the error the script ended with (tokenization, parse or runtime
+    /// error, or `interpreter::INTERRUPTED` after `cancel`), or with a description
+    /// of the panic if the worker panicked. Events not yet received are discarded.
+    pub fn join(self) -> Result<JobSnapshot, String> {
+        self.thread.join().unwrap_or_else(|payload| Err(panic_message(payload)))
+    }
+}
+
+/// Starts running a PostScript program on a worker thread.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::job::{spawn_job, JobEvent, JobOptions};
+/// use postscript_interpreter::interpreter::INTERRUPTED;
+///
+/// // A successful job: collect its output, then its final stack
+/// let job = spawn_job("(sum: ) print 1 2 add dup =".to_string(), JobOptions::default());
+/// let output: Vec<u8> = job.events().iter()
+///     .filter_map(|event| match event { JobEvent::Output(bytes) => Some(bytes), _ => None })
+///     .flatten()
+///     .collect();
+/// assert_eq!(output, b"sum: 3\n");
+/// assert_eq!(job.join().unwrap().operand_stack, vec!["3".to_string()]);
+///
+/// // A looping job streams output and progress until it is cancelled
+/// let options = JobOptions { progress_interval: 100, ..JobOptions::default() };
+/// let job = spawn_job("0 { 1 add (tick) print } 1000000000 exch repeat".to_string(), options);
+/// let mut ticks = 0;
+/// let mut progress = 0;
+/// for event in job.events().iter() {
+///     match event {
+///         JobEvent::Output(bytes) => ticks += bytes.windows(4).filter(|w| w == b"tick").count(),
+///         JobEvent::Progress { .. } => progress += 1,
+///     }
+///     if ticks >= 50 && progress >= 1 {
+///         job.cancel();
+///         break;
+///     }
+/// }
+/// assert_eq!(job.join(), Err(INTERRUPTED.to_string()));
+///
+/// // Errors in the script are reported by join
+/// let job = spawn_job("1 undefinedname".to_string(), JobOptions::default());
+/// assert_eq!(job.join(), Err("Undefined name: undefinedname".to_string()));
+///
+/// // Output is bytes: a Latin-1 character arrives as the single byte it is
+/// let job = spawn_job("(caf\\351) print".to_string(), JobOptions::default());
+/// let output: Vec<u8> = job.events().iter()
+///     .filter_map(|event| match event { JobEvent::Output(bytes) => Some(bytes), _ => None })
+///     .flatten()
+///     .collect();
+/// assert_eq!(output, b"caf\xE9");
+/// ```
+pub fn spawn_job(program: String, options: JobOptions) -> JobHandle {
+    let (sender, events) = mpsc::channel();
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = interrupt.clone();
+    let thread = thread::spawn(move || {
+        panic::catch_unwind(AssertUnwindSafe(|| run_job(&program, &options, sender, flag)))
+            .unwrap_or_else(|payload| Err(panic_message(payload)))
+    });
+    JobHandle { events, interrupt, thread }
+}
+
+/// Builds an interpreter as the options say and runs the program in it.
+fn run_job(program: &str, options: &JobOptions, sender: Sender<JobEvent>, interrupt: Arc<AtomicBool>) -> Result<JobSnapshot, String> {
+    let mut context = Context::new(options.lexical_scoping);
+    context.language_level = options.language_level;
+    context.compatibility = options.compatibility;
+    context.limits = options.limits.clone();
+    context.interrupt = interrupt;
+    context.output = Box::new(ChannelWriter(sender.clone()));
+    context.progress = Some(ProgressReporter {
+        interval: options.progress_interval,
+        callback: Box::new(move |executed, operand_stack| {
+            let _ = sender.send(JobEvent::Progress { executed, operand_stack });
+        }),
+    });
+    register_builtins(&mut context);
+    let mut interpreter = Interpreter::new(context);
+
+    let tokenizer = Tokenizer::with_language_level(program, options.language_level);
+    let values = parse_scanned(tokenizer, Some(interpreter.get_context()))?;
+    interpreter.execute(values)?;
+
+    let context = interpreter.get_context();
+    Ok(JobSnapshot {
+        operand_stack: context.operand_stack().iter().map(|value| value.to_string()).collect(),
+        dict_stack: context.dict_count(),
+        stats: context.stats.clone(),
+    })
+}
+
+/// Output sink that sends everything written to it as `JobEvent::Output`.
+/// Writes succeed even when nobody listens anymore, so the job keeps running.
+struct ChannelWriter(Sender<JobEvent>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.0.send(JobEvent::Output(buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Describes a panic payload as an error message.
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_string());
+    format!("Job panicked: {}", message)
+}
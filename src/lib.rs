@@ -11,6 +11,14 @@
 //! - **interpreter**: Executes PostScriptValue objects using a stack-based execution model
 //! - **commands**: Built-in PostScript command implementations (add, sub, if, for, etc.)
 //!
+//! The **filter** module holds the decoders and encoders of the `filter` operator.
+//! The **function** module builds on these to evaluate PDF Type 4 (calculator) functions,
+//! the **bench** module to time scripts over repeated runs, the **stdlib** module
+//! to load an optional prologue of utility procedures, the **job** module to run
+//! scripts on a worker thread, the **events** module to answer input with JSON
+//! events for editor integration, and the **session** module to save and replay
+//! REPL session transcripts.
+//!
 //! # Data Flow
 //!
 //! 1. **Input** → **parser::Tokenizer** → Converts text into tokens
@@ -40,4 +48,11 @@ pub mod types;
 pub mod parser;
 pub mod interpreter;
 pub mod commands;
+pub mod filter;
+pub mod function;
+pub mod bench;
+pub mod stdlib;
+pub mod job;
+pub mod events;
+pub mod session;
 
@@ -4,18 +4,43 @@
 //! It supports both interactive REPL mode and file execution mode.
 
 use std::env;
-use std::fs;
-use std::io::{self, Write};
-use postscript_interpreter::types::Context;
-use postscript_interpreter::interpreter::Interpreter;
-use postscript_interpreter::parser::{Tokenizer, parse};
-use postscript_interpreter::commands::register_builtins;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::rc::Rc;
+use std::cell::RefCell;
+use postscript_interpreter::types::{CompatibilityMode, Context, FileStream, FlightRecorder, PostScriptFile, PostScriptValue, MAX_LANGUAGE_LEVEL, PRODUCT, REVISION, STATEMENT_FILE, VERSION};
+use postscript_interpreter::interpreter::{Interpreter, INTERRUPTED};
+use postscript_interpreter::parser::{Tokenizer, format_with_width, is_unfinished, parse_scanned, scan_dsc, DEFAULT_FORMAT_WIDTH};
+use postscript_interpreter::commands::{register_builtins, help_text};
+use postscript_interpreter::bench::{bench_construction, run_bench, BenchOptions};
+use postscript_interpreter::stdlib::load_stdlib;
+use postscript_interpreter::events::EventSession;
+use postscript_interpreter::session::{read_transcript, write_transcript};
 
 /// Main entry point for the PostScript interpreter CLI.
 ///
 /// Parses command-line arguments to determine:
 /// - Scoping mode (--lexical flag enables lexical scoping, default is dynamic)
-/// - Input mode (file path for script execution, or REPL if no file provided)
+/// - Language level (--ps-level N emulates PostScript level 1, 2 or 3, default is 3)
+/// - Compatibility mode (--strict follows the PLRM exactly, default is relaxed)
+/// - Input mode (file paths for script execution, or REPL if no file provided)
+/// - Entry point (--entry NAME calls a procedure after the files are loaded;
+///   arguments after `--` are pushed onto the operand stack as strings first)
+/// - Output file (--output PATH writes everything scripts print to PATH instead of
+///   stdout, truncating it unless --append is given; `-` means stdout). Diagnostics
+///   always go to stderr
+/// - Standard library (--stdlib loads the utility procedures of the stdlib module)
+/// - Flight recorder (--trace N keeps the last N executed values and prints them
+///   after a runtime error)
+/// - Event mode (--events answers each line of stdin with a JSON event instead of
+///   running the REPL; see the events module)
+///
+/// `bench SCRIPT` as the first argument runs the benchmark subcommand instead
+/// (see `bench_command`), `--version` prints the version and exits, `--info`
+/// prints the DSC comments of the files (title, bounding box, page count) instead
+/// of running them, and `--format` prints the files in canonical form instead
+/// (`--width N` sets the line width, 80 by default).
 ///
 /// # Example Usage
 ///
@@ -28,84 +53,535 @@ use postscript_interpreter::commands::register_builtins;
 ///
 /// # Execute script with lexical scoping
 /// cargo run -- --lexical script.ps
+///
+/// # Execute script as a Level 1 interpreter
+/// cargo run -- --ps-level 1 script.ps
+///
+/// # Execute script with strict PLRM semantics
+/// cargo run -- --strict script.ps
+///
+/// # Load a library and call its main procedure with two arguments
+/// cargo run -- lib.ps --entry main -- first second
+///
+/// # Append script output to a file
+/// cargo run -- --output result.txt --append job.ps
+///
+/// # Show the last 20 executed values when the script fails
+/// cargo run -- --trace 20 job.ps
+///
+/// # Drive the interpreter from an editor with JSON events
+/// cargo run -- --events
+///
+/// # Show the version (the same values `product`, `version` and `revision` push)
+/// cargo run -- --version
+///
+/// # Show the bounding box and other DSC comments of a document
+/// cargo run -- --info figure.eps
+///
+/// # Print a script in canonical form, in lines of at most 100 characters
+/// cargo run -- --format --width 100 script.ps
+///
+/// # Time a script over 50 runs after 5 warmup runs
+/// cargo run -- bench job.ps --iterations 50 --warmup 5
 /// ```
 fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        bench_command(&args[2..]);
+        return;
+    }
+    if args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--version") {
+        println!("{} {} (revision {}, language level {})", PRODUCT, VERSION, REVISION, MAX_LANGUAGE_LEVEL);
+        return;
+    }
     let mut lexical_scoping = false;
-    let mut input_file = None;
+    let mut language_level = MAX_LANGUAGE_LEVEL;
+    let mut compatibility = CompatibilityMode::Relaxed;
+    let mut input_files = Vec::new();
+    let mut entry = None;
+    let mut entry_args = Vec::new();
+    let mut output_path = None;
+    let mut append = false;
+    let mut trace = None;
+    let mut stdlib = false;
+    let mut events = false;
+    let mut info = false;
+    let mut format_width = None;
 
     // Parse command-line arguments
-    for arg in args.iter().skip(1) {
-        if arg == "--lexical" {
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            entry_args.extend(args_iter.by_ref().cloned());
+        } else if arg == "--entry" {
+            match args_iter.next() {
+                Some(name) => entry = Some(name.clone()),
+                None => {
+                    eprintln!("error: --entry expects a procedure name");
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--output" {
+            match args_iter.next() {
+                Some(path) => output_path = Some(path.clone()),
+                None => {
+                    eprintln!("error: --output expects a file path");
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--trace" {
+            match args_iter.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => trace = Some(n),
+                _ => {
+                    eprintln!("error: --trace expects a positive number of entries");
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--stdlib" {
+            stdlib = true;
+        } else if arg == "--events" {
+            events = true;
+        } else if arg == "--info" {
+            info = true;
+        } else if arg == "--format" {
+            format_width = format_width.or(Some(DEFAULT_FORMAT_WIDTH));
+        } else if arg == "--width" {
+            match args_iter.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => format_width = Some(n),
+                _ => {
+                    eprintln!("error: --width expects a positive number of characters");
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--append" {
+            append = true;
+        } else if arg == "--lexical" {
             lexical_scoping = true;
+        } else if arg == "--strict" {
+            compatibility = CompatibilityMode::Strict;
+        } else if arg == "--ps-level" {
+            language_level = match args_iter.next().and_then(|n| n.parse::<u8>().ok()) {
+                Some(n) if (1..=MAX_LANGUAGE_LEVEL).contains(&n) => n,
+                _ => {
+                    eprintln!("error: --ps-level expects a level from 1 to {}", MAX_LANGUAGE_LEVEL);
+                    process::exit(2);
+                }
+            };
         } else {
-            input_file = Some(arg);
+            input_files.push(arg);
         }
     }
 
-    // Initialize the interpreter context with the chosen scoping mode
+    if info {
+        print_dsc_info(&input_files);
+        return;
+    }
+
+    if let Some(width) = format_width {
+        print_formatted(&input_files, language_level, width);
+        return;
+    }
+
+    if events && (!input_files.is_empty() || entry.is_some()) {
+        eprintln!("error: --events reads its input from stdin and cannot run files or --entry");
+        process::exit(2);
+    }
+
+    // Initialize the interpreter context with the chosen scoping mode, language level
+    // and compatibility mode
     let mut context = Context::new(lexical_scoping);
+    context.language_level = language_level;
+    context.compatibility = compatibility;
+    context.flight_recorder = trace.map(FlightRecorder::new);
+
+    // Redirect script output to a file if requested; exit code 3 means it could not be opened
+    if let Some(path) = output_path.filter(|p| p != "-") {
+        match OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(&path) {
+            Ok(file) => context.output = Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("error: could not open output file {}: {}", path, e);
+                process::exit(3);
+            }
+        }
+    }
     
     // Register all built-in PostScript commands (add, sub, if, for, etc.)
     register_builtins(&mut context);
     
+    // Route Ctrl+C to the interpreter's interrupt flag
+    sigint::install(context.interrupt.clone());
+
     // Create the interpreter with the configured context
     let mut interpreter = Interpreter::new(context);
+    if stdlib && let Err(e) = load_stdlib(&mut interpreter) {
+        eprintln!("error: could not load the standard library: {}", e);
+        process::exit(1);
+    }
 
-    // Choose execution mode based on whether a file was provided
-    if let Some(filename) = input_file {
-        // File execution mode
-        let content = fs::read_to_string(filename).expect("Could not read file");
-        run(&mut interpreter, &content);
-    } else {
+    // JSON event mode: one event per line of stdin, no REPL chrome
+    if events {
+        event_loop(interpreter);
+        return;
+    }
+
+    // Choose execution mode based on whether files or an entry point were provided
+    if input_files.is_empty() && entry.is_none() {
         // Interactive REPL mode
         repl(&mut interpreter);
+        flush_output(&mut interpreter);
+        return;
+    }
+
+    // File execution mode; an interrupted script exits with the conventional SIGINT status
+    for filename in input_files {
+        let content = match fs::read(filename) {
+            Ok(content) => content,
+            Err(e) => {
+                flush_output(&mut interpreter);
+                eprintln!("error: could not read {}: {}", filename, e);
+                process::exit(2);
+            }
+        };
+        if let Err(e) = run_file(&mut interpreter, filename, &content)
+            && e == INTERRUPTED
+        {
+            let context = interpreter.get_context();
+            eprintln!(
+                "Interrupted in {}: {} values on the operand stack, {} dictionaries on the dictionary stack",
+                filename,
+                context.operand_count(),
+                context.dict_count()
+            );
+            flush_output(&mut interpreter);
+            process::exit(130);
+        }
+    }
+
+    // Entry point: call the named procedure with the extra arguments as strings
+    if let Some(name) = entry {
+        let args = entry_args.into_iter()
+            .map(|a| PostScriptValue::String(Rc::new(RefCell::new(a))))
+            .collect();
+        if let Err(e) = interpreter.call(&name, args) {
+            flush_output(&mut interpreter);
+            eprintln!("Runtime Error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    flush_output(&mut interpreter);
+}
+
+/// The `--info` mode: prints the DSC comments of each file, one per line, after
+/// the file name. Exits with status 2 if a file cannot be read.
+fn print_dsc_info(files: &[&String]) {
+    if files.is_empty() {
+        eprintln!("error: --info expects the files to describe");
+        process::exit(2);
+    }
+    for filename in files {
+        let content = match fs::read(filename) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("error: could not read {}: {}", filename, e);
+                process::exit(2);
+            }
+        };
+        let info = scan_dsc(&String::from_utf8_lossy(&content)).to_string();
+        if info.is_empty() {
+            println!("{}: no DSC comments", filename);
+        }
+        for line in info.lines() {
+            println!("{}: {}", filename, line);
+        }
     }
 }
 
-/// Executes PostScript code through the complete pipeline:
-/// 1. Tokenization: Converts source text into tokens
-/// 2. Parsing: Converts tokens into PostScriptValue objects
-/// 3. Execution: Runs the values through the interpreter
-///
-/// Errors at any stage are reported to stderr with appropriate context.
-fn run(interpreter: &mut Interpreter, input: &str) {
-    let mut tokenizer = Tokenizer::new(input);
-    match tokenizer.tokenize() {
-        Ok(tokens) => {
-            match parse(tokens) {
-                Ok(values) => {
-                    if let Err(e) = interpreter.execute(values) {
-                        eprintln!("Runtime Error: {}", e);
+/// The `--format` mode: prints each file in canonical form (see `parser::format`),
+/// with lines of at most width characters. Exits with status 2 if a file cannot be
+/// read, and 1 if it does not parse.
+fn print_formatted(files: &[&String], language_level: u8, width: usize) {
+    if files.is_empty() {
+        eprintln!("error: --format expects the files to format");
+        process::exit(2);
+    }
+    for filename in files {
+        let content = match fs::read(filename) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("error: could not read {}: {}", filename, e);
+                process::exit(2);
+            }
+        };
+        let tokenizer = Tokenizer::from_bytes_with_language_level(&content, language_level);
+        match parse_scanned(tokenizer, None) {
+            Ok(values) => print!("{}", format_with_width(&values, width)),
+            Err(e) => {
+                eprintln!("Parse Error in {}: {}", filename, e.render(&content));
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Flushes the interpreter's output sink, reporting failures (e.g., a full disk) on stderr.
+fn flush_output(interpreter: &mut Interpreter) {
+    if let Err(e) = interpreter.get_context_mut().output.flush() {
+        eprintln!("error: could not write output: {}", e);
+    }
+}
+
+/// The `bench` subcommand: runs a script repeatedly and prints timing statistics.
+///
+/// Options:
+/// - `--iterations N` - Number of measured runs (default 10)
+/// - `--warmup N` - Number of unmeasured runs first (default 1)
+/// - `--reuse` - Run every iteration in one interpreter instead of fresh ones
+/// - `--show-output` - Let the script print (its output is discarded by default)
+/// - `--json` - Print the statistics as one JSON object
+/// - `--construct N` - Instead of running a script, time building N interpreters
+///   with and without a shared system dictionary
+/// - `--lexical`, `--strict`, `--ps-level N` - Configure the interpreters as usual
+///
+/// Exits with status 2 on invalid arguments and 1 if the script fails.
+fn bench_command(args: &[String]) {
+    let mut options = BenchOptions::default();
+    let mut json = false;
+    let mut construct = None;
+    let mut script = None;
+
+    let count = |value: Option<&String>, flag: &str| -> usize {
+        match value.and_then(|n| n.parse::<usize>().ok()) {
+            Some(n) => n,
+            None => {
+                eprintln!("error: {} expects a number", flag);
+                process::exit(2);
+            }
+        }
+    };
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--iterations" => options.iterations = count(args_iter.next(), arg),
+            "--warmup" => options.warmup = count(args_iter.next(), arg),
+            "--reuse" => options.reuse = true,
+            "--show-output" => options.show_output = true,
+            "--json" => json = true,
+            "--construct" => construct = Some(count(args_iter.next(), arg)),
+            "--lexical" => options.lexical_scoping = true,
+            "--strict" => options.compatibility = CompatibilityMode::Strict,
+            "--ps-level" => {
+                options.language_level = match args_iter.next().and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=MAX_LANGUAGE_LEVEL).contains(&n) => n,
+                    _ => {
+                        eprintln!("error: --ps-level expects a level from 1 to {}", MAX_LANGUAGE_LEVEL);
+                        process::exit(2);
                     }
-                }
-                Err(e) => eprintln!("Parse Error: {}", e),
+                };
+            }
+            _ => script = Some(arg),
+        }
+    }
+
+    if let Some(count) = construct {
+        match bench_construction(count, &options) {
+            Ok(report) if json => println!("{}", report.to_json()),
+            Ok(report) => print!("{}", report.to_text()),
+            Err(e) => {
+                eprintln!("Benchmark Error: {}", e);
+                process::exit(1);
             }
         }
-        Err(e) => eprintln!("Tokenization Error: {}", e),
+        return;
+    }
+    let Some(script) = script else {
+        eprintln!("error: bench expects a script file");
+        process::exit(2);
+    };
+    let content = match fs::read(script) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", script, e);
+            process::exit(2);
+        }
+    };
+    match run_bench(&content, &options) {
+        Ok(report) if json => println!("{}", report.to_json()),
+        Ok(report) => print!("{}", report.to_text()),
+        Err(e) => {
+            eprintln!("Benchmark Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Executes a line (or lines) of interactive input, the same way `run_file`
+/// executes a script: one object at a time as it is scanned, so `//name` can use
+/// a definition made earlier in the same input, and a syntax error is reported
+/// (under its source line) after everything before it has run.
+///
+/// Errors are reported to stderr and also returned so callers can react to them
+/// (e.g., to an interrupt).
+fn run(interpreter: &mut Interpreter, input: &[u8]) -> Result<(), String> {
+    run_file(interpreter, STATEMENT_FILE, input)
+}
+
+/// Executes a script file one object at a time, as an executable file object, so
+/// the script can read data embedded after its operators with `currentfile`.
+///
+/// Scanning happens as execution proceeds: a syntax error is reported when the
+/// scanner reaches it, after everything before it has run. Errors are reported to
+/// stderr and returned, as with `run`.
+fn run_file(interpreter: &mut Interpreter, filename: &str, content: &[u8]) -> Result<(), String> {
+    let language_level = interpreter.get_context().language_level;
+    let file = Rc::new(RefCell::new(PostScriptFile {
+        name: filename.to_string(),
+        stream: FileStream::Source(Tokenizer::from_bytes_with_language_level(content, language_level)),
+    }));
+    sigint::set_busy(true);
+    let result = interpreter.execute(vec![PostScriptValue::ExecutableFile(file.clone())]);
+    sigint::set_busy(false);
+    // Nothing stays buffered while idle, as Ctrl+C then exits without flushing
+    flush_output(interpreter);
+    if let Err(e) = &result {
+        // A syntax error in the file itself is shown on its source line
+        match &file.borrow().stream {
+            FileStream::Source(tokenizer) if let Some(error) = tokenizer.last_error()
+                && *e == error.to_string() => eprintln!("Parse Error: {}", error.render(content)),
+            _ => eprintln!("Runtime Error: {}", e),
+        }
+        print_trace(interpreter);
+    }
+    result
+}
+
+/// Prints the flight recorder's trace (if enabled) to stderr, oldest value first.
+fn print_trace(interpreter: &Interpreter) {
+    let trace = interpreter.last_trace();
+    if trace.is_empty() {
+        return;
+    }
+    eprintln!("Last {} executed values (oldest first):", trace.len());
+    for entry in trace {
+        eprintln!("  depth {:<4} {:<24} top: {}", entry.depth, entry.value, entry.top.join(" "));
+    }
+}
+
+/// Ctrl+C (SIGINT) handling.
+///
+/// While PostScript code is executing, Ctrl+C sets the interpreter's interrupt
+/// flag so the current execution stops with an "Interrupted" error and the
+/// session stays alive. While the CLI is idle (e.g., waiting at the REPL prompt),
+/// the first Ctrl+C asks for a second one, which exits the process; running code
+/// in between cancels the request. The handler cannot flush the output sink, so
+/// the CLI flushes it whenever it goes idle (see `run_file`).
+#[cfg(unix)]
+mod sigint {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, OnceLock};
+
+    const SIGINT: i32 = 2;
+    const STDERR: i32 = 2;
+    const EXIT_HINT: &[u8] = b"\n(press Ctrl+C again to exit)\n";
+
+    static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    static BUSY: AtomicBool = AtomicBool::new(false);
+    static EXIT_ARMED: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn write(fd: i32, buf: *const u8, count: usize) -> isize;
+        fn _exit(status: i32) -> !;
+    }
+
+    /// Signal handler: only touches atomics, writes with write(2) or exits, so it
+    /// is async-signal-safe.
+    extern "C" fn handle_sigint(_signum: i32) {
+        match INTERRUPT.get() {
+            Some(flag) if BUSY.load(Ordering::SeqCst) => flag.store(true, Ordering::SeqCst),
+            _ if !EXIT_ARMED.swap(true, Ordering::SeqCst) => unsafe {
+                write(STDERR, EXIT_HINT.as_ptr(), EXIT_HINT.len());
+            },
+            _ => unsafe { _exit(130) },
+        }
+    }
+
+    /// Installs the handler, routing interrupts to the given flag.
+    pub fn install(flag: Arc<AtomicBool>) {
+        let _ = INTERRUPT.set(flag);
+        unsafe {
+            signal(SIGINT, handle_sigint);
+        }
+    }
+
+    /// Marks whether PostScript code is currently executing; starting to execute
+    /// cancels a pending request to exit.
+    pub fn set_busy(busy: bool) {
+        if busy {
+            EXIT_ARMED.store(false, Ordering::SeqCst);
+        }
+        BUSY.store(busy, Ordering::SeqCst);
     }
 }
 
+/// Ctrl+C handling is only available on unix; elsewhere Ctrl+C keeps its default behavior.
+#[cfg(not(unix))]
+mod sigint {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    pub fn install(_flag: Arc<AtomicBool>) {}
+
+    pub fn set_busy(_busy: bool) {}
+}
+
 /// Interactive Read-Eval-Print Loop (REPL).
 ///
-/// Continuously reads lines from stdin, executes them, and displays results.
+/// Continuously reads lines from the Context's input (stdin), executes them, and
+/// displays results.
 /// The interpreter state persists across lines, so variables and definitions
 /// remain available throughout the session.
 ///
-/// Type 'quit' or press Ctrl+D to exit.
+/// Type 'quit' or press Ctrl+D to exit. Ctrl+C interrupts a running command
+/// and exits when pressed twice at the prompt.
+///
+/// Input that stops inside a procedure or string, such as `{ 1 2 add`, is
+/// continued on the next lines (prompted with `...`) and runs once complete.
+///
+/// Lines starting with ':' are meta-commands handled by the REPL itself
+/// (see `meta_command`). Every input that executes successfully is recorded
+/// in the session history so it can be saved as a runnable script.
 fn repl(interpreter: &mut Interpreter) {
-    println!("PostScript Interpreter (Rust)");
+    println!("{}", PRODUCT);
     println!("Type 'quit' to exit.");
     
+    let mut history: Vec<String> = Vec::new();
+    let mut input = String::new();
     loop {
-        print!("PS> ");
+        // Script output is buffered; write it out before the prompt
+        flush_output(interpreter);
+        print!("{}", if input.is_empty() { "PS> " } else { "... " });
         io::stdout().flush().unwrap();
         
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
+        let continued = !input.is_empty();
+        match interpreter.get_context_mut().input.read_line(&mut input) {
             Ok(n) => {
-                if n == 0 { break; } // EOF (Ctrl+D)
-                run(interpreter, &input);
+                if n == 0 {
+                    // EOF (Ctrl+D); unfinished input is run to report it
+                    if continued {
+                        let _ = run(interpreter, input.as_bytes());
+                    }
+                    break;
+                }
+                let line = input.trim();
+                if !continued && line.starts_with(':') {
+                    meta_command(interpreter, &mut history, line);
+                } else if needs_more(interpreter, input.as_bytes()) {
+                    continue;
+                } else if run(interpreter, input.as_bytes()).is_ok() && !line.is_empty() {
+                    history.push(input.trim_end().to_string());
+                }
+                input.clear();
             }
             Err(error) => {
                 eprintln!("error: {}", error);
@@ -115,3 +591,91 @@ fn repl(interpreter: &mut Interpreter) {
     }
 }
 
+/// Whether REPL input stops inside a procedure or string, so that the next line
+/// should continue it.
+fn needs_more(interpreter: &Interpreter, input: &[u8]) -> bool {
+    let language_level = interpreter.get_context().language_level;
+    is_unfinished(Tokenizer::from_bytes_with_language_level(input, language_level))
+}
+
+/// JSON event mode (`--events`).
+///
+/// Announces the event format version, then answers every line of stdin with one
+/// JSON event on stdout (see the events module for the format). Script output is
+/// captured into the events; nothing else is printed to stdout.
+fn event_loop(interpreter: Interpreter) {
+    let mut session = EventSession::new(interpreter);
+    let mut stdout = io::stdout();
+    let _ = writeln!(stdout, "{}", session.ready());
+    for line in io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                process::exit(1);
+            }
+        };
+        let event = session.handle_line(&line);
+        if writeln!(stdout, "{}", event).and_then(|_| stdout.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Handles a REPL meta-command (a line starting with ':').
+///
+/// Supported commands:
+/// - `:save-session FILE` - Write every successfully executed input of the session
+///   to FILE as a runnable PostScript script
+/// - `:replay FILE` - Feed each line of FILE through the REPL, echoing the line
+///   before its output as if it had been typed at the prompt (lines continuing
+///   unfinished input are run with it)
+/// - `:help [TOPIC]` - Show the help categories, a category, or an operator
+///
+/// Meta-commands are never recorded in the session history.
+fn meta_command(interpreter: &mut Interpreter, history: &mut Vec<String>, line: &str) {
+    let mut parts = line.splitn(2, char::is_whitespace);
+    let command = parts.next().unwrap_or("");
+    let argument = parts.next().map(str::trim).unwrap_or("");
+
+    match command {
+        ":help" => {
+            let topic = if argument.is_empty() { None } else { Some(argument) };
+            match help_text(topic) {
+                Ok(text) => print!("{}", text),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+        ":save-session" | ":replay" if argument.is_empty() => {
+            eprintln!("error: {} requires a file name", command);
+        }
+        ":save-session" => {
+            match fs::write(argument, write_transcript(history)) {
+                Ok(()) => println!("Saved {} entries to {}", history.len(), argument),
+                Err(e) => eprintln!("error: could not write {}: {}", argument, e),
+            }
+        }
+        ":replay" => {
+            let content = match fs::read(argument) {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("error: could not read {}: {}", argument, e);
+                    return;
+                }
+            };
+            // Lines are taken as bytes, as a script file's are
+            let language_level = interpreter.get_context().language_level;
+            for entry in read_transcript(&content, language_level) {
+                flush_output(interpreter);
+                for (i, line) in entry.split(|&b| b == b'\n').enumerate() {
+                    println!("{}{}", if i == 0 { "PS> " } else { "... " }, String::from_utf8_lossy(line));
+                }
+                let text = String::from_utf8_lossy(&entry).into_owned();
+                if run(interpreter, &entry).is_ok() && !text.trim().is_empty() && !text.trim_start().starts_with('%') {
+                    history.push(text);
+                }
+            }
+        }
+        _ => eprintln!("error: unknown meta-command {}", command),
+    }
+}
@@ -5,10 +5,54 @@
 //! 1. Tokenization: Converts raw text into tokens
 //! 2. Parsing: Converts tokens into PostScriptValue objects
 
-use crate::types::PostScriptValue;
+use crate::filter::Decoder;
+use crate::types::{Context, Limits, PostScriptValue, MAX_LANGUAGE_LEVEL};
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, BufRead, Read};
 
+/// Character classes of the PostScript scanner (PLRM section 3.2.2).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    /// Part of a name or number
+    Regular,
+    /// Null, tab, line feed, form feed, carriage return and space
+    Whitespace,
+    /// ( ) < > [ ] { } / %
+    Delimiter,
+}
+
+/// Lookup table of character classes for the ASCII range.
+/// Bytes outside the table are regular characters.
+const CHAR_CLASSES: [CharClass; 128] = build_char_classes();
+
+const fn build_char_classes() -> [CharClass; 128] {
+    let mut table = [CharClass::Regular; 128];
+    let whitespace = b"\0\t\n\x0c\r ";
+    let mut i = 0;
+    while i < whitespace.len() {
+        table[whitespace[i] as usize] = CharClass::Whitespace;
+        i += 1;
+    }
+    let delimiters = b"()<>[]{}/%";
+    let mut i = 0;
+    while i < delimiters.len() {
+        table[delimiters[i] as usize] = CharClass::Delimiter;
+        i += 1;
+    }
+    table
+}
+
+/// Returns the scanner character class of the byte c.
+fn char_class(c: u8) -> CharClass {
+    if c.is_ascii() { CHAR_CLASSES[c as usize] } else { CharClass::Regular }
+}
+
+/// Returns true if the byte c ends a name or number (whitespace or a delimiter).
+fn ends_token(c: u8) -> bool {
+    char_class(c) != CharClass::Regular
+}
 
 /// Represents a lexical token in PostScript source code.
 ///
@@ -19,12 +63,15 @@ pub enum Token {
     Int(i64),
     /// Real number literal (e.g., 3.14, -2.5)
     Real(f64),
-    /// String literal (e.g., (hello world))
+    /// String literal (e.g., (hello world) or <68656C6C6F>)
     String(String),
     /// Executable name (e.g., add, sub, myfunction)
     Name(String),
     /// Literal name starting with / (e.g., /x, /myvar)
     LiteralName(String),
+    /// Immediately evaluated name starting with // (e.g., //x), replaced by its
+    /// value when parsed (see `parse_with_context`)
+    ImmediateName(String),
     /// Left bracket [ (used as an operator in PostScript)
     LBracket,
     /// Right bracket ] (used as an operator in PostScript)
@@ -35,88 +82,645 @@ pub enum Token {
     RBrace,
 }
 
+/// A position in the source text: a 1-based line and column.
+///
+/// LF, CR and CR LF each end a line. Columns count bytes (so a character that
+/// takes several bytes in UTF-8 counts several times), starting at 1 for the first
+/// byte of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// A token together with the position of its first character.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// An error in scanning or parsing source text, at the position of the problem.
+///
+/// It displays as the message followed by the position ("Unexpected } at line
+/// 37:12"); the position is also available as numbers for tools to use, and
+/// `render` shows it under the source line. Converting it into a String gives the
+/// displayed text, so `?` works in functions that return string errors.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{parse, ParseError, Span, Tokenizer};
+///
+/// let error = parse(Tokenizer::new("1 2 }").tokenize().unwrap()).unwrap_err();
+/// assert_eq!(error, ParseError::UnexpectedRBrace(Span { line: 1, col: 5 }));
+/// assert_eq!(error.message(), "Unexpected }");
+/// assert_eq!(error.render(b"1 2 }"), "\
+/// Unexpected } at line 1:5
+///  1 | 1 2 }
+///    |     ^");
+///
+/// let error = parse(Tokenizer::new("1 2 add\n  3 }").tokenize().unwrap()).unwrap_err();
+/// assert_eq!(error.to_string(), "Unexpected } at line 2:5");
+///
+/// let error = Tokenizer::new("/s (abc").tokenize().unwrap_err();
+/// assert!(matches!(error, ParseError::UnterminatedString(_)));
+/// assert_eq!(error.to_string(), "Unterminated string at line 1:4");
+///
+/// let error = parse(Tokenizer::new("{ 1 {\n2 }").tokenize().unwrap()).unwrap_err();
+/// assert_eq!(error.to_string(), "Unterminated procedure at line 1:1");
+///
+/// let error = Tokenizer::new("(a) )").tokenize().unwrap_err();
+/// assert_eq!(error, ParseError::UnexpectedRParen(Span { line: 1, col: 5 }));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A } with no { before it
+    UnexpectedRBrace(Span),
+    /// A ) outside a string
+    UnexpectedRParen(Span),
+    /// A { whose procedure the input ends inside
+    UnterminatedBlock(Span),
+    /// A { nested deeper than the limit, which is given
+    NestingTooDeep(Span, usize),
+    /// A ( whose string the input ends inside
+    UnterminatedString(Span),
+    /// A < whose hexadecimal string the input ends inside
+    UnterminatedHexString(Span),
+    /// A <~ whose ASCII85 string the input ends inside
+    UnterminatedAscii85String(Span),
+    /// A byte that is neither a hex digit nor whitespace in a hexadecimal string
+    InvalidHexCharacter(Span, u8),
+    /// A byte that cannot appear in an ASCII85 string
+    InvalidAscii85Character(Span, u8),
+    /// ASCII85 data that does not decode, with the problem
+    InvalidAscii85Data(Span, String),
+    /// `<<`, `>>` or `<~` when scanning for language level 1
+    Level2Syntax(Span),
+    /// An immediately evaluated name, with no dictionary stack to look it up in
+    ImmediateNameWithoutContext(Span, String),
+    /// An immediately evaluated name that is not defined
+    UndefinedImmediateName(Span, String),
+    /// Reading the input failed, with the error message
+    Io(Span, String),
+}
+
+impl ParseError {
+    /// The position of the error.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedRBrace(span)
+            | ParseError::UnexpectedRParen(span)
+            | ParseError::UnterminatedBlock(span)
+            | ParseError::NestingTooDeep(span, _)
+            | ParseError::UnterminatedString(span)
+            | ParseError::UnterminatedHexString(span)
+            | ParseError::UnterminatedAscii85String(span)
+            | ParseError::InvalidHexCharacter(span, _)
+            | ParseError::InvalidAscii85Character(span, _)
+            | ParseError::InvalidAscii85Data(span, _)
+            | ParseError::Level2Syntax(span)
+            | ParseError::ImmediateNameWithoutContext(span, _)
+            | ParseError::UndefinedImmediateName(span, _)
+            | ParseError::Io(span, _) => *span,
+        }
+    }
+
+    /// Whether the error only means that the input ended too early: inside a
+    /// procedure or a string of any kind. More input may complete it.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            ParseError::UnterminatedBlock(_)
+                | ParseError::UnterminatedString(_)
+                | ParseError::UnterminatedHexString(_)
+                | ParseError::UnterminatedAscii85String(_)
+        )
+    }
+
+    /// The description of the error, without its position.
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedRBrace(_) => "Unexpected }".to_string(),
+            ParseError::UnexpectedRParen(_) => "Unexpected )".to_string(),
+            ParseError::UnterminatedBlock(_) => "Unterminated procedure".to_string(),
+            ParseError::NestingTooDeep(_, limit) => {
+                format!("Syntax error: procedure nesting too deep (limit {})", limit)
+            }
+            ParseError::UnterminatedString(_) => "Unterminated string".to_string(),
+            ParseError::UnterminatedHexString(_) => "Unterminated hex string".to_string(),
+            ParseError::UnterminatedAscii85String(_) => "Unterminated ASCII85 string".to_string(),
+            ParseError::InvalidHexCharacter(_, c) => {
+                format!("Syntax error: invalid character {} in hex string", describe_byte(*c))
+            }
+            ParseError::InvalidAscii85Character(_, c) => {
+                format!("Syntax error: invalid character {} in ASCII85 string", describe_byte(*c))
+            }
+            ParseError::InvalidAscii85Data(_, problem) => format!("Syntax error: {} in ASCII85 string", problem),
+            ParseError::Level2Syntax(_) => "Syntax error: << >> and <~ require language level 2".to_string(),
+            ParseError::ImmediateNameWithoutContext(_, name) => {
+                format!("Syntax error: //{} needs a dictionary stack to look it up in", name)
+            }
+            ParseError::UndefinedImmediateName(_, name) => format!("Undefined name: //{}", name),
+            ParseError::Io(_, message) => format!("I/O error: {}", message),
+        }
+    }
+
+    /// Renders the error for a reader: the displayed text, then the source line
+    /// it is on with a caret under the position.
+    ///
+    /// source is the text that was scanned, from its start; lines end at LF, CR or
+    /// CR LF, as for positions. Without the line in source, only the text is
+    /// rendered; of a long line, only the part around the position is shown.
+    pub fn render(&self, source: &[u8]) -> String {
+        let span = self.span();
+        let mut text = self.to_string();
+        let Some(line) = source_line(source, span.line) else {
+            return text;
+        };
+        // A long line is cut down to the part around the position, marked by ...
+        let col = (span.col - 1).min(line.len());
+        let start = col.saturating_sub(SNIPPET_WIDTH / 2);
+        let end = line.len().min(start + SNIPPET_WIDTH);
+        let (open, close) = (if start > 0 { "..." } else { "" }, if end < line.len() { "..." } else { "" });
+        // Columns count bytes; the caret goes under the character at the position,
+        // lined up by repeating any tabs before it
+        let before = String::from_utf8_lossy(&line[start..col]);
+        let padding: String = before.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let snippet = String::from_utf8_lossy(&line[start..end]);
+        let gutter = " ".repeat(span.line.to_string().len());
+        text.push_str(&format!("\n {} | {}{}{}\n {} | {}{}^", span.line, open, snippet, close, gutter, " ".repeat(open.len()), padding));
+        text
+    }
+}
+
+/// How many bytes of a source line `ParseError::render` shows at most.
+const SNIPPET_WIDTH: usize = 80;
+
+/// Returns the bytes of a 1-based line of source, without its line ending.
+fn source_line(source: &[u8], number: usize) -> Option<&[u8]> {
+    let mut start = 0;
+    let mut line = 1;
+    let mut i = 0;
+    while i < source.len() {
+        if matches!(source[i], b'\n' | b'\r') {
+            if line == number {
+                break;
+            }
+            if source[i] == b'\r' && source.get(i + 1) == Some(&b'\n') {
+                i += 1;
+            }
+            line += 1;
+            start = i + 1;
+        }
+        i += 1;
+    }
+    (line == number).then(|| &source[start..i])
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}", self.message(), self.span())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for String {
+    fn from(error: ParseError) -> String {
+        error.to_string()
+    }
+}
+
 /// Tokenizer converts PostScript source text into a sequence of tokens.
 ///
 /// The tokenizer handles:
 /// - Numbers (integers and reals)
-/// - Strings with escape sequences
-/// - Names (executable and literal)
+/// - Strings with escape sequences, hexadecimal strings (<48656C6C6F>) and
+///   ASCII85 strings (<~87cURD]~>)
+/// - Names (executable, literal and immediately evaluated)
 /// - Brackets and braces
 /// - Comments (% to end of line)
-/// - Whitespace
+/// - Whitespace and delimiters as classified by the PLRM (see `CharClass`)
+///
+/// Syntax introduced after language level 1 (`<<`, `>>` and `<~`) is rejected
+/// when the tokenizer is configured for level 1.
+///
+/// The input is scanned as bytes, as PostScript files may hold any byte. Inside a
+/// string literal every byte is one character of the string, so a string holds one
+/// character per byte (0-255) whatever the encoding of the file; in a name, the
+/// bytes are read as UTF-8, except that a byte which is not part of a valid UTF-8
+/// sequence is the Latin-1 character of that value, so distinct names stay
+/// distinct in a Latin-1 file. Input can be given
+/// whole (`new`, `from_bytes`) or read as scanning proceeds (`from_reader`).
+///
+/// A program being executed from a file is scanned one object at a time with
+/// `next_object`; in between, the rest of the input can be read as bytes through
+/// the tokenizer's `BufRead` implementation (this is how `currentfile` reads data
+/// embedded after an operator).
 pub struct Tokenizer {
-    input: Vec<char>,
+    /// The bytes of the input from `base` on, as far as they have been read
+    input: Vec<u8>,
+    /// The position of the first byte of `input`; bytes before it have been scanned
+    /// and dropped (only when reading from `source`)
+    base: usize,
     position: usize,
+    language_level: u8,
+    /// Where the rest of the input is read from, until it ends
+    source: Option<Box<dyn Read>>,
+    /// The error that ended reading from `source`, to be reported by the scanner
+    read_error: Option<io::Error>,
+    /// The last position located by `span_at`, and its span
+    located: Cell<(usize, Span)>,
+    /// The last error scanning has returned; iterating ends after one
+    error: Option<ParseError>,
 }
 
+/// How many bytes `Tokenizer` asks its source for at a time.
+const READ_CHUNK: usize = 8192;
+
+/// How many scanned bytes `Tokenizer` keeps before dropping them, when reading
+/// from a source.
+const KEEP_SCANNED: usize = 64 * 1024;
+
 impl Tokenizer {
     /// Creates a new tokenizer for the given input string.
     pub fn new(input: &str) -> Self {
+        Tokenizer::from_bytes(input.as_bytes())
+    }
+
+    /// Creates a new tokenizer for raw file contents.
+    ///
+    /// A UTF-8 byte order mark at the start is skipped. The contents need not be
+    /// UTF-8: bytes of any value are kept as they are inside strings, and read as
+    /// UTF-8 in names, falling back to Latin-1 for bytes that are not (see `Tokenizer`).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::parser::{Tokenizer, Token};
+    ///
+    /// // Latin-1 "café" in a string literal, with more in a comment
+    /// let tokens = Tokenizer::from_bytes(b"(caf\xE9) % d\xE9j\xE0 vu\n=").tokenize().unwrap();
+    /// let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+    /// assert_eq!(tokens, vec![Token::String("caf\u{E9}".to_string()), Token::Name("=".to_string())]);
+    ///
+    /// // UTF-8 "é" is two bytes, so two characters of a string, but stays one
+    /// // character in a name
+    /// let tokens = Tokenizer::new("(é) /é").tokenize().unwrap();
+    /// let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+    /// assert_eq!(tokens, vec![Token::String("\u{C3}\u{A9}".to_string()), Token::LiteralName("é".to_string())]);
+    ///
+    /// // Latin-1 bytes in names are not UTF-8, so they are read as Latin-1
+    /// let tokens = Tokenizer::from_bytes(b"/caf\xE9 /caf\xE8").tokenize().unwrap();
+    /// let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+    /// assert_eq!(tokens, vec![Token::LiteralName("caf\u{E9}".to_string()), Token::LiteralName("caf\u{E8}".to_string())]);
+    ///
+    /// // A byte order mark does not change the tokens
+    /// let plain = Tokenizer::from_bytes(b"/x 1 def").tokenize().unwrap();
+    /// assert_eq!(Tokenizer::from_bytes(b"\xEF\xBB\xBF/x 1 def").tokenize().unwrap(), plain);
+    /// ```
+    pub fn from_bytes(input: &[u8]) -> Self {
         Tokenizer {
-            input: input.chars().collect(),
+            input: input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input).to_vec(),
+            base: 0,
             position: 0,
+            language_level: MAX_LANGUAGE_LEVEL,
+            source: None,
+            read_error: None,
+            located: Cell::new((0, Span { line: 1, col: 1 })),
+            error: None,
+        }
+    }
+
+    /// Creates a new tokenizer for raw file contents that only accepts the syntax
+    /// of the given language level.
+    pub fn from_bytes_with_language_level(input: &[u8], language_level: u8) -> Self {
+        Tokenizer {
+            language_level,
+            ..Tokenizer::from_bytes(input)
+        }
+    }
+
+    /// Creates a new tokenizer that reads its input from reader as it scans, so
+    /// the input is never held in memory as a whole.
+    ///
+    /// The bytes are scanned as by `from_bytes`. An error reading them is reported
+    /// as a syntax error where the input broke off.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::parser::{Token, Tokenizer};
+    /// use std::io::{Cursor, Read};
+    ///
+    /// // 100,000 copies of "1 " followed by "add"
+    /// let reader = Cursor::new("1 ".repeat(100_000)).chain(&b"add"[..]);
+    /// let tokens: Vec<Token> = Tokenizer::from_reader(reader).map(|t| t.unwrap().token).collect();
+    /// assert_eq!(tokens.len(), 100_001);
+    /// assert_eq!(tokens.last(), Some(&Token::Name("add".to_string())));
+    /// ```
+    pub fn from_reader(reader: impl Read + 'static) -> Self {
+        let mut tokenizer = Tokenizer {
+            source: Some(Box::new(reader)),
+            ..Tokenizer::from_bytes(b"")
+        };
+        // Skip a byte order mark, as from_bytes does
+        tokenizer.byte_at(2);
+        if tokenizer.input.starts_with(b"\xEF\xBB\xBF") {
+            tokenizer.input.drain(..3);
+        }
+        tokenizer
+    }
+
+    /// Creates a new tokenizer that reads its input from reader and only accepts the
+    /// syntax of the given language level.
+    pub fn from_reader_with_language_level(reader: impl Read + 'static, language_level: u8) -> Self {
+        Tokenizer {
+            language_level,
+            ..Tokenizer::from_reader(reader)
+        }
+    }
+
+    /// Creates a new tokenizer that only accepts the syntax of the given language level.
+    pub fn with_language_level(input: &str, language_level: u8) -> Self {
+        Tokenizer {
+            language_level,
+            ..Tokenizer::new(input)
         }
     }
 
     /// Tokenizes the entire input string into a vector of tokens.
     ///
     /// Returns an error if the input contains invalid syntax (e.g., unterminated string).
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens = Vec::new();
-        while self.position < self.input.len() {
+    /// To scan without collecting the tokens, iterate over the tokenizer instead.
+    pub fn tokenize(&mut self) -> Result<Vec<SpannedToken>, ParseError> {
+        self.collect()
+    }
+
+    /// Scans the next token, skipping whitespace and comments.
+    ///
+    /// Returns None at the end of the input.
+    pub fn next_token(&mut self) -> Result<Option<SpannedToken>, ParseError> {
+        loop {
             self.skip_whitespace();
-            if self.position >= self.input.len() {
-                break;
-            }
+            let Some(c) = self.peek() else {
+                return match self.read_error.take() {
+                    Some(e) => Err(ParseError::Io(self.span_at(self.position), e.to_string())),
+                    None => Ok(None),
+                };
+            };
+            let span = self.span_at(self.position);
+            self.drop_scanned();
 
-            let c = self.input[self.position];
-            match c {
-                '%' => self.skip_comment(),
-                '(' => tokens.push(self.read_string()?),
-                '[' => {
-                    tokens.push(Token::LBracket);
+            let next = self.byte_at(self.position + 1);
+            let token = match c {
+                b'%' => {
+                    self.skip_comment();
+                    continue;
+                }
+                b'(' => self.read_string()?,
+                b')' => {
+                    self.position += 1;
+                    return Err(ParseError::UnexpectedRParen(span));
+                }
+                b'[' => {
                     self.position += 1;
+                    Token::LBracket
                 }
-                ']' => {
-                    tokens.push(Token::RBracket);
+                b']' => {
                     self.position += 1;
+                    Token::RBracket
                 }
-                '{' => {
-                    tokens.push(Token::LBrace);
+                b'{' => {
                     self.position += 1;
+                    Token::LBrace
                 }
-                '}' => {
-                    tokens.push(Token::RBrace);
+                b'}' => {
                     self.position += 1;
+                    Token::RBrace
+                }
+                b'/' if next == Some(b'/') => {
+                    self.position += 1; // Skip the first '/'
+                    match self.read_literal_name() {
+                        Token::LiteralName(name) => Token::ImmediateName(name),
+                        token => token,
+                    }
+                }
+                b'/' => self.read_literal_name(),
+                b'<' | b'>' if self.language_level < 2 && starts_level2_syntax(c, next) => {
+                    return Err(ParseError::Level2Syntax(span));
+                }
+                b'<' if next == Some(b'~') => self.read_ascii85_string()?,
+                b'<' if next != Some(b'<') => self.read_hex_string()?,
+                b'<' | b'>' => {
+                    // << and >> are dictionary delimiters; a lone < or > is a
+                    // single-character name
+                    let len = if next == Some(c) { 2 } else { 1 };
+                    self.position += len;
+                    Token::Name(char::from(c).to_string().repeat(len))
                 }
-                '/' => tokens.push(self.read_literal_name()?),
                 _ => {
                     // Try to parse as number first, otherwise treat as name
-                    if c.is_digit(10) || c == '-' || c == '+' || c == '.' {
-                         if let Some(tok) = self.try_read_number() {
-                             tokens.push(tok);
-                         } else {
-                             tokens.push(self.read_name()?);
-                         }
+                    let number = if c.is_ascii_digit() || c == b'-' || c == b'+' || c == b'.' {
+                        self.try_read_number()
                     } else {
-                        tokens.push(self.read_name()?);
+                        None
+                    };
+                    match number {
+                        Some(tok) => tok,
+                        None => self.read_name(),
                     }
                 }
+            };
+            return Ok(Some(SpannedToken { token, span }));
+        }
+    }
+
+    /// Scans the next object of a program being executed from a file: a single
+    /// token, or a whole procedure.
+    ///
+    /// As in the PLRM, the whitespace character that ends a name or number is
+    /// consumed with it (CR LF counting as one), so data read right after an
+    /// operator starts on the next character. Returns None at the end of the input.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::parser::Tokenizer;
+    /// use postscript_interpreter::types::PostScriptValue;
+    /// use std::io::BufRead;
+    ///
+    /// let mut tokenizer = Tokenizer::new("{ 1 add } exec\nDATA\n2");
+    /// assert_eq!(tokenizer.next_object().unwrap(), Some(PostScriptValue::Block(vec![
+    ///     PostScriptValue::Int(1),
+    ///     PostScriptValue::Name("add".to_string()),
+    /// ])));
+    /// assert_eq!(tokenizer.next_object().unwrap(), Some(PostScriptValue::Name("exec".to_string())));
+    ///
+    /// let mut line = String::new();
+    /// tokenizer.read_line(&mut line).unwrap();
+    /// assert_eq!(line, "DATA\n");
+    /// assert_eq!(tokenizer.next_object().unwrap(), Some(PostScriptValue::Int(2)));
+    /// assert_eq!(tokenizer.next_object().unwrap(), None);
+    /// ```
+    pub fn next_object(&mut self) -> Result<Option<PostScriptValue>, ParseError> {
+        self.scan_object(None)
+    }
+
+    /// Scans the next object like `next_object`, replacing immediately evaluated
+    /// names (`//name`) by their values in context (see `parse_with_context`).
+    ///
+    /// As the object is only scanned now, definitions made by the objects executed
+    /// before it are seen.
+    pub fn next_object_with_context(&mut self, context: &Context) -> Result<Option<PostScriptValue>, ParseError> {
+        self.scan_object(Some(context))
+    }
+
+    /// The last error scanning has returned, by iterating or from `next_object`.
+    ///
+    /// This gives the error itself to code that only saw it as text, such as the
+    /// caller of an interpreter executing a file.
+    pub fn last_error(&self) -> Option<&ParseError> {
+        self.error.as_ref()
+    }
+
+    /// Scans the next object for `next_object` and `next_object_with_context`.
+    fn scan_object(&mut self, context: Option<&Context>) -> Result<Option<PostScriptValue>, ParseError> {
+        let result = self.scan_object_tokens(context);
+        if let Err(e) = &result {
+            self.error = Some(e.clone());
+        }
+        result
+    }
+
+    /// Scans the tokens of the next object and parses them, for `scan_object`.
+    fn scan_object_tokens(&mut self, context: Option<&Context>) -> Result<Option<PostScriptValue>, ParseError> {
+        let Some(first) = self.next_token()? else {
+            return Ok(None);
+        };
+        let block = first.token == Token::LBrace;
+        if !block && !ends_token(self.input[self.position - 1 - self.base]) {
+            self.skip_terminating_whitespace();
+        }
+        let mut tokens = vec![first];
+        let mut depth = 1;
+        // A procedure cut short by the end of the input is reported by the parser
+        while block && depth > 0 && let Some(token) = self.next_token()? {
+            match token.token {
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+        Ok(parse_sequence(&mut tokens.into_iter().map(Ok), None, context, 0)?.pop())
+    }
+
+    /// Consumes one whitespace character after a token, or a CR LF pair.
+    fn skip_terminating_whitespace(&mut self) {
+        match self.peek() {
+            Some(b'\r') if self.byte_at(self.position + 1) == Some(b'\n') => self.position += 2,
+            Some(c) if char_class(c) == CharClass::Whitespace => self.position += 1,
+            _ => {}
+        }
+    }
+
+    /// Returns the byte at a position in the input, reading more of it from the
+    /// source if needed; None past the end of the input.
+    fn byte_at(&mut self, position: usize) -> Option<u8> {
+        while position >= self.base + self.input.len() && self.read_more() {}
+        self.input.get(position - self.base).copied()
+    }
+
+    /// Returns the byte at the current position (see `byte_at`).
+    fn peek(&mut self) -> Option<u8> {
+        self.byte_at(self.position)
+    }
+
+    /// Returns the bytes from start up to the current position.
+    fn scanned_since(&self, start: usize) -> &[u8] {
+        &self.input[start - self.base..self.position - self.base]
+    }
+
+    /// Reads the next chunk of the input from the source. Returns false if there
+    /// is no more input.
+    fn read_more(&mut self) -> bool {
+        let Some(source) = &mut self.source else {
+            return false;
+        };
+        let end = self.input.len();
+        self.input.resize(end + READ_CHUNK, 0);
+        let result = loop {
+            match source.read(&mut self.input[end..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        let read = match result {
+            Ok(read) => read,
+            Err(e) => {
+                self.read_error = Some(e);
+                0
+            }
+        };
+        self.input.truncate(end + read);
+        if read == 0 {
+            self.source = None;
+        }
+        read > 0
+    }
+
+    /// Drops the bytes before the current position once enough have gathered, when
+    /// reading from a source. Only called at the start of a token, after it is
+    /// located, as nothing before it is looked at again.
+    fn drop_scanned(&mut self) {
+        if self.source.is_some() && self.position - self.base >= KEEP_SCANNED {
+            self.input.drain(..self.position - self.base);
+            self.base = self.position;
+        }
+    }
+
+    /// Returns the line and column of a position in the input.
+    ///
+    /// LF, CR and CRLF each count as a single line break. Positions are mostly
+    /// asked for in increasing order, so counting goes on from the last one.
+    fn span_at(&self, position: usize) -> Span {
+        let (mut i, mut span) = self.located.get();
+        if position < i {
+            (i, span) = (0, Span { line: 1, col: 1 });
+        }
+        while i < position {
+            match self.input[i - self.base] {
+                b'\n' => span = Span { line: span.line + 1, col: 1 },
+                b'\r' if self.input.get(i + 1 - self.base) != Some(&b'\n') => {
+                    span = Span { line: span.line + 1, col: 1 }
+                }
+                _ => span.col += 1,
             }
+            i += 1;
         }
-        Ok(tokens)
+        self.located.set((position, span));
+        span
     }
 
-    /// Skips whitespace characters (space, tab, newline, etc.).
+    /// Skips whitespace characters (null, tab, line feed, form feed, carriage return, space).
     fn skip_whitespace(&mut self) {
-        while self.position < self.input.len() && self.input[self.position].is_whitespace() {
+        while self.peek().is_some_and(|c| char_class(c) == CharClass::Whitespace) {
             self.position += 1;
         }
     }
 
     /// Skips a comment (from % to end of line).
+    /// A line ends at LF, CR or CRLF.
     fn skip_comment(&mut self) {
-        while self.position < self.input.len() && self.input[self.position] != '\n' {
+        while self.peek().is_some_and(|c| !matches!(c, b'\n' | b'\r')) {
             self.position += 1;
         }
     }
@@ -126,84 +730,182 @@ impl Tokenizer {
     /// Handles:
     /// - Nested parentheses (strings can contain balanced parens)
     /// - Escape sequences (\n, \r, \t, \\, \(, \), etc.)
-    fn read_string(&mut self) -> Result<Token, String> {
+    /// - Octal escapes of one to three digits (\101 is A)
+    /// - A backslash before a line break, which continues the string on the next
+    ///   line; any other CR or CR LF in the string reads as a single LF
+    ///
+    /// Every other byte is one character of the string, whatever its value.
+    fn read_string(&mut self) -> Result<Token, ParseError> {
+        let start = self.position;
         self.position += 1; // Skip '('
         let mut s = String::new();
         let mut depth = 1;
-        
-        while self.position < self.input.len() {
-            let c = self.input[self.position];
+
+        while let Some(c) = self.peek() {
             match c {
-                '(' => {
+                b'(' => {
                     depth += 1;
-                    s.push(c);
+                    s.push('(');
                 }
-                ')' => {
+                b')' => {
                     depth -= 1;
                     if depth == 0 {
                         self.position += 1;
                         return Ok(Token::String(s));
                     }
-                    s.push(c);
+                    s.push(')');
                 }
-                '\\' => {
+                b'\\' => {
                     self.position += 1;
-                    if self.position >= self.input.len() {
-                        return Err("Unexpected end of input in string".to_string());
-                    }
-                    let escaped = self.input[self.position];
+                    let Some(escaped) = self.peek() else {
+                        break;
+                    };
                     match escaped {
-                        'n' => s.push('\n'),
-                        'r' => s.push('\r'),
-                        't' => s.push('\t'),
-                        'b' => s.push('\x08'),
-                        'f' => s.push('\x0c'),
-                        '\\' => s.push('\\'),
-                        '(' => s.push('('),
-                        ')' => s.push(')'),
-                        _ => s.push(escaped), // Fallback
+                        b'n' => s.push('\n'),
+                        b'r' => s.push('\r'),
+                        b't' => s.push('\t'),
+                        b'b' => s.push('\x08'),
+                        b'f' => s.push('\x0c'),
+                        b'0'..=b'7' => {
+                            // Up to three octal digits; the high-order overflow of
+                            // values above 255 is ignored
+                            let mut value: u32 = 0;
+                            let mut digits = 0;
+                            while digits < 3 {
+                                match self.peek().filter(|c| (b'0'..=b'7').contains(c)) {
+                                    Some(digit) => value = value * 8 + u32::from(digit - b'0'),
+                                    None => break,
+                                }
+                                self.position += 1;
+                                digits += 1;
+                            }
+                            s.push(char::from((value & 0xFF) as u8));
+                            continue;
+                        }
+                        // A backslash at the end of a line joins the next line on
+                        b'\n' => {}
+                        b'\r' => {
+                            if self.byte_at(self.position + 1) == Some(b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        // \\, \( and \) stand for the character itself, as does
+                        // any other escaped character
+                        _ => s.push(char::from(escaped)),
                     }
                 }
-                _ => s.push(c),
+                // Any end of line in a string reads as a line feed
+                b'\r' => {
+                    if self.byte_at(self.position + 1) == Some(b'\n') {
+                        self.position += 1;
+                    }
+                    s.push('\n');
+                }
+                _ => s.push(char::from(c)),
             }
             self.position += 1;
         }
-        Err("Unterminated string".to_string())
+        Err(ParseError::UnterminatedString(self.span_at(start)))
     }
 
-    /// Reads a literal name (starts with /).
+    /// Reads a hexadecimal string literal enclosed in < and >.
     ///
-    /// Literal names are used as keys in dictionaries and for variable definitions.
-    /// Example: /x, /myvar, /add
-    fn read_literal_name(&mut self) -> Result<Token, String> {
-        self.position += 1; // Skip '/'
+    /// Each pair of hex digits (either case) is one byte of the string; whitespace
+    /// between digits is ignored, and a final odd digit is padded with 0, so `<4>`
+    /// is the single byte 0x40. Any other character is a syntax error.
+    fn read_hex_string(&mut self) -> Result<Token, ParseError> {
+        let start = self.position;
+        self.position += 1; // Skip '<'
+        let mut s = String::new();
+        let mut high: Option<u8> = None;
+
+        while let Some(c) = self.peek() {
+            self.position += 1;
+            if c == b'>' {
+                if let Some(high) = high {
+                    s.push(char::from(high << 4));
+                }
+                return Ok(Token::String(s));
+            }
+            if char_class(c) == CharClass::Whitespace {
+                continue;
+            }
+            let digit = char::from(c).to_digit(16).ok_or_else(|| {
+                ParseError::InvalidHexCharacter(self.span_at(self.position - 1), c)
+            })? as u8;
+            match high.take() {
+                Some(high) => s.push(char::from(high << 4 | digit)),
+                None => high = Some(digit),
+            }
+        }
+        Err(ParseError::UnterminatedHexString(self.span_at(start)))
+    }
+
+    /// Reads an ASCII85 string literal enclosed in <~ and ~> (language level 2).
+    ///
+    /// The data is decoded as by the ASCII85Decode filter: each group of 5
+    /// characters from `!` to `u` is 4 bytes, `z` is 4 zero bytes, whitespace is
+    /// ignored, and a final group of n characters gives n - 1 bytes.
+    fn read_ascii85_string(&mut self) -> Result<Token, ParseError> {
         let start = self.position;
-        while self.position < self.input.len() {
-            let c = self.input[self.position];
-            if c.is_whitespace() || "()[]{}%/".contains(c) {
+        self.position += 2; // Skip '<~'
+        let mut encoded = Vec::new();
+
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(ParseError::UnterminatedAscii85String(self.span_at(start)));
+            };
+            if c == b'~' && self.byte_at(self.position + 1) == Some(b'>') {
+                self.position += 2;
                 break;
             }
+            if !matches!(c, b'!'..=b'u' | b'z') && char_class(c) != CharClass::Whitespace {
+                return Err(ParseError::InvalidAscii85Character(self.span_at(self.position), c));
+            }
+            encoded.push(c);
             self.position += 1;
         }
-        let name: String = self.input[start..self.position].iter().collect();
-        Ok(Token::LiteralName(name))
+
+        encoded.extend_from_slice(b"~>");
+        let mut decoder = Decoder::by_name("ASCII85Decode").expect("ASCII85Decode is a filter");
+        let data = decoder.decode(&mut encoded.as_slice()).map_err(|e| {
+            // The decoder's message ends with the problem, after the filter name
+            let problem = e.rsplit(": ").next().unwrap_or(&e);
+            ParseError::InvalidAscii85Data(self.span_at(start), problem.to_string())
+        })?;
+        Ok(Token::String(data.into_iter().map(char::from).collect()))
+    }
+
+    /// Reads a literal name (starts with /).
+    ///
+    /// Literal names are used as keys in dictionaries and for variable definitions.
+    /// Example: /x, /myvar, /add
+    fn read_literal_name(&mut self) -> Token {
+        self.position += 1; // Skip '/'
+        Token::LiteralName(self.read_name_text())
     }
 
     /// Reads an executable name (no leading /).
     ///
     /// Executable names are looked up and executed.
     /// Example: add, sub, myfunction
-    fn read_name(&mut self) -> Result<Token, String> {
+    fn read_name(&mut self) -> Token {
+        Token::Name(self.read_name_text())
+    }
+
+    /// Reads the characters of a name up to the next whitespace or delimiter, as
+    /// UTF-8 (each byte that is not is taken as the Latin-1 character of its value).
+    fn read_name_text(&mut self) -> String {
         let start = self.position;
-        while self.position < self.input.len() {
-            let c = self.input[self.position];
-            if c.is_whitespace() || "()[]{}%/".contains(c) {
-                break;
-            }
+        while self.peek().is_some_and(|c| !ends_token(c)) {
             self.position += 1;
         }
-        let name: String = self.input[start..self.position].iter().collect();
-        Ok(Token::Name(name))
+        let mut text = String::new();
+        for chunk in self.scanned_since(start).utf8_chunks() {
+            text.push_str(chunk.valid());
+            text.extend(chunk.invalid().iter().map(|&byte| char::from(byte)));
+        }
+        text
     }
 
     /// Attempts to read a number (integer or real).
@@ -215,25 +917,25 @@ impl Tokenizer {
     /// - Optional sign (+/-)
     /// - Integer literals (e.g., 42, -17)
     /// - Real literals (e.g., 3.14, -2.5, .5)
+    /// - Radix literals (e.g., 16#FF, 2#1011), which are integers
     /// - Distinguishes numbers from names (e.g., "123" vs "123abc")
     fn try_read_number(&mut self) -> Option<Token> {
         let start = self.position;
-        
+
         // Check for optional sign
-        if self.position < self.input.len() && (self.input[self.position] == '+' || self.input[self.position] == '-') {
+        if matches!(self.peek(), Some(b'+' | b'-')) {
             self.position += 1;
         }
-        
+
         let mut has_digit = false;
         let mut has_dot = false;
-        
+
         // Read digits and optional decimal point
-        while self.position < self.input.len() {
-            let c = self.input[self.position];
-            if c.is_digit(10) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
                 has_digit = true;
                 self.position += 1;
-            } else if c == '.' {
+            } else if c == b'.' {
                 if has_dot { break; } // Second dot means end of number
                 has_dot = true;
                 self.position += 1;
@@ -247,17 +949,21 @@ impl Tokenizer {
             self.position = start;
             return None;
         }
-        
-        let s: String = self.input[start..self.position].iter().collect();
-        
+
+        // Only ASCII digits, signs and dots have been read
+        let s = String::from_utf8_lossy(self.scanned_since(start)).into_owned();
+
+        // An unsigned integer followed by # is the base of a radix number
+        if has_digit && !has_dot && s.as_bytes()[0].is_ascii_digit() && self.peek() == Some(b'#') {
+            self.position += 1;
+            return self.read_radix_digits(start, &s);
+        }
+
         // Verify the next character is a delimiter (not part of a name)
-        if self.position < self.input.len() {
-            let c = self.input[self.position];
-            if !c.is_whitespace() && !"()[]{}%/".contains(c) {
-                 // Continues as a name (e.g., "123abc")
-                 self.position = start;
-                 return None;
-            }
+        if self.peek().is_some_and(|c| !ends_token(c)) {
+            // Continues as a name (e.g., "123abc")
+            self.position = start;
+            return None;
         }
 
         // Parse as real or integer
@@ -270,11 +976,55 @@ impl Tokenizer {
                 return Some(Token::Int(i));
             }
         }
-        
+
         // Parsing failed, treat as name
         self.position = start;
         None
     }
+
+    /// Reads the digits of a radix number such as 16#FF or 2#1011, just after the #.
+    ///
+    /// The base must be 2 to 36, and the digits (0-9, then letters in either case)
+    /// must be valid in it. The digits are read as an unsigned 64-bit value and
+    /// taken as two's complement, so `16#FFFFFFFFFFFFFFFF` is -1 (as `cvrs` writes
+    /// it). Anything else, including a value that does not fit in 64 bits, is a
+    /// name, so this rewinds to start and returns None.
+    fn read_radix_digits(&mut self, start: usize, base: &str) -> Option<Token> {
+        let digits_start = self.position;
+        while self.peek().is_some_and(|c| !ends_token(c)) {
+            self.position += 1;
+        }
+        let digits = String::from_utf8_lossy(self.scanned_since(digits_start)).into_owned();
+        let value = base
+            .parse::<u32>()
+            .ok()
+            .filter(|base| (2..=36).contains(base))
+            .filter(|_| !digits.is_empty() && !digits.starts_with(['+', '-']))
+            .and_then(|base| u64::from_str_radix(&digits, base).ok())
+            .map(|bits| bits as i64);
+        if value.is_none() {
+            self.position = start;
+        }
+        value.map(Token::Int)
+    }
+}
+
+/// Checks whether the byte c, followed by next, starts `<<`, `>>` or `<~`.
+fn starts_level2_syntax(c: u8, next: Option<u8>) -> bool {
+    match next {
+        Some(next) => (c == b'<' && (next == b'<' || next == b'~')) || (c == b'>' && next == b'>'),
+        None => false,
+    }
+}
+
+/// Describes a byte in a syntax error: as a quoted character if it is ASCII, by its
+/// value otherwise.
+fn describe_byte(c: u8) -> String {
+    if c.is_ascii() {
+        format!("{:?}", char::from(c))
+    } else {
+        format!("byte 0x{:02X}", c)
+    }
 }
 
 /// Parses a sequence of tokens into PostScriptValue objects.
@@ -286,9 +1036,181 @@ impl Tokenizer {
 ///
 /// The resulting Vec<PostScriptValue> is passed to the interpreter's execute() method,
 /// which pushes these values onto the execution stack for processing.
-pub fn parse(tokens: Vec<Token>) -> Result<Vec<PostScriptValue>, String> {
-    let mut iter = tokens.into_iter();
-    parse_sequence(&mut iter, None)
+///
+/// The tokens can come from any iterator; `parse_scanned` takes them straight from
+/// a tokenizer. Immediately evaluated names (`//name`) need the dictionary stack,
+/// so they are an error here; use `parse_with_context` for them.
+///
+/// Procedures may be nested up to `Limits::max_nesting_depth` deep (the default
+/// limit here; the Context's when parsing with one), so that hostile input cannot
+/// exhaust the native stack.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{parse, parse_scanned, ParseError, Tokenizer};
+///
+/// // A million nested procedures are an error, not a crash
+/// let source = "{".repeat(1_000_000) + &"}".repeat(1_000_000);
+/// let error = parse_scanned(Tokenizer::new(&source), None).unwrap_err();
+/// assert!(matches!(error, ParseError::NestingTooDeep(_, 1000)));
+/// assert_eq!(error.to_string(), "Syntax error: procedure nesting too deep (limit 1000) at line 1:1001");
+///
+/// // Up to the limit is fine
+/// let source = "{".repeat(1000) + &"}".repeat(1000);
+/// assert!(parse(Tokenizer::new(&source).tokenize().unwrap()).is_ok());
+/// ```
+pub fn parse(tokens: impl IntoIterator<Item = SpannedToken>) -> Result<Vec<PostScriptValue>, ParseError> {
+    parse_sequence(&mut tokens.into_iter().map(Ok), None, None, 0)
+}
+
+/// Parses tokens as they are scanned, without collecting them first: the tokens
+/// of a procedure are only held until the procedure is built.
+///
+/// tokens is usually a `Tokenizer`; scanning errors end the parse. Immediately
+/// evaluated names are looked up in context if there is one, as in
+/// `parse_with_context`.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{parse_scanned, Tokenizer};
+/// use postscript_interpreter::types::PostScriptValue;
+///
+/// // A few megabytes of generated source, parsed straight from the tokenizer
+/// let source = "/square { dup mul } def\n".to_string() + &"3 square (abc) pop\n".repeat(150_000);
+/// assert!(source.len() > 2_000_000);
+/// let values = parse_scanned(Tokenizer::new(&source), None).unwrap();
+/// assert_eq!(values.len(), 3 + 4 * 150_000);
+/// assert_eq!(values[1], PostScriptValue::Block(vec![
+///     PostScriptValue::Name("dup".to_string()),
+///     PostScriptValue::Name("mul".to_string()),
+/// ]));
+///
+/// let error = parse_scanned(Tokenizer::new("1 2 (three"), None).unwrap_err();
+/// assert_eq!(error.to_string(), "Unterminated string at line 1:5");
+/// ```
+pub fn parse_scanned(
+    tokens: impl IntoIterator<Item = Result<SpannedToken, ParseError>>,
+    context: Option<&Context>,
+) -> Result<Vec<PostScriptValue>, ParseError> {
+    parse_sequence(&mut tokens.into_iter(), None, context, 0)
+}
+
+/// Parses tokens like `parse`, replacing each immediately evaluated name (`//name`)
+/// by the value the name has in context, even inside procedures.
+///
+/// This is how a prologue freezes the current value of a name into a procedure:
+/// redefining the name afterwards does not change the procedure. A name with no
+/// value is an undefined name error. `//` on its own is the empty name, looked up
+/// like any other.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::commands::register_builtins;
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::parser::{parse, parse_with_context, Tokenizer};
+/// use postscript_interpreter::types::{Context, PostScriptValue};
+///
+/// let mut context = Context::new(false);
+/// register_builtins(&mut context);
+/// let mut interpreter = Interpreter::new(context);
+/// interpreter.execute(parse(Tokenizer::new("/x 5 def").tokenize().unwrap()).unwrap()).unwrap();
+///
+/// let tokens = Tokenizer::new("{ //x /x x }").tokenize().unwrap();
+/// let values = parse_with_context(tokens.clone(), interpreter.get_context()).unwrap();
+/// assert_eq!(values, vec![PostScriptValue::Block(vec![
+///     PostScriptValue::Int(5),
+///     PostScriptValue::LiteralName("x".to_string()),
+///     PostScriptValue::Name("x".to_string()),
+/// ])]);
+///
+/// assert!(parse(tokens).is_err());
+/// let tokens = Tokenizer::new("//nosuchname").tokenize().unwrap();
+/// let error = parse_with_context(tokens, interpreter.get_context()).unwrap_err();
+/// assert_eq!(error.message(), "Undefined name: //nosuchname");
+/// ```
+pub fn parse_with_context(
+    tokens: impl IntoIterator<Item = SpannedToken>,
+    context: &Context,
+) -> Result<Vec<PostScriptValue>, ParseError> {
+    parse_sequence(&mut tokens.into_iter().map(Ok), None, Some(context), 0)
+}
+
+/// Parses input that more may follow, as typed into an interactive loop: Ok(None)
+/// means the input stops inside a procedure or string and needs more to
+/// complete (see `ParseError::is_incomplete`).
+///
+/// Other errors are returned as they are, even inside an unfinished procedure, as
+/// no more input could correct them.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{parse_incomplete, ParseError};
+///
+/// // Strings and procedures still open at the end need more input
+/// assert_eq!(parse_incomplete("(abc"), Ok(None));
+/// assert_eq!(parse_incomplete("<48 65"), Ok(None));
+/// assert_eq!(parse_incomplete("{ 1 { 2 } { 3"), Ok(None));
+/// assert_eq!(parse_incomplete("/f { (a) (b\n").unwrap(), None);
+///
+/// // Once they are closed, the values are there
+/// let values = parse_incomplete("{ 1 { 2 } { 3\n} }").unwrap().unwrap();
+/// assert_eq!(values.len(), 1);
+///
+/// // An error inside an unfinished procedure is still an error
+/// let error = parse_incomplete("{ 1 <4G> { 2").unwrap_err();
+/// assert!(matches!(error, ParseError::InvalidHexCharacter(_, b'G')));
+/// assert!(matches!(parse_incomplete("{ 1 } } {"), Err(ParseError::UnexpectedRBrace(_))));
+/// ```
+pub fn parse_incomplete(source: &str) -> Result<Option<Vec<PostScriptValue>>, ParseError> {
+    parse_incomplete_scanned(Tokenizer::new(source), None)
+}
+
+/// Parses tokens as they are scanned, as `parse_scanned` does, telling input that
+/// needs more to complete from errors, as `parse_incomplete` does.
+pub fn parse_incomplete_scanned(
+    tokens: impl IntoIterator<Item = Result<SpannedToken, ParseError>>,
+    context: Option<&Context>,
+) -> Result<Option<Vec<PostScriptValue>>, ParseError> {
+    match parse_scanned(tokens, context) {
+        Err(e) if e.is_incomplete() => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+/// Whether scanned input stops inside a procedure or string, so an interactive loop
+/// should read more before executing it (see `ParseError::is_incomplete`).
+///
+/// Unlike `parse_incomplete`, nothing is parsed: input that is executed as it is
+/// scanned may use `//name` for a name defined earlier in the same input, which
+/// could not be looked up yet. Input with a syntax error is finished; executing it
+/// reports the error.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{Tokenizer, is_unfinished};
+///
+/// assert!(is_unfinished(Tokenizer::new("/f { //x (a")));
+/// assert!(is_unfinished(Tokenizer::new("{ 1 { 2 } <48")));
+/// assert!(!is_unfinished(Tokenizer::new("/x 5 def { //x } exec")));
+/// assert!(!is_unfinished(Tokenizer::new("{ 1 } } {")));
+/// ```
+pub fn is_unfinished(tokens: impl IntoIterator<Item = Result<SpannedToken, ParseError>>) -> bool {
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            Err(e) => return e.is_incomplete(),
+            Ok(SpannedToken { token: Token::LBrace, .. }) => depth += 1,
+            Ok(SpannedToken { token: Token::RBrace, .. }) if depth == 0 => return false,
+            Ok(SpannedToken { token: Token::RBrace, .. }) => depth -= 1,
+            Ok(_) => {}
+        }
+    }
+    depth > 0
 }
 
 /// Recursively parses a sequence of tokens until a terminator is found.
@@ -298,14 +1220,21 @@ pub fn parse(tokens: Vec<Token>) -> Result<Vec<PostScriptValue>, String> {
 /// - Recursively parsing blocks ({ ... }) into Block values
 /// - Treating [ and ] as executable names (operators)
 ///
-/// The terminator parameter is used when parsing blocks to know when to stop.
-fn parse_sequence(iter: &mut std::vec::IntoIter<Token>, terminator: Option<Token>) -> Result<Vec<PostScriptValue>, String> {
+/// The opening parameter is the position of the { when parsing a block, which then
+/// ends at the matching }; a block still open at the end of the input is reported
+/// there. Immediately evaluated names are looked up in context, if there is one.
+/// depth is the number of blocks the sequence is inside, which may not exceed
+/// `Limits::max_nesting_depth`.
+fn parse_sequence(
+    iter: &mut impl Iterator<Item = Result<SpannedToken, ParseError>>,
+    opening: Option<Span>,
+    context: Option<&Context>,
+    depth: usize,
+) -> Result<Vec<PostScriptValue>, ParseError> {
     let mut sequence = Vec::new();
-    while let Some(token) = iter.next() {
-        if let Some(ref term) = terminator {
-            if token == *term {
-                return Ok(sequence);
-            }
+    while let Some(SpannedToken { token, span }) = iter.next().transpose()? {
+        if opening.is_some() && token == Token::RBrace {
+            return Ok(sequence);
         }
         
         match token {
@@ -314,6 +1243,13 @@ fn parse_sequence(iter: &mut std::vec::IntoIter<Token>, terminator: Option<Token
             Token::String(s) => sequence.push(PostScriptValue::String(Rc::new(RefCell::new(s)))),
             Token::Name(n) => sequence.push(PostScriptValue::Name(n)),
             Token::LiteralName(n) => sequence.push(PostScriptValue::LiteralName(n)),
+            Token::ImmediateName(n) => {
+                let Some(context) = context else {
+                    return Err(ParseError::ImmediateNameWithoutContext(span, n));
+                };
+                let value = context.lookup(&n).ok_or(ParseError::UndefinedImmediateName(span, n))?;
+                sequence.push(value);
+            }
             Token::LBracket => {
                 // [ is treated as an executable name (operator)
                 // In PostScript, [ pushes a mark on the stack
@@ -327,18 +1263,344 @@ fn parse_sequence(iter: &mut std::vec::IntoIter<Token>, terminator: Option<Token
             Token::LBrace => {
                 // { starts a procedure/block - parse until matching }
                 // The contents become a Block value (executable array)
-                let block = parse_sequence(iter, Some(Token::RBrace))?;
+                let limit = context.map_or(Limits::default().max_nesting_depth, |c| c.limits.max_nesting_depth);
+                if depth >= limit {
+                    return Err(ParseError::NestingTooDeep(span, limit));
+                }
+                let block = parse_sequence(iter, Some(span), context, depth + 1)?;
                 sequence.push(PostScriptValue::Block(block));
             }
             Token::RBrace => {
-                return Err("Unexpected }".to_string());
+                return Err(ParseError::UnexpectedRBrace(span));
             }
         }
     }
     
-    if terminator.is_some() {
-        return Err("Unexpected end of input, expected terminator".to_string());
+    if let Some(opening) = opening {
+        return Err(ParseError::UnterminatedBlock(opening));
     }
     
     Ok(sequence)
 }
+
+/// The tokens of the input, scanned one at a time as they are asked for (see
+/// `next_token`). After an error, the iteration ends.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{Token, Tokenizer};
+///
+/// let mut tokens = Tokenizer::new("1 (a) (b").map(|result| result.map(|t| t.token));
+/// assert_eq!(tokens.next(), Some(Ok(Token::Int(1))));
+/// assert_eq!(tokens.next(), Some(Ok(Token::String("a".to_string()))));
+/// assert!(matches!(tokens.next(), Some(Err(_))));
+/// assert_eq!(tokens.next(), None);
+/// ```
+impl Iterator for Tokenizer {
+    type Item = Result<SpannedToken, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let result = self.next_token().transpose();
+        if let Some(Err(e)) = &result {
+            self.error = Some(e.clone());
+        }
+        result
+    }
+}
+
+/// The rest of the input, read as bytes.
+impl Read for Tokenizer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Tokenizer {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.peek().is_none() && let Some(e) = self.read_error.take() {
+            return Err(e);
+        }
+        Ok(&self.input[self.position - self.base..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.base + self.input.len());
+    }
+}
+
+/// The line width `format` keeps to.
+pub const DEFAULT_FORMAT_WIDTH: usize = 80;
+
+/// Formats values as canonical PostScript source, which parses back to the same
+/// values: strings escaped, literal names with `/`, reals with a decimal point,
+/// and procedures that do not fit on a line opened at the end of one, with their
+/// contents indented below. Lines are kept within `DEFAULT_FORMAT_WIDTH`
+/// characters where the values allow it; each ends with a newline.
+///
+/// Values with no syntax, which only immediately evaluated names and running
+/// programs produce (dictionaries, operators, ...), are written as `==` shows them
+/// and do not parse back.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{format, format_with_width, parse, Tokenizer};
+///
+/// let values = parse(Tokenizer::new("/sq{dup mul}def (a\\051b) 16#FF 2.50").tokenize().unwrap()).unwrap();
+/// assert_eq!(format(&values), "/sq { dup mul } def (a\\)b) 255 2.5\n");
+/// assert_eq!(format_with_width(&values, 12), "\
+/// /sq {
+///   dup mul
+/// } def (a\\)b)
+/// 255 2.5
+/// ");
+///
+/// // Formatting keeps the values: parse(format(parse(source))) == parse(source)
+/// // holds for every script of the repository that parses without a dictionary
+/// // stack, at any width
+/// let mut checked = 0;
+/// for entry in std::fs::read_dir(".").unwrap() {
+///     let path = entry.unwrap().path();
+///     if path.extension().is_none_or(|extension| extension != "ps") {
+///         continue;
+///     }
+///     let source = std::fs::read(&path).unwrap();
+///     let Ok(values) = parse(Tokenizer::from_bytes(&source).tokenize().unwrap_or_default()) else {
+///         continue;
+///     };
+///     for width in [1, 20, 40, 80, 200] {
+///         let text = format_with_width(&values, width);
+///         let reparsed = parse(Tokenizer::new(&text).tokenize().unwrap()).unwrap();
+///         assert_eq!(reparsed, values, "{} at width {}", path.display(), width);
+///         assert_eq!(format_with_width(&reparsed, width), text);
+///     }
+///     checked += 1;
+/// }
+/// assert!(checked > 50, "only {} scripts parsed", checked);
+/// ```
+pub fn format(values: &[PostScriptValue]) -> String {
+    format_with_width(values, DEFAULT_FORMAT_WIDTH)
+}
+
+/// Formats values like `format`, keeping lines within width characters where the
+/// values allow it.
+pub fn format_with_width(values: &[PostScriptValue], width: usize) -> String {
+    let mut text = String::new();
+    format_sequence(values, 0, width, &mut text);
+    text
+}
+
+/// Appends the lines of a sequence of values at an indentation to text, for
+/// `format_with_width`.
+fn format_sequence(values: &[PostScriptValue], indent: usize, width: usize, text: &mut String) {
+    let mut line = String::new();
+    for value in values {
+        let flat = flat_syntax(value);
+        let fits = indent + line.chars().count() + 1 + flat.chars().count() <= width;
+        match value {
+            PostScriptValue::Block(body) if !fits && !body.is_empty() => {
+                // Too long for the line: open it here, with its contents below
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push('{');
+                end_line(&mut line, indent, text);
+                format_sequence(body, indent + FORMAT_INDENT, width, text);
+                line.push('}');
+            }
+            _ => {
+                if !line.is_empty() && !fits {
+                    end_line(&mut line, indent, text);
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&flat);
+            }
+        }
+    }
+    if !line.is_empty() {
+        end_line(&mut line, indent, text);
+    }
+}
+
+/// How many spaces `format` indents the contents of a procedure by.
+const FORMAT_INDENT: usize = 2;
+
+/// Appends line to text at an indentation, and empties it.
+fn end_line(line: &mut String, indent: usize, text: &mut String) {
+    text.push_str(&" ".repeat(indent));
+    text.push_str(line);
+    text.push('\n');
+    line.clear();
+}
+
+/// The syntax of a value on a single line: procedures as `{ a b }`, anything else
+/// as `to_ps_syntax` writes it.
+fn flat_syntax(value: &PostScriptValue) -> String {
+    match value {
+        PostScriptValue::Block(body) if body.is_empty() => "{ }".to_string(),
+        PostScriptValue::Block(body) => {
+            let items: Vec<String> = body.iter().map(flat_syntax).collect();
+            format!("{{ {} }}", items.join(" "))
+        }
+        other => other.to_ps_syntax(),
+    }
+}
+
+/// A value of a Document Structuring Convention comment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DscValue<T> {
+    /// The value as given in the header, or in the trailer for `(atend)`
+    Value(T),
+    /// `(atend)` in the header, with no value in the trailer
+    AtEnd,
+    /// A value that could not be understood, as written
+    Raw(String),
+}
+
+impl<T: fmt::Display> fmt::Display for DscValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DscValue::Value(value) => write!(f, "{}", value),
+            DscValue::AtEnd => write!(f, "(atend)"),
+            DscValue::Raw(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// The Document Structuring Convention (DSC) comments of a document that
+/// `scan_dsc` understands. A field is None when its comment is missing.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DscInfo {
+    /// The DSC version of a first line such as `%!PS-Adobe-3.0` ("3.0")
+    pub version: Option<String>,
+    /// `%%Title:` text, as written
+    pub title: Option<DscValue<String>>,
+    /// `%%BoundingBox:` llx lly urx ury, in default user space units
+    pub bounding_box: Option<DscValue<[i64; 4]>>,
+    /// `%%Pages:` page count
+    pub pages: Option<DscValue<u32>>,
+}
+
+/// One line per comment found, such as `BoundingBox: 0 0 612 792`.
+impl fmt::Display for DscInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(version) = &self.version {
+            writeln!(f, "PS-Adobe: {}", version)?;
+        }
+        if let Some(title) = &self.title {
+            writeln!(f, "Title: {}", title)?;
+        }
+        if let Some(bounding_box) = &self.bounding_box {
+            match bounding_box {
+                DscValue::Value([llx, lly, urx, ury]) => writeln!(f, "BoundingBox: {} {} {} {}", llx, lly, urx, ury)?,
+                DscValue::AtEnd => writeln!(f, "BoundingBox: (atend)")?,
+                DscValue::Raw(text) => writeln!(f, "BoundingBox: {}", text)?,
+            }
+        }
+        if let Some(pages) = &self.pages {
+            writeln!(f, "Pages: {}", pages)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the Document Structuring Convention comments of a document.
+///
+/// The header comments run from the first line to `%%EndComments`, or to the
+/// first line that does not start with `%%` (a `%!` first line included); the
+/// first occurrence of each comment counts there, and `%%+` lines continue the
+/// comment before them. A comment given as `(atend)` takes its value from the
+/// trailer, after `%%Trailer`. Values that cannot be understood are kept as
+/// written rather than rejected.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::parser::{scan_dsc, DscValue};
+///
+/// let info = scan_dsc("%!PS-Adobe-3.0\n%%Title: (Report)\n%%BoundingBox: (atend)\n\
+///                      %%Pages: (atend)\n%%EndComments\n/x 1 def\n\
+///                      %%Trailer\n%%BoundingBox: 0 0 612 792\n%%EOF\n");
+/// assert_eq!(info.version.as_deref(), Some("3.0"));
+/// assert_eq!(info.title, Some(DscValue::Value("(Report)".to_string())));
+/// assert_eq!(info.bounding_box, Some(DscValue::Value([0, 0, 612, 792])));
+/// assert_eq!(info.pages, Some(DscValue::AtEnd));
+///
+/// let info = scan_dsc("%!PS-Adobe-2.0\n%%BoundingBox: 0 0 8.5in 11in\n%%Pages: many\n");
+/// assert_eq!(info.bounding_box, Some(DscValue::Raw("0 0 8.5in 11in".to_string())));
+/// assert_eq!(info.pages, Some(DscValue::Raw("many".to_string())));
+/// ```
+pub fn scan_dsc(source: &str) -> DscInfo {
+    let mut info = DscInfo::default();
+    // The comment a %%+ line continues, while still in the header
+    let mut last: Option<&str> = None;
+    let mut in_header = true;
+    let mut in_trailer = false;
+
+    // Lines end at LF, CR or CR LF
+    let lines = source.split('\n').flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'));
+    for (i, line) in lines.enumerate() {
+        if i == 0 && let Some(version) = line.strip_prefix("%!PS-Adobe-") {
+            info.version = version.split_whitespace().next().map(str::to_string);
+            continue;
+        }
+        if in_header && (line == "%%EndComments" || !line.starts_with("%%")) {
+            in_header = false;
+            continue;
+        }
+        if line == "%%Trailer" {
+            in_trailer = true;
+            continue;
+        }
+        if !in_header && !in_trailer {
+            continue;
+        }
+        if let Some(more) = line.strip_prefix("%%+") {
+            if in_header && last == Some("Title") && let Some(DscValue::Value(title)) = &mut info.title {
+                title.push(' ');
+                title.push_str(more.trim());
+            }
+            continue;
+        }
+        let Some((keyword, value)) = line.strip_prefix("%%").and_then(|comment| comment.split_once(':')) else {
+            last = None;
+            continue;
+        };
+        let value = value.trim();
+        last = Some(keyword);
+        match keyword {
+            "Title" => set_dsc_value(&mut info.title, value, in_trailer, |text| Some(text.to_string())),
+            "BoundingBox" => set_dsc_value(&mut info.bounding_box, value, in_trailer, |text| {
+                let numbers: Vec<i64> = text.split_whitespace().map(str::parse).collect::<Result<_, _>>().ok()?;
+                numbers.try_into().ok()
+            }),
+            "Pages" => set_dsc_value(&mut info.pages, value, in_trailer, |text| text.parse().ok()),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Records the value of a DSC comment: in the header the first one counts, and in
+/// the trailer only a comment that was (atend) gets its value.
+fn set_dsc_value<T>(field: &mut Option<DscValue<T>>, text: &str, in_trailer: bool, parse: impl Fn(&str) -> Option<T>) {
+    let wanted = if in_trailer { matches!(field, Some(DscValue::AtEnd)) } else { field.is_none() };
+    if !wanted {
+        return;
+    }
+    *field = Some(if text == "(atend)" && !in_trailer {
+        DscValue::AtEnd
+    } else {
+        parse(text).map_or_else(|| DscValue::Raw(text.to_string()), DscValue::Value)
+    });
+}
@@ -0,0 +1,101 @@
+//! REPL Session Transcripts
+//!
+//! The REPL records every input that executes successfully. `:save-session` writes
+//! that history out as a transcript, a runnable PostScript script with one entry per
+//! input, and `:replay` reads a transcript back entry by entry:
+//!
+//! - `write_transcript` formats the history, after a `TRANSCRIPT_HEADER` comment
+//! - `read_transcript` splits a transcript (or any script) into the entries the REPL
+//!   would have read, joining the lines of an input that continues a procedure or
+//!   string, as the `...` prompt does
+
+use crate::parser::{Tokenizer, is_unfinished};
+
+/// The comment line a saved transcript starts with.
+pub const TRANSCRIPT_HEADER: &str = "% PostScript session transcript";
+
+/// Formats session history as a transcript: the header, then every entry on its own
+/// line(s).
+pub fn write_transcript(history: &[String]) -> String {
+    let mut transcript = format!("{}\n", TRANSCRIPT_HEADER);
+    for entry in history {
+        transcript.push_str(entry);
+        transcript.push('\n');
+    }
+    transcript
+}
+
+/// Splits a transcript into the entries the REPL reads from it, as bytes.
+///
+/// Every line is an entry, except that a line leaving a procedure or string
+/// unfinished (see `parser::is_unfinished`) is joined with the lines after it, up to
+/// the one that completes it or the end of the transcript. Lines end with `\n` or
+/// `\r\n`; entries keep `\n` between their lines. Comment and blank lines are
+/// entries too, as they would be if typed at the prompt.
+///
+/// # Example
+///
+/// A session is run, saved, and replayed into a fresh interpreter, which ends up
+/// with the same definitions:
+///
+/// ```rust
+/// use postscript_interpreter::commands::register_builtins;
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::parser::{Tokenizer, parse_scanned};
+/// use postscript_interpreter::session::{read_transcript, write_transcript};
+/// use postscript_interpreter::types::Context;
+///
+/// fn session() -> Interpreter {
+///     let mut context = Context::new(false);
+///     register_builtins(&mut context);
+///     Interpreter::new(context)
+/// }
+/// fn run(interpreter: &mut Interpreter, entry: &[u8]) -> Result<(), String> {
+///     let values = parse_scanned(Tokenizer::from_bytes(entry), None).map_err(|e| e.to_string())?;
+///     interpreter.execute(values)
+/// }
+///
+/// // Only the inputs that succeed are recorded, as in the REPL
+/// let mut first = session();
+/// let mut history = Vec::new();
+/// for input in ["/x 6 def", "/square {\n  dup mul\n} def", "nosuchname", "/y x square def"] {
+///     if run(&mut first, input.as_bytes()).is_ok() {
+///         history.push(input.to_string());
+///     }
+/// }
+/// let transcript = write_transcript(&history);
+///
+/// let entries = read_transcript(transcript.as_bytes(), 3);
+/// assert_eq!(entries.len(), 4);
+/// assert_eq!(entries[2], b"/square {\n  dup mul\n} def");
+///
+/// let mut second = session();
+/// for entry in &entries {
+///     run(&mut second, entry).unwrap();
+/// }
+/// for name in ["x", "square", "y"] {
+///     assert_eq!(second.get_context().lookup(name), first.get_context().lookup(name));
+/// }
+/// assert_eq!(format!("{}", second.get_context().lookup("y").unwrap()), "36");
+/// ```
+pub fn read_transcript(content: &[u8], language_level: u8) -> Vec<Vec<u8>> {
+    let mut entries = Vec::new();
+    let mut entry = Vec::new();
+    let mut lines = content.split(|&b| b == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_none() && line.is_empty() {
+            break;
+        }
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !entry.is_empty() {
+            entry.push(b'\n');
+        }
+        entry.extend_from_slice(line);
+        let unfinished = is_unfinished(Tokenizer::from_bytes_with_language_level(&entry, language_level));
+        if lines.peek().is_some() && unfinished {
+            continue;
+        }
+        entries.push(std::mem::take(&mut entry));
+    }
+    entries
+}
@@ -0,0 +1,68 @@
+//! Standard Library Prologue
+//!
+//! An optional set of utility procedures written in PostScript (see `PROLOGUE`),
+//! for the helpers every program ends up writing: unit conversions, min/max, and
+//! debugging aids.
+//!
+//! The procedures live in their own dictionary, inserted into the dictionary stack
+//! between the system dictionary and the user dictionary. They are visible to all
+//! code, `end` cannot pop them, `Interpreter::clear_user_definitions` keeps them,
+//! and a program that defines a name of its own shadows the library's.
+//!
+//! | Procedure   | Stack                          | Effect                                 |
+//! |-------------|--------------------------------|----------------------------------------|
+//! | `inch`      | num → num                      | Inches to points                       |
+//! | `cm`        | num → num                      | Centimetres to points                  |
+//! | `mm`        | num → num                      | Millimetres to points                  |
+//! | `min`       | num1 num2 → num                | The smaller number                     |
+//! | `max`       | num1 num2 → num                | The larger number                      |
+//! | `sum`       | num1 … numn n → num            | Sum of the top n numbers (n ≥ 1)       |
+//! | `dumpstack` | any1 … anyn → any1 … anyn      | Prints every operand, topmost first    |
+
+use crate::interpreter::Interpreter;
+use crate::parser::{Tokenizer, parse};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// PostScript source of the standard library.
+pub const PROLOGUE: &str = include_str!("stdlib.ps");
+
+/// Loads the standard library into an interpreter.
+///
+/// Must be called at most once per interpreter, after `register_builtins`.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::types::{Context, PostScriptValue};
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::parser::{Tokenizer, parse};
+/// use postscript_interpreter::commands::register_builtins;
+/// use postscript_interpreter::stdlib::load_stdlib;
+///
+/// let mut context = Context::new(false);
+/// register_builtins(&mut context);
+/// let mut interpreter = Interpreter::new(context);
+/// load_stdlib(&mut interpreter).unwrap();
+///
+/// let program = "2 inch 3 9 max 4 2 sum /max { pop pop 0 } def 1 2 max";
+/// interpreter.execute(parse(Tokenizer::new(program).tokenize().unwrap()).unwrap()).unwrap();
+/// assert_eq!(interpreter.get_context().operand_stack(), &[
+///     PostScriptValue::Int(144),
+///     PostScriptValue::Int(13),
+///     PostScriptValue::Int(0),
+/// ]);
+/// ```
+pub fn load_stdlib(interpreter: &mut Interpreter) -> Result<(), String> {
+    let values = parse(Tokenizer::new(PROLOGUE).tokenize()?)?;
+    let library = Rc::new(RefCell::new(HashMap::new()));
+
+    // Run the prologue with the library dictionary as the current dictionary
+    let context = interpreter.get_context_mut();
+    context.insert_permanent_dict(library.clone());
+    context.begin(library)?;
+    let result = interpreter.execute(values);
+    let ended = interpreter.get_context_mut().end();
+    result.and(ended)
+}
@@ -4,10 +4,47 @@
 //! - `PostScriptValue`: Represents all possible PostScript values and execution states
 //! - `Context`: Holds the complete interpreter state (stacks, dictionaries, scoping mode)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::rc::Rc;
+use std::any::Any;
+use std::rc::{Rc, Weak};
 use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use crate::parser::Tokenizer;
+use crate::filter::{DecodeFilter, EncodeFilter};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Highest PostScript language level the interpreter can emulate.
+pub const MAX_LANGUAGE_LEVEL: u8 = 3;
+
+/// Name of the interpreter, pushed by `product` and shown by the CLI.
+pub const PRODUCT: &str = "PostScript Interpreter (Rust)";
+
+/// Version of the interpreter, pushed by `version` and shown by `--version`.
+/// Taken from the crate version, so the two cannot drift apart.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Revision number of the interpreter, pushed by `revision` and shown by `--version`.
+/// Incremented with every release, including those that keep the version.
+pub const REVISION: i64 = 1;
+
+/// Name of the file that holds a statement of interactive input (a REPL line, or a
+/// line read by `executive`) while it executes.
+///
+/// `currentfile` does not push this file but the Context's input, which holds the
+/// lines after the statement, so data for the statement can follow it.
+pub const STATEMENT_FILE: &str = "%statementedit";
+
+/// Tolerance of the `for` loop's limit test, as a fraction of the step.
+///
+/// The control value of iteration i is computed as `initial + i * step` rather than
+/// by repeated addition, and a value that passes the limit by less than
+/// `FOR_LOOP_TOLERANCE * |step|` still runs, so `0 0.1 0.3 { } for` includes the
+/// iteration for 0.30000000000000004. Integer loops never come that close to the
+/// limit without reaching it, so they are unaffected.
+pub const FOR_LOOP_TOLERANCE: f64 = 1e-9;
 
 /// Represents all possible values and execution states in the PostScript interpreter.
 ///
@@ -16,7 +53,16 @@ use std::cell::RefCell;
 /// - The interpreter executes PostScriptValue objects
 /// - The operand stack stores PostScriptValue objects
 /// - The execution stack contains PostScriptValue objects to be executed
+///
+/// # Executability
+///
+/// PostScript objects carry a literal/executable attribute. Here it is part of the
+/// variant: `Name`, `Block`, `Closure`, `ExecutableString`, `ExecutableFile` and
+/// `NativeFn` are executable, their counterparts `LiteralName`, `Array`, `String`
+/// and `File` (and every other value) are literal. `cvx` and `cvlit` convert between the pairs; see
+/// `is_executable`.
 #[derive(Debug, Clone, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub enum PostScriptValue {
     /// Integer number (e.g., 42, -17)
     Int(i64),
@@ -30,6 +76,10 @@ pub enum PostScriptValue {
     /// String literal (e.g., (hello world))
     /// Wrapped in Rc<RefCell<>> to support mutation (required for putinterval)
     String(Rc<RefCell<String>>),
+
+    /// Executable string (made by `cvx` from a string, sharing its storage)
+    /// Executing it scans its contents and executes the resulting values
+    ExecutableString(Rc<RefCell<String>>),
     
     /// Executable name - a name that will be looked up and executed (e.g., add, sub, myfunction)
     Name(String),
@@ -39,7 +89,8 @@ pub enum PostScriptValue {
     LiteralName(String),
     
     /// Array of values (e.g., [1 2 3])
-    Array(Vec<PostScriptValue>),
+    /// Uses Rc<RefCell<>> so `put` changes it in place, visibly through every reference
+    Array(Rc<RefCell<Vec<PostScriptValue>>>),
     
     /// Dictionary - a hash map wrapped in Rc<RefCell<>> for shared mutable access
     /// Multiple references can point to the same dictionary (e.g., on dict stack)
@@ -47,6 +98,20 @@ pub enum PostScriptValue {
     
     /// Mark value used for array construction (the [ operator pushes this)
     Mark,
+
+    /// The null object, which fills newly created arrays
+    Null,
+
+    /// Snapshot pushed by `save`; `restore` rolls composite objects back to it
+    Save(Rc<SaveState>),
+
+    /// File object made by `file`; copies share the open file
+    File(Rc<RefCell<PostScriptFile>>),
+
+    /// Executable file (made by `cvx` from a file, or running a script), sharing the open file
+    /// Executing it reads and executes one object at a time, so the program can read
+    /// the data that follows an operator (see `currentfile`)
+    ExecutableFile(Rc<RefCell<PostScriptFile>>),
     
     /// Native Rust function that implements a built-in PostScript command
     /// Takes a mutable Context reference and returns Result
@@ -59,13 +124,41 @@ pub enum PostScriptValue {
     // === Control Flow States ===
     // These variants represent active loop states on the execution stack
     
-    /// Active for-loop state
-    /// Stores current iteration value, step size, limit, and procedure to execute
-    ForLoop { current: f64, step: f64, limit: f64, proc: Box<PostScriptValue> },
+    /// Active for-loop state with a real control value
+    /// Stores initial value, step size, limit, the index of the next iteration, and
+    /// procedure to execute (see `FOR_LOOP_TOLERANCE` for the limit test)
+    ForLoop { initial: f64, step: f64, limit: f64, index: u64, proc: Box<PostScriptValue> },
+
+    /// Active for-loop state with an integer control value (all three operands were integers)
+    /// Stores the next control value, step size, limit, and procedure to execute; the
+    /// next value is None once stepping would overflow, which ends the loop
+    IntForLoop { next: Option<i64>, step: i64, limit: i64, proc: Box<PostScriptValue> },
     
     /// Active repeat-loop state
     /// Stores remaining iteration count and procedure to execute
     RepeatLoop { count: i64, proc: Box<PostScriptValue> },
+
+    /// Active `loop` state
+    /// Executes the procedure until `exit` (or an error) ends the loop
+    Loop { proc: Box<PostScriptValue> },
+
+    /// Active forall-style loop state
+    /// Pushes the `width` items starting at items[index] and executes the procedure,
+    /// once per remaining group (width 2 enumerates the key-value pairs of a dictionary)
+    ForAllLoop { items: Vec<PostScriptValue>, index: usize, width: usize, proc: Box<PostScriptValue> },
+
+    /// Active merge sort driven by a user comparator (`array proc sort`)
+    /// The state advances by one comparison each time it is executed
+    SortLoop(Box<MergeSort>),
+
+    /// Marker of an active `stopped` context
+    /// Pushes false when reached normally; `stop` and errors unwind to it and push true
+    StopContext,
+
+    /// Active `executive` loop
+    /// Runs `prompt` and then (once `prompted`) reads, scans and executes one line of
+    /// the Context's input; errors in the line unwind to it and are printed
+    Executive { prompted: bool },
     
     // === Lexical Scoping Support ===
     
@@ -78,136 +171,2154 @@ pub enum PostScriptValue {
     RestoreEnv(Vec<Rc<RefCell<HashMap<String, PostScriptValue>>>>),
 }
 
+impl PostScriptValue {
+    /// Returns the PostScript type name of the value (as reported by the `type` operator).
+    ///
+    /// Used in error messages so that type check errors say which type was rejected.
+    /// Internal execution states report the type of the object they stand for.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PostScriptValue::Int(_) => "integertype",
+            PostScriptValue::Real(_) => "realtype",
+            PostScriptValue::Bool(_) => "booleantype",
+            PostScriptValue::String(_) | PostScriptValue::ExecutableString(_) => "stringtype",
+            PostScriptValue::Name(_) | PostScriptValue::LiteralName(_) => "nametype",
+            PostScriptValue::Array(_) | PostScriptValue::Block(_) | PostScriptValue::Closure { .. } => "arraytype",
+            PostScriptValue::Dict(_) => "dicttype",
+            PostScriptValue::Mark => "marktype",
+            PostScriptValue::Null => "nulltype",
+            PostScriptValue::Save(_) => "savetype",
+            PostScriptValue::File(_) | PostScriptValue::ExecutableFile(_) => "filetype",
+            PostScriptValue::NativeFn(_) => "operatortype",
+            PostScriptValue::ForLoop { .. }
+            | PostScriptValue::IntForLoop { .. }
+            | PostScriptValue::RepeatLoop { .. }
+            | PostScriptValue::Loop { .. }
+            | PostScriptValue::ForAllLoop { .. }
+            | PostScriptValue::SortLoop(_)
+            | PostScriptValue::StopContext
+            | PostScriptValue::Executive { .. }
+            | PostScriptValue::RestoreEnv(_) => "operatortype",
+        }
+    }
+
+    /// Returns true if the value is executable (as reported by the `xcheck` operator).
+    pub fn is_executable(&self) -> bool {
+        matches!(
+            self,
+            PostScriptValue::Name(_)
+                | PostScriptValue::Block(_)
+                | PostScriptValue::Closure { .. }
+                | PostScriptValue::ExecutableString(_)
+                | PostScriptValue::ExecutableFile(_)
+                | PostScriptValue::NativeFn(_)
+        )
+    }
+
+    /// Renders the value in PostScript syntax, as `==` and `pstack` print it.
+    ///
+    /// Scanning the text of a number, string or name gives back an equal value:
+    /// - Strings are in parentheses, with `(`, `)` and `\` escaped by a backslash,
+    ///   `\n`, `\r`, `\t`, `\b` and `\f` for those characters, and other control
+    ///   characters as three octal digits (`\033`)
+    /// - Literal names start with `/`
+    /// - Reals always have a decimal point or an exponent (`3.0`)
+    /// - Arrays are in `[...]` and procedures in `{...}`, with their elements
+    ///   rendered the same way; an array inside itself is `-array-`
+    ///
+    /// Values with no syntax print as in the PLRM: dictionaries as `-dict-`, marks as
+    /// `-mark-`, files as `-file-`, and so on.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::PostScriptValue;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// let values = vec![
+    ///     PostScriptValue::Int(-7),
+    ///     PostScriptValue::Real(3.0),
+    ///     PostScriptValue::Real(0.25),
+    ///     PostScriptValue::String(Rc::new(RefCell::new("a(b)c \\ d\n\te".to_string()))),
+    ///     PostScriptValue::String(Rc::new(RefCell::new("\x1b[0m\x7f".to_string()))),
+    ///     PostScriptValue::LiteralName("key".to_string()),
+    ///     PostScriptValue::Name("add".to_string()),
+    /// ];
+    /// for value in values {
+    ///     let text = value.to_ps_syntax();
+    ///     let scanned = parse(Tokenizer::new(&text).tokenize().unwrap()).unwrap();
+    ///     assert_eq!(scanned, vec![value], "{}", text);
+    /// }
+    ///
+    /// let string = PostScriptValue::String(Rc::new(RefCell::new("a(b)c".to_string())));
+    /// assert_eq!(string.to_ps_syntax(), "(a\\(b\\)c)");
+    /// assert_eq!(PostScriptValue::Real(3.0).to_ps_syntax(), "3.0");
+    /// ```
+    pub fn to_ps_syntax(&self) -> String {
+        let mut text = String::new();
+        self.write_ps_syntax(&mut text, &mut Vec::new());
+        text
+    }
+
+    /// Appends the `to_ps_syntax` text of the value; arrays holds the arrays being
+    /// rendered, to catch an array inside itself.
+    fn write_ps_syntax(&self, text: &mut String, arrays: &mut Vec<usize>) {
+        let elements = |items: &[PostScriptValue], text: &mut String, arrays: &mut Vec<usize>| {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    text.push(' ');
+                }
+                item.write_ps_syntax(text, arrays);
+            }
+        };
+        match self {
+            PostScriptValue::Real(r) => {
+                let number = r.to_string();
+                text.push_str(&number);
+                if r.is_finite() && !number.contains(['.', 'e']) {
+                    text.push_str(".0");
+                }
+            }
+            PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => {
+                text.push('(');
+                for c in s.borrow().chars() {
+                    match c {
+                        '(' | ')' | '\\' => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                        '\n' => text.push_str("\\n"),
+                        '\r' => text.push_str("\\r"),
+                        '\t' => text.push_str("\\t"),
+                        '\x08' => text.push_str("\\b"),
+                        '\x0c' => text.push_str("\\f"),
+                        c if c.is_ascii_control() || ('\u{80}'..='\u{FF}').contains(&c) => {
+                            text.push_str(&format!("\\{:03o}", c as u32))
+                        }
+                        c => text.push(c),
+                    }
+                }
+                text.push(')');
+            }
+            PostScriptValue::Array(arr) => {
+                let address = Rc::as_ptr(arr) as usize;
+                if arrays.contains(&address) {
+                    text.push_str("-array-");
+                    return;
+                }
+                arrays.push(address);
+                text.push('[');
+                elements(&arr.borrow(), text, arrays);
+                text.push(']');
+                arrays.pop();
+            }
+            PostScriptValue::Block(body) | PostScriptValue::Closure { body, .. } => {
+                text.push('{');
+                elements(body, text, arrays);
+                text.push('}');
+            }
+            PostScriptValue::Dict(_) => text.push_str("-dict-"),
+            PostScriptValue::Mark => text.push_str("-mark-"),
+            PostScriptValue::Save(_) => text.push_str("-save-"),
+            PostScriptValue::File(_) | PostScriptValue::ExecutableFile(_) => text.push_str("-file-"),
+            other => text.push_str(&other.to_string()),
+        }
+    }
+
+    /// Gives an array or procedure the elements of `items`, as `sort` does with its
+    /// result: an array's elements are replaced in place, so every reference to it
+    /// sees them, and a procedure (which is not shared) is returned as a copy with
+    /// the new body. Other values are returned as they are.
+    pub fn with_elements(self, items: Vec<PostScriptValue>) -> PostScriptValue {
+        match self {
+            PostScriptValue::Array(array) => {
+                *array.borrow_mut() = items;
+                PostScriptValue::Array(array)
+            }
+            PostScriptValue::Block(_) => PostScriptValue::Block(items),
+            PostScriptValue::Closure { env, .. } => PostScriptValue::Closure { body: items, env },
+            other => other,
+        }
+    }
+
+    /// Returns a deep copy of the value.
+    ///
+    /// Dictionaries, strings and arrays (including procedure bodies) are copied
+    /// recursively into fresh Rc cells, so mutating the copy never affects the
+    /// original. Sharing inside the copied structure is preserved: two references
+    /// to the same dictionary or string remain one object in the copy, and a
+    /// dictionary that contains itself is copied into a dictionary that contains
+    /// the copy. Native functions and captured closure environments are copied by
+    /// reference.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::PostScriptValue;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    ///
+    /// let dict = Rc::new(RefCell::new(HashMap::new()));
+    /// dict.borrow_mut().insert("self".to_string(), PostScriptValue::Dict(dict.clone()));
+    ///
+    /// let copy = PostScriptValue::Dict(dict.clone()).deep_clone();
+    /// if let PostScriptValue::Dict(c) = copy {
+    ///     assert!(!Rc::ptr_eq(&c, &dict));
+    ///     match c.borrow().get("self") {
+    ///         Some(PostScriptValue::Dict(inner)) => assert!(Rc::ptr_eq(inner, &c)),
+    ///         _ => panic!("self reference was not copied"),
+    ///     }
+    /// }
+    /// ```
+    pub fn deep_clone(&self) -> PostScriptValue {
+        self.deep_clone_with(&mut HashMap::new())
+    }
+
+    /// Recursive worker for `deep_clone`.
+    ///
+    /// `copies` maps the address of every Rc cell copied so far to its copy, which
+    /// is what preserves sharing and terminates cycles.
+    fn deep_clone_with(&self, copies: &mut HashMap<usize, PostScriptValue>) -> PostScriptValue {
+        match self {
+            PostScriptValue::String(s) => {
+                let addr = Rc::as_ptr(s) as usize;
+                if let Some(copy) = copies.get(&addr) {
+                    return copy.clone();
+                }
+                let copy = PostScriptValue::String(Rc::new(RefCell::new(s.borrow().clone())));
+                copies.insert(addr, copy.clone());
+                copy
+            }
+            PostScriptValue::ExecutableString(s) => match PostScriptValue::String(s.clone()).deep_clone_with(copies) {
+                PostScriptValue::String(copy) => PostScriptValue::ExecutableString(copy),
+                other => other,
+            },
+            PostScriptValue::Dict(d) => {
+                let addr = Rc::as_ptr(d) as usize;
+                if let Some(copy) = copies.get(&addr) {
+                    return copy.clone();
+                }
+                // Register the new dictionary before copying its entries so that
+                // entries referring back to it resolve to the copy
+                let new_dict = Rc::new(RefCell::new(HashMap::new()));
+                copies.insert(addr, PostScriptValue::Dict(new_dict.clone()));
+                let entries: Vec<(String, PostScriptValue)> = d.borrow().iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                for (key, value) in entries {
+                    let value = value.deep_clone_with(copies);
+                    new_dict.borrow_mut().insert(key, value);
+                }
+                PostScriptValue::Dict(new_dict)
+            }
+            PostScriptValue::Array(arr) => {
+                let addr = Rc::as_ptr(arr) as usize;
+                if let Some(copy) = copies.get(&addr) {
+                    return copy.clone();
+                }
+                // Register the new array before copying its elements so that
+                // elements referring back to it resolve to the copy
+                let new_array = Rc::new(RefCell::new(Vec::new()));
+                copies.insert(addr, PostScriptValue::Array(new_array.clone()));
+                let elements = arr.borrow().clone();
+                let elements = elements.iter().map(|v| v.deep_clone_with(copies)).collect();
+                *new_array.borrow_mut() = elements;
+                PostScriptValue::Array(new_array)
+            }
+            PostScriptValue::Block(arr) => {
+                PostScriptValue::Block(arr.iter().map(|v| v.deep_clone_with(copies)).collect())
+            }
+            PostScriptValue::Closure { body, env } => PostScriptValue::Closure {
+                body: body.iter().map(|v| v.deep_clone_with(copies)).collect(),
+                env: env.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
 impl fmt::Display for PostScriptValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_display(f, &mut Vec::new())
+    }
+}
+
+impl PostScriptValue {
+    /// Writes the `Display` text of the value; arrays holds the arrays being
+    /// rendered, so that an array inside itself prints as `-array-`.
+    fn write_display(&self, f: &mut fmt::Formatter<'_>, arrays: &mut Vec<usize>) -> fmt::Result {
         match self {
             PostScriptValue::Int(i) => write!(f, "{}", i),
             PostScriptValue::Real(r) => write!(f, "{}", r),
             PostScriptValue::Bool(b) => write!(f, "{}", b),
-            PostScriptValue::String(s) => write!(f, "({})", s.borrow()),
+            PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => write!(f, "({})", s.borrow()),
             PostScriptValue::Name(n) => write!(f, "{}", n),
             PostScriptValue::LiteralName(n) => write!(f, "/{}", n),
             PostScriptValue::Array(arr) => {
+                let address = Rc::as_ptr(arr) as usize;
+                if arrays.contains(&address) {
+                    return write!(f, "-array-");
+                }
+                arrays.push(address);
                 write!(f, "[")?;
-                for (i, v) in arr.iter().enumerate() {
+                for (i, v) in arr.borrow().iter().enumerate() {
                     if i > 0 { write!(f, " ")?; }
-                    write!(f, "{}", v)?;
+                    v.write_display(f, arrays)?;
                 }
+                arrays.pop();
                 write!(f, "]")
             }
             PostScriptValue::Dict(_) => write!(f, "--nostringval--"),
             PostScriptValue::Mark => write!(f, "--mark--"),
+            PostScriptValue::Null => write!(f, "null"),
+            PostScriptValue::Save(_) => write!(f, "--save--"),
+            PostScriptValue::File(_) | PostScriptValue::ExecutableFile(_) => write!(f, "--file--"),
             PostScriptValue::NativeFn(_) => write!(f, "--native-function--"),
             PostScriptValue::Block(arr) => {
                 write!(f, "{{")?;
                 for (i, v) in arr.iter().enumerate() {
                     if i > 0 { write!(f, " ")?; }
-                    write!(f, "{}", v)?;
+                    v.write_display(f, arrays)?;
                 }
                 write!(f, "}}")
             }
-            PostScriptValue::ForLoop { .. } => write!(f, "--for-loop--"),
+            PostScriptValue::ForLoop { .. } | PostScriptValue::IntForLoop { .. } => write!(f, "--for-loop--"),
             PostScriptValue::RepeatLoop { .. } => write!(f, "--repeat-loop--"),
+            PostScriptValue::Loop { .. } => write!(f, "--loop--"),
+            PostScriptValue::ForAllLoop { .. } => write!(f, "--forall-loop--"),
+            PostScriptValue::SortLoop(_) => write!(f, "--sort-loop--"),
+            PostScriptValue::StopContext => write!(f, "--stopped--"),
+            PostScriptValue::Executive { .. } => write!(f, "--executive--"),
             PostScriptValue::Closure { .. } => write!(f, "--closure--"),
             PostScriptValue::RestoreEnv(_) => write!(f, "--restore-env--"),
         }
     }
 }
 
-/// The complete interpreter state.
+/// State of a bottom-up merge sort whose comparisons are made by a PostScript procedure.
 ///
-/// This structure holds all the runtime state needed to execute PostScript code:
-/// - Operand stack: Where values are pushed/popped during computation
-/// - Dictionary stack: Hierarchical namespace for variable lookup
-/// - Execution stack: Queue of values waiting to be executed
-/// - Scoping mode: Determines how closures capture their environment
+/// Runs of `width` elements of `items` are merged pairwise into `merged`; once a pass
+/// is complete `merged` becomes `items` and the width doubles. `left` and `right` are
+/// the heads of the two runs currently being merged, starting at `start`. The sort is
+/// stable: the right element is only taken first when the comparator says it must
+/// precede the left one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeSort {
+    /// Elements as of the current pass
+    pub items: Vec<PostScriptValue>,
+    /// Output of the current pass so far
+    pub merged: Vec<PostScriptValue>,
+    /// Length of the sorted runs being merged in this pass
+    pub width: usize,
+    /// Index of the first element of the pair of runs being merged
+    pub start: usize,
+    /// Next unmerged element of the left run
+    pub left: usize,
+    /// Next unmerged element of the right run
+    pub right: usize,
+    /// Comparator: `a b proc` must leave true if a has to come before b
+    pub proc: PostScriptValue,
+    /// The array being sorted, which receives the sorted elements at the end (see
+    /// `PostScriptValue::with_elements`)
+    pub original: PostScriptValue,
+    /// True while the comparator's result is waiting on the operand stack
+    pub pending: bool,
+}
+
+impl MergeSort {
+    /// Starts sorting the elements of `original` with the comparator `proc`.
+    pub fn new(items: Vec<PostScriptValue>, proc: PostScriptValue, original: PostScriptValue) -> Self {
+        let right = items.len().min(1);
+        MergeSort { items, merged: Vec::new(), width: 1, start: 0, left: 0, right, proc, original, pending: false }
+    }
+
+    /// Takes the next element from the left or right run, given the comparator's answer.
+    pub fn take(&mut self, right_first: bool) {
+        if right_first {
+            self.merged.push(self.items[self.right].clone());
+            self.right += 1;
+        } else {
+            self.merged.push(self.items[self.left].clone());
+            self.left += 1;
+        }
+    }
+
+    /// Advances the sort until a comparison is needed.
+    ///
+    /// Returns the pair to compare (right element, left element), or None when the
+    /// sort is finished and `items` holds the sorted elements.
+    pub fn next_comparison(&mut self) -> Option<(PostScriptValue, PostScriptValue)> {
+        let n = self.items.len();
+        while self.width < n {
+            let mid = (self.start + self.width).min(n);
+            let end = (self.start + 2 * self.width).min(n);
+            if self.left < mid && self.right < end {
+                return Some((self.items[self.right].clone(), self.items[self.left].clone()));
+            }
+            // One run is exhausted: the rest of the other one is already in order
+            self.merged.extend_from_slice(&self.items[self.left..mid]);
+            self.merged.extend_from_slice(&self.items[self.right..end]);
+            self.start = end;
+            if self.start >= n {
+                self.items = std::mem::take(&mut self.merged);
+                self.width *= 2;
+                self.start = 0;
+            }
+            self.left = self.start;
+            self.right = (self.start + self.width).min(n);
+        }
+        None
+    }
+}
+
+/// Selects between exact PLRM semantics and the interpreter's relaxed defaults.
 ///
-/// # Communication with Other Modules
+/// Every behavior that differs between the modes consults `Context::is_strict`,
+/// so the list below is the complete set of deviations, together with the integer
+/// edge cases both modes treat the same way:
 ///
-/// - **parser**: Creates PostScriptValue objects that get pushed to execution_stack
-/// - **interpreter**: Pops from execution_stack, manipulates operand_stack and dict_stack
-/// - **commands**: Built-in functions receive &mut Context to manipulate all stacks
-pub struct Context {
-    /// Operand stack - holds values during computation
-    /// Commands pop arguments from here and push results back
-    pub operand_stack: Vec<PostScriptValue>,
-    
-    /// Dictionary stack - hierarchical namespace for variable lookup
-    /// Each dictionary is wrapped in Rc<RefCell<>> for shared mutable access
-    /// Lookup searches from top to bottom (most recent to oldest)
-    /// The bottom dictionary is the system dictionary with built-in commands
-    pub dict_stack: Vec<Rc<RefCell<HashMap<String, PostScriptValue>>>>,
-    
-    /// Execution stack - holds values waiting to be executed
-    /// The interpreter pops from this stack and executes each value
-    /// Procedures and loops push their contents here for execution
-    pub execution_stack: Vec<PostScriptValue>,
-    
-    /// Scoping mode flag
-    /// - false: Dynamic scoping (variables resolved in calling context)
-    /// - true: Lexical scoping (variables resolved in defining context)
-    pub lexical_scoping: bool,
+/// - `div` by zero: Relaxed pushes infinity (or NaN), Strict raises undefinedresult
+/// - `sqrt` of a negative number: Relaxed pushes NaN, Strict raises rangecheck
+/// - `ceiling` and `floor` of an integer: Relaxed pushes a real, Strict keeps the integer
+/// - Adding a key to a dictionary at its capacity: Relaxed grows the dictionary from
+///   language level 2 on, Strict raises dictfull at every level
+/// - Non-standard extension operators (e.g., `deepcopy`): registered only in Relaxed mode
+/// - Integer overflow in `add`, `sub`, `mul`, `neg`, `abs` and `idiv`: both modes push
+///   the result as a real, as the PLRM specifies; `idiv` and `mod` by zero raise
+///   undefinedresult in both modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompatibilityMode {
+    /// Exact PLRM semantics
+    Strict,
+    /// Interpreter defaults, including the documented deviations
+    #[default]
+    Relaxed,
 }
 
-impl Context {
-    /// Creates a new Context with the specified scoping mode.
-    ///
-    /// Initializes:
-    /// - Empty operand stack
-    /// - Dictionary stack with one system dictionary (for built-in commands)
-    /// - Empty execution stack
-    pub fn new(lexical_scoping: bool) -> Self {
-        let system_dict = Rc::new(RefCell::new(HashMap::new()));
-        Context {
-            operand_stack: Vec::new(),
-            dict_stack: vec![system_dict],
-            execution_stack: Vec::new(),
-            lexical_scoping,
+/// Resource limits enforced by the interpreter.
+///
+/// The stack limits are checked by the interpreter after every executed value. The
+/// object size limits are checked by the operators that create strings, arrays and
+/// dictionaries, before allocating, so a huge size request fails with a limit check
+/// error instead of exhausting memory.
+///
+/// The limits can be read and adjusted from PostScript with `currentsystemparams`
+/// and `setsystemparams`, or from Rust through `Context::limits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// Maximum number of values on the operand stack (system parameter MaxOpStack)
+    pub max_operand_stack: usize,
+
+    /// Maximum number of values on the execution stack (system parameter MaxExecStack)
+    pub max_exec_stack: usize,
+
+    /// Maximum number of dictionaries on the dictionary stack (system parameter MaxDictStack)
+    pub max_dict_stack: usize,
+
+    /// Maximum length of a single string, in characters (system parameter MaxStringLength)
+    pub max_string_length: usize,
+
+    /// Maximum number of elements in a single array (system parameter MaxArrayLength)
+    pub max_array_length: usize,
+
+    /// Maximum capacity requested for a single dictionary (system parameter MaxDictLength)
+    pub max_dict_length: usize,
+
+    /// Maximum estimated memory of the strings, arrays and dictionaries a Context
+    /// allocates, in bytes (system parameter MaxLocalVM; see `Context::allocate`)
+    pub max_vm: usize,
+
+    /// Maximum depth of procedures nested in source text, checked by the parser
+    /// (deeper nesting is a syntax error rather than a native stack overflow).
+    /// Arrays and dictionaries are built by `]` and `>>` as they execute, so their
+    /// nesting is bounded by the operand stack instead
+    pub max_nesting_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_operand_stack: 100_000,
+            max_exec_stack: 100_000,
+            max_dict_stack: 1_000,
+            max_string_length: 64 * 1024 * 1024,
+            max_array_length: 1024 * 1024,
+            max_dict_length: 1024 * 1024,
+            max_vm: 1024 * 1024 * 1024,
+            max_nesting_depth: 1_000,
         }
     }
+}
 
-    /// Pushes a value onto the operand stack.
-    pub fn push(&mut self, val: PostScriptValue) {
-        self.operand_stack.push(val);
+/// A composite object being created, for memory accounting (see `Context::allocate`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Allocation {
+    /// A string of this many characters
+    String(usize),
+    /// An array or procedure of this many elements
+    Array(usize),
+    /// A dictionary of this capacity
+    Dict(usize),
+}
+
+impl Allocation {
+    /// Rough number of bytes the object takes: one per character, and the size of
+    /// a value (and of a key, for dictionaries) per element.
+    pub fn bytes(self) -> usize {
+        let value = std::mem::size_of::<PostScriptValue>();
+        match self {
+            Allocation::String(n) => n,
+            Allocation::Array(n) => n.saturating_mul(value),
+            Allocation::Dict(n) => n.saturating_mul(value + std::mem::size_of::<String>()),
+        }
     }
+}
 
-    /// Pops a value from the operand stack.
-    /// Returns None if the stack is empty.
-    pub fn pop(&mut self) -> Option<PostScriptValue> {
-        self.operand_stack.pop()
+/// Counters the interpreter maintains while executing.
+///
+/// They accumulate across `execute` calls; reset them by assigning
+/// `ExecutionStats::default()` to `Context::stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionStats {
+    /// Number of values executed (each one also consumes one unit of fuel)
+    pub executed: u64,
+    /// Deepest the operand stack has been
+    pub peak_operand_stack: usize,
+    /// Deepest the execution stack has been
+    pub peak_exec_stack: usize,
+}
+
+/// Pseudo-random number generator behind `rand`, `srand` and `rrand`.
+///
+/// A Park-Miller "minimal standard" generator: the state is a number from 1 to
+/// 2^31 - 2, and each step multiplies it by 16807 modulo 2^31 - 1. It is fully
+/// deterministic, so a script (or an embedder) that sets the seed gets the same
+/// sequence on every run, and `state` can be saved and restored with `seed` to
+/// replay a sequence.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::types::RandomGenerator;
+///
+/// let mut generator = RandomGenerator::new(42);
+/// let checkpoint = generator.state();
+/// let first: Vec<i64> = (0..3).map(|_| generator.next_value()).collect();
+/// generator.seed(checkpoint);
+/// let again: Vec<i64> = (0..3).map(|_| generator.next_value()).collect();
+/// assert_eq!(first, again);
+/// assert!(first.iter().all(|&n| (0..1 << 31).contains(&n)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomGenerator {
+    state: i64,
+}
+
+impl RandomGenerator {
+    const MODULUS: i64 = (1 << 31) - 1;
+    const MULTIPLIER: i64 = 16807;
+
+    /// Creates a generator with the given seed (see `seed`).
+    pub fn new(seed: i64) -> Self {
+        let mut generator = RandomGenerator { state: 1 };
+        generator.seed(seed);
+        generator
     }
 
-    /// Peeks at the top value on the operand stack without removing it.
-    /// Returns None if the stack is empty.
-    pub fn peek(&self) -> Option<&PostScriptValue> {
-        self.operand_stack.last()
+    /// Restarts the sequence from a seed. Any integer is accepted: it is reduced
+    /// modulo 2^31 - 1, and 0 (which would repeat forever) is replaced by 1.
+    pub fn seed(&mut self, seed: i64) {
+        self.state = match seed.rem_euclid(Self::MODULUS) {
+            0 => 1,
+            state => state,
+        };
     }
-    
-    /// Defines a key-value pair in the current (topmost) dictionary.
-    ///
-    /// Used by the `def` command to create or update variables.
-    /// The definition goes into the dictionary at the top of the dict_stack.
-    pub fn define(&mut self, key: String, value: PostScriptValue) {
-        if let Some(dict) = self.dict_stack.last() {
-            dict.borrow_mut().insert(key, value);
+
+    /// The current state; passing it to `seed` resumes the sequence from here.
+    pub fn state(&self) -> i64 {
+        self.state
+    }
+
+    /// Advances the generator and returns the new state, a number from 1 to 2^31 - 2.
+    pub fn next_value(&mut self) -> i64 {
+        self.state = self.state * Self::MULTIPLIER % Self::MODULUS;
+        self.state
+    }
+}
+
+impl Default for RandomGenerator {
+    fn default() -> Self {
+        RandomGenerator::new(1)
+    }
+}
+
+/// Access attribute of a dictionary, array or string, reduced by `readonly`,
+/// `executeonly` and `noaccess` and tested by `rcheck` and `wcheck`.
+///
+/// The attribute belongs to the object, as the PLRM specifies for dictionaries:
+/// making an array read-only affects every reference to it. The variants are
+/// ordered from most to least restrictive, and an object's access is never
+/// increased (see `Context::restrict_access`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Access {
+    /// Cannot be read, written or executed
+    NoAccess,
+    /// Can only be executed
+    ExecuteOnly,
+    /// Can be read and executed but not changed
+    ReadOnly,
+    /// No restrictions (the access of every new object)
+    #[default]
+    Unlimited,
+}
+
+impl Access {
+    /// Name of the attribute as used in PostScript.
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::NoAccess => "noaccess",
+            Access::ExecuteOnly => "executeonly",
+            Access::ReadOnly => "readonly",
+            Access::Unlimited => "unlimited",
         }
     }
+}
 
-    /// Looks up a name in the dictionary stack.
-    ///
-    /// Searches from top to bottom (most recent to oldest dictionary).
-    /// Returns the first matching value found, or None if not found.
-    ///
-    /// This implements PostScript's hierarchical namespace:
-    /// - Local definitions (in top dictionaries) shadow global ones
-    /// - Built-in commands (in system dictionary at bottom) are always available
-    pub fn lookup(&self, key: &str) -> Option<PostScriptValue> {
-        for dict in self.dict_stack.iter().rev() {
-            if let Some(val) = dict.borrow().get(key) {
-                return Some(val.clone());
+/// Address of the cell of a dictionary, array or string, which identifies the object.
+fn cell_address(value: &PostScriptValue) -> Option<usize> {
+    match value {
+        PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => Some(Rc::as_ptr(s) as usize),
+        PostScriptValue::Array(arr) => Some(Rc::as_ptr(arr) as usize),
+        PostScriptValue::Dict(dict) => Some(Rc::as_ptr(dict) as usize),
+        _ => None,
+    }
+}
+
+/// Snapshot of the composite objects reachable from a Context, taken by `save`.
+///
+/// PostScript objects live in `Rc<RefCell<...>>` cells, so the snapshot records
+/// every dictionary, array and string cell reachable from the operand, dictionary
+/// and execution stacks together with a copy of its contents. `restore` writes
+/// the copies back into the same cells, so every outstanding reference sees the
+/// rollback. Objects created after the snapshot are not part of it and keep
+/// their contents.
+///
+/// The global dictionary and everything reachable from it stand for global VM:
+/// they are skipped, so `restore` never rolls them back.
+///
+/// Two snapshots are equal only if they are the same snapshot.
+#[derive(Debug)]
+pub struct SaveState {
+    dicts: Snapshot<HashMap<String, PostScriptValue>>,
+    arrays: Snapshot<Vec<PostScriptValue>>,
+    strings: Snapshot<String>,
+    /// Addresses of all recorded cells
+    cells: HashSet<usize>,
+    /// Memory in use when the snapshot was taken (see `Context::vm_used`)
+    vm_used: usize,
+}
+
+/// Recorded cells of one kind, each with a copy of its contents.
+type Snapshot<T> = Vec<(Rc<RefCell<T>>, T)>;
+
+impl SaveState {
+    /// Records every cell reachable from the given values and dictionaries, except
+    /// those reachable from the global dictionary.
+    fn capture<'a>(
+        values: impl Iterator<Item = &'a PostScriptValue>,
+        dicts: &[Rc<RefCell<HashMap<String, PostScriptValue>>>],
+        global_dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>,
+    ) -> Self {
+        let mut state = SaveState { cells: SaveState::global_cells(global_dict), ..SaveState::empty() };
+        for value in values {
+            state.visit(value);
+        }
+        for dict in dicts {
+            state.visit_dict(dict);
+        }
+        state
+    }
+
+    fn empty() -> Self {
+        SaveState { dicts: Vec::new(), arrays: Vec::new(), strings: Vec::new(), cells: HashSet::new(), vm_used: 0 }
+    }
+
+    /// Addresses of the cells reachable from the global dictionary.
+    fn global_cells(global_dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>) -> HashSet<usize> {
+        let mut global = SaveState::empty();
+        global.visit_dict(global_dict);
+        global.cells
+    }
+
+    /// Marks a cell as recorded; returns false if it already was.
+    fn first_visit<T>(&mut self, cell: &Rc<RefCell<T>>) -> bool {
+        self.cells.insert(Rc::as_ptr(cell) as usize)
+    }
+
+    /// Records the cells of a value and, recursively, of its contents.
+    fn visit(&mut self, value: &PostScriptValue) {
+        match value {
+            PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) if self.first_visit(s) => {
+                self.strings.push((s.clone(), s.borrow().clone()));
             }
+            PostScriptValue::Array(arr) if self.first_visit(arr) => {
+                let elements = arr.borrow().clone();
+                for element in &elements {
+                    self.visit(element);
+                }
+                self.arrays.push((arr.clone(), elements));
+            }
+            PostScriptValue::Dict(dict) => self.visit_dict(dict),
+            PostScriptValue::Block(items) => items.iter().for_each(|v| self.visit(v)),
+            PostScriptValue::Closure { body, env } => {
+                body.iter().for_each(|v| self.visit(v));
+                env.iter().for_each(|d| self.visit_dict(d));
+            }
+            PostScriptValue::RestoreEnv(env) => env.iter().for_each(|d| self.visit_dict(d)),
+            PostScriptValue::ForLoop { proc, .. }
+            | PostScriptValue::IntForLoop { proc, .. }
+            | PostScriptValue::RepeatLoop { proc, .. }
+            | PostScriptValue::Loop { proc } => self.visit(proc),
+            PostScriptValue::ForAllLoop { items, proc, .. } => {
+                items.iter().for_each(|v| self.visit(v));
+                self.visit(proc);
+            }
+            PostScriptValue::SortLoop(state) => {
+                state.items.iter().chain(&state.merged).for_each(|v| self.visit(v));
+                self.visit(&state.proc);
+                self.visit(&state.original);
+            }
+            _ => {}
         }
-        None
+    }
+
+    /// Records a dictionary cell and, recursively, its values.
+    fn visit_dict(&mut self, dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>) {
+        if self.first_visit(dict) {
+            let entries = dict.borrow().clone();
+            for value in entries.values() {
+                self.visit(value);
+            }
+            self.dicts.push((dict.clone(), entries));
+        }
+    }
+
+    /// Returns true unless the value is a composite object the snapshot does not know,
+    /// i.e. one created after it was taken.
+    fn contains(&self, value: &PostScriptValue) -> bool {
+        cell_address(value).is_none_or(|addr| self.cells.contains(&addr))
+    }
+
+    /// Writes the recorded contents back into their cells.
+    fn write_back(&self) {
+        write_back_cells(&self.dicts);
+        write_back_cells(&self.arrays);
+        write_back_cells(&self.strings);
+    }
+}
+
+/// Writes recorded contents back into their cells. Cells whose contents did not
+/// change are left alone, so shared dictionaries (see `SharedSystemDict`) are not
+/// written to.
+fn write_back_cells<T: Clone + SameContents>(cells: &Snapshot<T>) {
+    for (cell, contents) in cells {
+        if !cell.borrow().same_contents(contents) {
+            *cell.borrow_mut() = contents.clone();
+        }
+    }
+}
+
+/// Comparison of a cell's contents with a recorded copy that does not look inside
+/// the composite objects they hold, so it ends on an array or dictionary that
+/// contains itself.
+trait SameContents {
+    fn same_contents(&self, other: &Self) -> bool;
+}
+
+impl SameContents for String {
+    fn same_contents(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl SameContents for Vec<PostScriptValue> {
+    fn same_contents(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| same_object(a, b))
+    }
+}
+
+impl SameContents for HashMap<String, PostScriptValue> {
+    fn same_contents(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, a)| other.get(key).is_some_and(|b| same_object(a, b)))
+    }
+}
+
+/// True if two values are the same object: composites with a cell are compared by
+/// identity, procedures element by element, and simple objects by value. Anything
+/// else counts as different, which only costs a redundant write-back.
+fn same_object(a: &PostScriptValue, b: &PostScriptValue) -> bool {
+    use PostScriptValue::*;
+    match (a, b) {
+        (Block(x), Block(y)) => x.same_contents(y),
+        (Int(_) | Real(_) | Bool(_) | Name(_) | LiteralName(_) | Null | Mark | NativeFn(_), _) => a == b,
+        _ => cell_address(a).is_some() && cell_address(a) == cell_address(b) && std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+impl PartialEq for SaveState {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// The bytes of a string object: characters up to 255 are single bytes (as
+/// Latin-1, the way string literals and readstring store them), others are UTF-8.
+///
+/// This is how strings are written to files and the output, and scanned when
+/// executed.
+pub fn latin1_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match u8::try_from(c) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Where a file object reads from or writes to.
+pub enum FileStream {
+    /// The Context's input (`%stdin`)
+    Stdin,
+    /// The Context's output (`%stdout`)
+    Stdout,
+    /// The Context's error output (`%stderr`)
+    Stderr,
+    /// A file opened for reading
+    Reader(Box<dyn BufRead>),
+    /// A file opened for writing
+    Writer(Box<dyn Write>),
+    /// A file being executed, scanned object by object (see `PostScriptFile::next_object`)
+    Source(Tokenizer),
+    /// A decode filter made by `filter`, reading from another file
+    Decode(Box<DecodeFilter>),
+    /// An encode filter made by `filter`, writing into another file or a string
+    Encode(Box<EncodeFilter>),
+    /// A file closed with `closefile`
+    Closed,
+}
+
+/// An open file, shared by every copy of a file object (see `PostScriptValue::File`).
+///
+/// The standard streams are not owned by the file: `%stdin` and `%stdout` read and
+/// write the Context's `input` and `output`, so they follow any redirection.
+///
+/// Two file objects are equal only if they refer to the same open file.
+pub struct PostScriptFile {
+    /// Name the file was opened with
+    pub name: String,
+    /// The stream the file reads or writes
+    pub stream: FileStream,
+}
+
+impl PartialEq for PostScriptFile {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PostScriptFile {
+    /// Reads the next object of the file for executing it, or None at its end.
+    ///
+    /// The first call hands a file opened for reading over to a scanner, which reads
+    /// the rest of it as it scans; a closed file has no more objects.
+    /// Standard streams and files opened for writing cannot be executed.
+    /// Immediately evaluated names are looked up in context as they are scanned.
+    pub fn next_object(&mut self, context: &Context) -> Result<Option<PostScriptValue>, String> {
+        match &mut self.stream {
+            FileStream::Source(tokenizer) => Ok(tokenizer.next_object_with_context(context)?),
+            FileStream::Reader(_) => {
+                let FileStream::Reader(reader) = std::mem::replace(&mut self.stream, FileStream::Closed) else {
+                    unreachable!();
+                };
+                self.stream = FileStream::Source(Tokenizer::from_reader_with_language_level(reader, context.language_level));
+                self.next_object(context)
+            }
+            FileStream::Closed => Ok(None),
+            _ => Err(format!("Invalid file access: {} cannot be executed", self.name)),
+        }
+    }
+}
+
+impl fmt::Debug for PostScriptFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.stream {
+            FileStream::Stdin => "stdin",
+            FileStream::Stdout => "stdout",
+            FileStream::Stderr => "stderr",
+            FileStream::Reader(_) => "reading",
+            FileStream::Writer(_) => "writing",
+            FileStream::Source(_) => "executing",
+            FileStream::Decode(_) => "decoding",
+            FileStream::Encode(_) => "encoding",
+            FileStream::Closed => "closed",
+        };
+        write!(f, "PostScriptFile({:?}, {})", self.name, state)
+    }
+}
+
+/// Source of time for the `usertime` and `realtime` operators, installed with
+/// `Context::clock`.
+///
+/// The default is `SystemClock`. Tests and embedders can install their own to make
+/// timings deterministic.
+///
+/// # Example
+///
+/// A clock that advances one millisecond per executed value, driven by the progress
+/// callback, makes `usertime` count the values executed between two calls:
+///
+/// ```rust
+/// use postscript_interpreter::types::{Clock, Context, PostScriptValue, ProgressReporter};
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::parser::{Tokenizer, parse};
+/// use postscript_interpreter::commands::register_builtins;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// struct TickClock(Rc<Cell<i64>>);
+///
+/// impl Clock for TickClock {
+///     fn usertime(&self) -> i64 { self.0.get() }
+///     fn realtime(&self) -> i64 { 1_000_000 + self.0.get() }
+/// }
+///
+/// let ticks = Rc::new(Cell::new(0));
+/// let counter = ticks.clone();
+/// let mut context = Context::new(false);
+/// register_builtins(&mut context);
+/// context.clock = Box::new(TickClock(ticks));
+/// context.progress = Some(ProgressReporter {
+///     interval: 1,
+///     callback: Box::new(move |_, _| counter.set(counter.get() + 1)),
+/// });
+/// let mut interpreter = Interpreter::new(context);
+///
+/// // Every iteration of the loop executes three values: the loop's own state and
+/// // the procedure's `1 pop`. Ten more iterations take thirty more milliseconds
+/// let mut elapsed = |count: i64| {
+///     let program = format!("usertime {} {{ 1 pop }} repeat usertime exch sub", count);
+///     let values = parse(Tokenizer::new(&program).tokenize().unwrap()).unwrap();
+///     interpreter.execute(values).unwrap();
+///     match interpreter.get_context_mut().pop() {
+///         Some(PostScriptValue::Int(ms)) => ms,
+///         other => panic!("expected an integer, got {:?}", other),
+///     }
+/// };
+/// let short = elapsed(10);
+/// let long = elapsed(20);
+/// assert_eq!(long - short, 30);
+/// ```
+pub trait Clock {
+    /// Milliseconds of execution time, for `usertime`
+    fn usertime(&self) -> i64;
+    /// Wall-clock milliseconds since an arbitrary fixed point, for `realtime`
+    fn realtime(&self) -> i64;
+}
+
+/// The default `Clock`: `usertime` counts from the clock's creation (which happens
+/// in `Context::new`) using `Instant`, and `realtime` is the system time since the
+/// Unix epoch.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Creates a clock whose `usertime` starts at 0 now.
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn usertime(&self) -> i64 {
+        self.start.elapsed().as_millis() as i64
+    }
+
+    fn realtime(&self) -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+    }
+}
+
+/// Source of environment variables for `getenv`, installed with
+/// `Context::set_env_provider`: returns the value of a variable, or None if it is
+/// not set.
+pub type EnvProvider = Box<dyn Fn(&str) -> Option<String>>;
+
+/// Periodic progress callback, installed with `Context::progress`.
+///
+/// Every `interval` executed values (counted by `ExecutionStats::executed`), the
+/// interpreter calls `callback` with the number of values executed so far and the
+/// current operand stack depth. An interval of 0 disables the callback.
+pub struct ProgressReporter {
+    /// Number of executed values between two calls
+    pub interval: u64,
+    /// Called with (values executed, operand stack depth)
+    pub callback: Box<dyn FnMut(u64, usize)>,
+}
+
+/// One executed value recorded by the flight recorder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Short text form of the executed value (see `FlightRecorder`)
+    pub value: String,
+    /// Operand stack depth just before the value was executed
+    pub depth: usize,
+    /// Short text forms of the topmost operands at that point, bottom first
+    pub top: Vec<String>,
+}
+
+/// Bounded ring buffer of the most recently executed values, for post-mortem debugging.
+///
+/// Install one with `Context::flight_recorder` and read it back with
+/// `Interpreter::last_trace` after an error. Each entry stores text summaries rather
+/// than the values themselves, so recording never copies large composites: procedures
+/// and arrays longer than a few elements and long strings are abbreviated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightRecorder {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl FlightRecorder {
+    /// Number of topmost operands captured with each entry
+    pub const TOP_OPERANDS: usize = 3;
+
+    /// Creates a recorder that keeps the last `capacity` executed values.
+    pub fn new(capacity: usize) -> Self {
+        FlightRecorder { capacity, entries: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Records a value about to be executed, dropping the oldest entry when full.
+    pub fn record(&mut self, value: &PostScriptValue, operand_stack: &[PostScriptValue]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        let first = operand_stack.len().saturating_sub(Self::TOP_OPERANDS);
+        self.entries.push_back(TraceEntry {
+            value: summarize(value),
+            depth: operand_stack.len(),
+            top: operand_stack[first..].iter().map(summarize).collect(),
+        });
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The recorded entries, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+}
+
+/// Text form of a value for the flight recorder, abbreviated when it would be long.
+fn summarize(value: &PostScriptValue) -> String {
+    summarize_with(value, &mut Vec::new())
+}
+
+/// `summarize` with the arrays being summarized in arrays, so that an array inside
+/// itself is written `-array-`.
+fn summarize_with(value: &PostScriptValue, arrays: &mut Vec<usize>) -> String {
+    const MAX_ELEMENTS: usize = 8;
+    const MAX_CHARS: usize = 40;
+    let elements = |items: &[PostScriptValue], arrays: &mut Vec<usize>| {
+        items.iter().map(|item| summarize_with(item, arrays)).collect::<Vec<String>>().join(" ")
+    };
+    match value {
+        PostScriptValue::Block(items) if items.len() > MAX_ELEMENTS => {
+            format!("{{--{} elements--}}", items.len())
+        }
+        PostScriptValue::Block(items) => format!("{{{}}}", elements(items, arrays)),
+        PostScriptValue::Array(items) if items.borrow().len() > MAX_ELEMENTS => {
+            format!("[--{} elements--]", items.borrow().len())
+        }
+        PostScriptValue::Array(items) => {
+            let address = Rc::as_ptr(items) as usize;
+            if arrays.contains(&address) {
+                return "-array-".to_string();
+            }
+            arrays.push(address);
+            let text = format!("[{}]", elements(&items.borrow(), arrays));
+            arrays.pop();
+            text
+        }
+        PostScriptValue::String(s) if s.borrow().chars().count() > MAX_CHARS => {
+            let head: String = s.borrow().chars().take(MAX_CHARS).collect();
+            format!("({}...)", head)
+        }
+        other => other.to_string(),
+    }
+}
+
+/// A dictionary stack, bottom first, as held by a Context.
+pub type DictStack = Vec<Rc<RefCell<HashMap<String, PostScriptValue>>>>;
+
+/// Resource categories that exist in every new Context.
+///
+/// `Category` lists the categories themselves: each has an instance there, a
+/// dictionary whose `/Category` entry is its name, and defining a dictionary in
+/// `Category` creates a new category (see `Context::define_resource`).
+pub const RESOURCE_CATEGORIES: &[&str] = &["Font", "ProcSet", "Generic", "Category"];
+
+/// The `Category` resource instance that describes a category.
+fn category_instance(name: &str) -> PostScriptValue {
+    let mut dict = HashMap::new();
+    dict.insert("Category".to_string(), PostScriptValue::LiteralName(name.to_string()));
+    PostScriptValue::Dict(Rc::new(RefCell::new(dict)))
+}
+
+/// A system dictionary built once and shared by many Contexts.
+///
+/// Populating the system dictionary with `register_builtins` for every new Context
+/// is wasted work when a program creates many short-lived interpreters (e.g., one
+/// per request in a server). A `SharedSystemDict` is built from one fully registered
+/// Context and then handed to `Context::with_shared_systemdict`, which only clones
+/// a reference to it. Each Context still gets its own user dictionary, so instances
+/// never see each other's definitions.
+///
+/// The dictionary is reference counted with `Rc`, so it is shared between Contexts
+/// on the same thread.
+///
+/// # Example
+///
+/// ```rust
+/// use postscript_interpreter::types::{Context, PostScriptValue, SharedSystemDict};
+/// use postscript_interpreter::interpreter::Interpreter;
+/// use postscript_interpreter::parser::{Tokenizer, parse};
+/// use postscript_interpreter::commands::register_builtins;
+///
+/// let mut template = Context::new(false);
+/// register_builtins(&mut template);
+/// let shared = SharedSystemDict::from_context(template);
+///
+/// let mut first = Interpreter::new(Context::with_shared_systemdict(false, &shared));
+/// let mut second = Interpreter::new(Context::with_shared_systemdict(false, &shared));
+/// first.execute(parse(Tokenizer::new("/x 42 def /add { mul } def").tokenize().unwrap()).unwrap()).unwrap();
+///
+/// second.call("add", vec![PostScriptValue::Int(3), PostScriptValue::Int(4)]).unwrap();
+/// assert_eq!(second.get_context().peek(), Some(&PostScriptValue::Int(7)));
+/// assert_eq!(second.get_context().lookup("x"), None);
+///
+/// // Extra native commands stay private to the Context that registers them
+/// first.get_context_mut().define_system("answer".to_string(), PostScriptValue::Int(42));
+/// assert_eq!(second.get_context().lookup("answer"), None);
+/// ```
+#[derive(Clone)]
+pub struct SharedSystemDict {
+    dict: Rc<RefCell<HashMap<String, PostScriptValue>>>,
+    access: Access,
+    language_level: u8,
+    compatibility: CompatibilityMode,
+}
+
+impl SharedSystemDict {
+    /// Takes the system dictionary of a Context whose built-in commands are registered.
+    ///
+    /// The Context is consumed so the dictionary can no longer be changed through it.
+    /// Its language level and compatibility mode are recorded and applied to every
+    /// Context built from the shared dictionary, since the registered operators
+    /// depend on them, and so is the dictionary's access (read-only after
+    /// `register_builtins`).
+    pub fn from_context(context: Context) -> Self {
+        SharedSystemDict {
+            access: context.access(&PostScriptValue::Dict(context.system_dict())),
+            dict: context.system_dict(),
+            language_level: context.language_level,
+            compatibility: context.compatibility,
+        }
+    }
+}
+
+/// The complete interpreter state.
+///
+/// This structure holds all the runtime state needed to execute PostScript code:
+/// - Operand stack: Where values are pushed/popped during computation
+/// - Dictionary stack: Hierarchical namespace for variable lookup
+/// - Execution stack: Queue of values waiting to be executed
+/// - Scoping mode: Determines how closures capture their environment
+///
+/// # Communication with Other Modules
+///
+/// - **parser**: Creates PostScriptValue objects that get scheduled on the execution stack
+/// - **interpreter**: Takes values off the execution stack, manipulates the operand and
+///   dictionary stacks
+/// - **commands**: Built-in functions receive &mut Context to manipulate all stacks
+///
+/// # Stack API
+///
+/// The three stacks are private; they are read through `operand_stack`, `dict_stack`
+/// and `execution_stack` (bottom first) and changed only through methods, which
+/// maintain these invariants:
+///
+/// - The dictionary stack always holds the permanent dictionaries (system dictionary
+///   at the bottom, user dictionary on top of them); `end` and `clear_dict_stack`
+///   never pop them, and `begin` respects `Limits::max_dict_stack`
+/// - `replace_dict_stack` only accepts stacks that hold the permanent dictionaries
+///
+/// Embedders that still need the raw vectors can enable the `legacy-fields` feature,
+/// which adds deprecated `*_mut` accessors that bypass these checks.
+pub struct Context {
+    /// Operand stack - holds values during computation
+    /// Commands pop arguments from here and push results back
+    operand_stack: Vec<PostScriptValue>,
+    
+    /// Dictionary stack - hierarchical namespace for variable lookup
+    /// Each dictionary is wrapped in Rc<RefCell<>> for shared mutable access
+    /// Lookup searches from top to bottom (most recent to oldest)
+    /// The bottom dictionary is the system dictionary with built-in commands, and
+    /// the user dictionary that receives top-level definitions sits above it
+    /// (possibly with other permanent dictionaries in between, see `permanent_dicts`)
+    dict_stack: Vec<Rc<RefCell<HashMap<String, PostScriptValue>>>>,
+    
+    /// Execution stack - holds values waiting to be executed
+    /// The interpreter pops from this stack and executes each value
+    /// Procedures and loops push their contents here for execution
+    execution_stack: Vec<PostScriptValue>,
+    
+    /// Scoping mode flag
+    /// - false: Dynamic scoping (variables resolved in calling context)
+    /// - true: Lexical scoping (variables resolved in defining context)
+    pub lexical_scoping: bool,
+
+    /// PostScript language level being emulated (1, 2 or 3)
+    /// Consulted by `register_builtins` to decide which operators to install
+    /// and by the tokenizer to decide which syntax is accepted
+    pub language_level: u8,
+
+    /// Stack depth limits checked by the interpreter and by `begin`
+    pub limits: Limits,
+
+    /// Remaining execution budget (number of values the interpreter may still execute)
+    /// - None: unlimited (the default)
+    /// - Some(n): execution fails once n more values have been executed
+    pub fuel: Option<u64>,
+
+    /// Strict PLRM semantics or relaxed defaults (see `CompatibilityMode`)
+    /// Consulted by `register_builtins`, so it must be set before registration
+    pub compatibility: CompatibilityMode,
+
+    /// Interrupt request flag, polled by the interpreter between executed values
+    /// Setting it (e.g., from a Ctrl+C handler or another thread) aborts the current
+    /// `execute` call with an "Interrupted" error; the flag is cleared when handled
+    pub interrupt: Arc<AtomicBool>,
+
+    /// Output sink for everything scripts print (`print`, `=`, `==`, ...)
+    /// Defaults to buffered standard output, which `flush` (or `Context::flush_output`)
+    /// writes out; embedders and the CLI can replace it with any writer (e.g., a
+    /// buffered file). Use `Context::write_output` to write to it.
+    pub output: Box<dyn Write>,
+
+    /// Sink for what scripts write to `%stderr`
+    /// Defaults to standard error (unbuffered); embedders can replace it, e.g., to
+    /// capture diagnostics apart from `output`
+    pub error_output: Box<dyn Write>,
+
+    /// Input source for `executive`, read a line at a time
+    /// Defaults to standard input; embedders can replace it with any reader (e.g., a
+    /// `Cursor` over a script). The CLI's REPL reads its lines from it too
+    pub input: Box<dyn BufRead>,
+
+    /// Resource registry used by `defineresource`, `findresource` and friends
+    /// Maps a category name (e.g., "Font") to the resources defined in it, by name
+    /// Only categories present here exist; `Context::new` creates those in
+    /// `RESOURCE_CATEGORIES`. Prefer `define_resource`, which keeps `Category` in step
+    pub resources: HashMap<String, HashMap<String, PostScriptValue>>,
+
+    /// Post-mortem trace of the last executed values (see `FlightRecorder`)
+    /// - None: recording disabled (the default)
+    /// - Some(recorder): the interpreter records every value before executing it
+    pub flight_recorder: Option<FlightRecorder>,
+
+    /// Execution counters (see `ExecutionStats`)
+    pub stats: ExecutionStats,
+
+    /// Progress reporting while executing (see `ProgressReporter`)
+    /// - None: no reporting (the default)
+    pub progress: Option<ProgressReporter>,
+
+    /// Generator used by `rand`, `srand` and `rrand` (see `RandomGenerator`)
+    /// Always starts from the same seed; embedders can seed or replace it
+    pub random: RandomGenerator,
+
+    /// Time source of `usertime` and `realtime` (see `Clock`)
+    /// Defaults to a `SystemClock` started when the Context is created
+    pub clock: Box<dyn Clock>,
+
+    /// True while the system dictionary is a `SharedSystemDict` used by other Contexts
+    /// `define_system` copies it before the first change, so sharing is never observable
+    shared_system_dict: bool,
+
+    /// Number of dictionaries at the bottom of the dict_stack that `end` cannot pop
+    permanent_dicts: usize,
+
+    /// Number of dictionaries at the bottom of the dict_stack that `end` cannot pop
+    /// for the time being, when more than the permanent ones (see `set_pinned_dicts`)
+    pinned_dicts: usize,
+
+    /// Snapshots taken by `save` that can still be restored, oldest first
+    saves: Vec<Rc<SaveState>>,
+
+    /// Dictionary pushed by `globaldict`, for definitions shared by all of a job's code
+    /// It is not on the dictionary stack but searched right below the user dictionary,
+    /// and `restore` does not roll it back
+    global_dict: Rc<RefCell<HashMap<String, PostScriptValue>>>,
+
+    /// Whether `setglobal` selected global allocation (see `set_global_allocation`)
+    global_allocation: bool,
+
+    /// Estimated memory taken by the composite objects allocated so far (see `allocate`)
+    vm_used: usize,
+
+    /// Environment variables read by `getenv`, or None if scripts may not read any
+    /// (see `set_env_provider`)
+    env_provider: Option<EnvProvider>,
+
+    /// Capacities requested from `dict`, by cell address (see `set_dict_capacity`)
+    /// The weak reference keeps the address from being reused by a new object
+    dict_capacities: HashMap<usize, (Weak<dyn Any>, usize)>,
+
+    /// Objects whose access was reduced, by cell address (see `restrict_access`)
+    /// The weak reference keeps the address from being reused by a new object
+    access: HashMap<usize, (Weak<dyn Any>, Access)>,
+}
+
+impl Context {
+    /// Creates a new Context with the specified scoping mode.
+    ///
+    /// Initializes:
+    /// - Empty operand stack
+    /// - Dictionary stack with the system dictionary (for built-in commands) and
+    ///   the user dictionary (for the program's own definitions) on top of it
+    /// - Empty execution stack
+    /// - The highest supported language level (see `MAX_LANGUAGE_LEVEL`)
+    /// - Default resource limits and unlimited fuel
+    /// - Relaxed compatibility mode
+    /// - Output going to standard output and input coming from standard input
+    /// - A resource registry with the categories in `RESOURCE_CATEGORIES`, empty but
+    ///   for the instances of `Category`
+    /// - No flight recorder
+    pub fn new(lexical_scoping: bool) -> Self {
+        let system_dict = Rc::new(RefCell::new(HashMap::new()));
+        let user_dict = Rc::new(RefCell::new(HashMap::new()));
+        Context {
+            operand_stack: Vec::new(),
+            dict_stack: vec![system_dict, user_dict],
+            execution_stack: Vec::new(),
+            lexical_scoping,
+            language_level: MAX_LANGUAGE_LEVEL,
+            limits: Limits::default(),
+            fuel: None,
+            compatibility: CompatibilityMode::default(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+            output: Box::new(BufWriter::new(io::stdout())),
+            error_output: Box::new(io::stderr()),
+            input: Box::new(BufReader::new(io::stdin())),
+            resources: RESOURCE_CATEGORIES.iter()
+                .map(|&category| {
+                    let instances = match category {
+                        "Category" => RESOURCE_CATEGORIES.iter()
+                            .map(|&name| (name.to_string(), category_instance(name)))
+                            .collect(),
+                        _ => HashMap::new(),
+                    };
+                    (category.to_string(), instances)
+                })
+                .collect(),
+            flight_recorder: None,
+            stats: ExecutionStats::default(),
+            progress: None,
+            random: RandomGenerator::default(),
+            clock: Box::new(SystemClock::new()),
+            shared_system_dict: false,
+            permanent_dicts: 2,
+            pinned_dicts: 0,
+            saves: Vec::new(),
+            global_dict: Rc::new(RefCell::new(HashMap::new())),
+            global_allocation: false,
+            vm_used: 0,
+            env_provider: Some(Box::new(|name| std::env::var(name).ok())),
+            dict_capacities: HashMap::new(),
+            access: HashMap::new(),
+        }
+    }
+
+    /// Creates a new Context whose system dictionary is a shared, pre-built one.
+    ///
+    /// Equivalent to `Context::new` followed by `register_builtins`, without
+    /// populating a dictionary: the system dictionary is the shared one, and the
+    /// language level and compatibility mode are those it was built with. The user
+    /// dictionary is a fresh one for this Context.
+    ///
+    /// Extra native commands can still be added with `define_system`; the first one
+    /// gives this Context a private copy of the system dictionary.
+    pub fn with_shared_systemdict(lexical_scoping: bool, shared: &SharedSystemDict) -> Self {
+        let mut context = Context::new(lexical_scoping);
+        context.dict_stack[0] = shared.dict.clone();
+        context.language_level = shared.language_level;
+        context.compatibility = shared.compatibility;
+        context.shared_system_dict = true;
+        context.restrict_access(&PostScriptValue::Dict(shared.dict.clone()), shared.access);
+        context
+    }
+
+    /// Returns true if the Context follows strict PLRM semantics.
+    ///
+    /// All code paths that behave differently in strict mode go through this helper.
+    pub fn is_strict(&self) -> bool {
+        self.compatibility == CompatibilityMode::Strict
+    }
+
+    /// Writes text to the output sink.
+    ///
+    /// I/O failures (e.g., a full disk behind a file sink) are reported as errors.
+    pub fn write_output(&mut self, text: &str) -> Result<(), String> {
+        self.write_output_bytes(text.as_bytes())
+    }
+
+    /// Writes bytes to the output sink, such as those of a string object (see
+    /// `latin1_bytes`).
+    pub fn write_output_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.output.write_all(bytes).map_err(|e| format!("I/O error: {}", e))
+    }
+
+    /// Writes out whatever the output sink has buffered.
+    pub fn flush_output(&mut self) -> Result<(), String> {
+        self.output.flush().map_err(|e| format!("I/O error: {}", e))
+    }
+
+    /// Pushes a value onto the operand stack.
+    pub fn push(&mut self, val: PostScriptValue) {
+        self.operand_stack.push(val);
+    }
+
+    /// Pops a value from the operand stack.
+    /// Returns None if the stack is empty.
+    pub fn pop(&mut self) -> Option<PostScriptValue> {
+        self.operand_stack.pop()
+    }
+
+    /// Peeks at the top value on the operand stack without removing it.
+    /// Returns None if the stack is empty.
+    pub fn peek(&self) -> Option<&PostScriptValue> {
+        self.operand_stack.last()
+    }
+
+    /// The operand stack, bottom first.
+    pub fn operand_stack(&self) -> &[PostScriptValue] {
+        &self.operand_stack
+    }
+
+    /// Number of values on the operand stack.
+    pub fn operand_count(&self) -> usize {
+        self.operand_stack.len()
+    }
+
+    /// Exchanges the top two operands.
+    /// Fails with a stack underflow, leaving the stack alone, if there are fewer than two.
+    pub fn swap(&mut self) -> Result<(), String> {
+        let len = self.operand_stack.len();
+        if len < 2 {
+            return Err("Stack underflow".to_string());
+        }
+        self.operand_stack.swap(len - 1, len - 2);
+        Ok(())
+    }
+
+    /// Rotates the top `n` operands by `j` positions, in place.
+    ///
+    /// Positive `j` moves values towards the top (the topmost ones wrap around to
+    /// the bottom of the group), negative `j` away from it; `j` is taken modulo `n`.
+    /// Fails with a range check, leaving the stack alone, if there are fewer than `n`.
+    pub fn roll_operands(&mut self, n: usize, j: i64) -> Result<(), String> {
+        let len = self.operand_stack.len();
+        if n > len {
+            return Err(format!("Range check error: roll count {} exceeds stack depth {}", n, len));
+        }
+        if n > 0 {
+            let shift = j.rem_euclid(n as i64) as usize;
+            self.operand_stack[len - n..].rotate_right(shift);
+        }
+        Ok(())
+    }
+
+    /// Removes the top `n` operands (or all of them, if there are fewer) and
+    /// returns them, bottom first.
+    pub fn take_operands(&mut self, n: usize) -> Vec<PostScriptValue> {
+        let at = self.operand_stack.len().saturating_sub(n);
+        self.operand_stack.split_off(at)
+    }
+
+    /// Removes operands from the top until at most `len` remain.
+    pub fn truncate_operands(&mut self, len: usize) {
+        self.operand_stack.truncate(len);
+    }
+
+    /// Removes all operands.
+    pub fn clear_operands(&mut self) {
+        self.operand_stack.clear();
+    }
+
+    /// Replaces the whole operand stack, returning the previous one.
+    pub fn replace_operand_stack(&mut self, operands: Vec<PostScriptValue>) -> Vec<PostScriptValue> {
+        std::mem::replace(&mut self.operand_stack, operands)
+    }
+
+    /// The execution stack, bottom first (the next value to execute is last).
+    pub fn execution_stack(&self) -> &[PostScriptValue] {
+        &self.execution_stack
+    }
+
+    /// Schedules a value to be executed next.
+    pub fn schedule(&mut self, value: PostScriptValue) {
+        self.execution_stack.push(value);
+    }
+
+    /// Schedules a sequence of values so that the first one is executed first.
+    pub fn schedule_block(&mut self, values: &[PostScriptValue]) {
+        for value in values.iter().rev() {
+            self.execution_stack.push(value.clone());
+        }
+    }
+
+    /// Schedules a procedure for execution.
+    ///
+    /// The contents of a procedure are scheduled; a closure (lexical scoping) runs in
+    /// its captured environment, and the current dictionary stack is restored when its
+    /// body finishes. Any other value is scheduled as is.
+    pub fn schedule_procedure(&mut self, proc: &PostScriptValue) {
+        match proc {
+            PostScriptValue::Block(block) => self.schedule_block(block),
+            PostScriptValue::Closure { body, env } => {
+                self.execution_stack.push(PostScriptValue::RestoreEnv(self.dict_stack.clone()));
+                self.dict_stack = env.clone();
+                self.schedule_block(body);
+            }
+            _ => self.execution_stack.push(proc.clone()),
+        }
+    }
+
+    /// Takes the next value to execute off the execution stack.
+    pub fn next_scheduled(&mut self) -> Option<PostScriptValue> {
+        self.execution_stack.pop()
+    }
+
+    /// Records a value about to be executed in the flight recorder, if one is attached.
+    pub fn record_trace(&mut self, value: &PostScriptValue) {
+        if let Some(recorder) = self.flight_recorder.as_mut() {
+            recorder.record(value, &self.operand_stack);
+        }
+    }
+
+    /// Removes the top `n` scheduled values (or all of them if there are fewer),
+    /// returning them bottom first.
+    pub fn take_scheduled(&mut self, n: usize) -> Vec<PostScriptValue> {
+        let at = self.execution_stack.len().saturating_sub(n);
+        self.execution_stack.split_off(at)
+    }
+
+    /// Removes all scheduled values, returning them (bottom first).
+    pub fn clear_execution_stack(&mut self) -> Vec<PostScriptValue> {
+        std::mem::take(&mut self.execution_stack)
+    }
+    
+    /// Defines a key-value pair in the current (topmost) dictionary.
+    ///
+    /// Used by the `def` command to create or update variables.
+    /// The definition goes into the dictionary at the top of the dict_stack, or into
+    /// the global dictionary if that is the user dictionary and global allocation is
+    /// selected (see `set_global_allocation`).
+    pub fn define(&mut self, key: String, value: PostScriptValue) {
+        if self.global_allocation && self.dict_stack.len() == self.permanent_dicts {
+            self.global_dict.borrow_mut().insert(key, value);
+        } else if let Some(dict) = self.dict_stack.last() {
+            dict.borrow_mut().insert(key, value);
+        }
+    }
+
+    /// Defines a key-value pair in the system dictionary (bottom of the dict_stack).
+    ///
+    /// Used by `register_builtins`; embedders can use it for extra native commands
+    /// that should survive `Interpreter::clear_user_definitions`.
+    /// If the system dictionary is shared, this Context switches to a private copy first.
+    pub fn define_system(&mut self, key: String, value: PostScriptValue) {
+        if self.shared_system_dict {
+            let shared = PostScriptValue::Dict(self.dict_stack[0].clone());
+            let private = self.dict_stack[0].borrow().clone();
+            self.dict_stack[0] = Rc::new(RefCell::new(private));
+            self.shared_system_dict = false;
+            self.restrict_access(&PostScriptValue::Dict(self.system_dict()), self.access(&shared));
+        }
+        self.dict_stack[0].borrow_mut().insert(key, value);
+    }
+
+    /// Number of dictionaries at the bottom of the dict_stack that `end` cannot pop.
+    ///
+    /// These are the system dictionary, any dictionaries added with
+    /// `insert_permanent_dict` (such as the standard library's), and the user
+    /// dictionary, which is always the topmost of them.
+    pub fn permanent_dicts(&self) -> usize {
+        self.permanent_dicts
+    }
+
+    /// The dictionary stack, bottom first.
+    pub fn dict_stack(&self) -> &[Rc<RefCell<HashMap<String, PostScriptValue>>>] {
+        &self.dict_stack
+    }
+
+    /// Number of dictionaries on the dictionary stack.
+    pub fn dict_count(&self) -> usize {
+        self.dict_stack.len()
+    }
+
+    /// The system dictionary (bottom of the dictionary stack).
+    pub fn system_dict(&self) -> Rc<RefCell<HashMap<String, PostScriptValue>>> {
+        self.dict_stack[0].clone()
+    }
+
+    /// The user dictionary (topmost permanent dictionary), which receives
+    /// definitions made outside any `begin`/`end` pair.
+    pub fn user_dict(&self) -> Rc<RefCell<HashMap<String, PostScriptValue>>> {
+        self.dict_stack[self.permanent_dicts - 1].clone()
+    }
+
+    /// The global dictionary (see `globaldict`), which is not on the dictionary stack.
+    pub fn global_dict(&self) -> Rc<RefCell<HashMap<String, PostScriptValue>>> {
+        self.global_dict.clone()
+    }
+
+    /// The current dictionary (top of the dictionary stack), where `define` stores.
+    pub fn current_dict(&self) -> Rc<RefCell<HashMap<String, PostScriptValue>>> {
+        self.dict_stack[self.dict_stack.len() - 1].clone()
+    }
+
+    /// Pushes a dictionary onto the dictionary stack, making it the current dictionary.
+    /// Fails with a dict stack overflow once `Limits::max_dict_stack` is reached.
+    pub fn begin(&mut self, dict: Rc<RefCell<HashMap<String, PostScriptValue>>>) -> Result<(), String> {
+        if self.dict_stack.len() >= self.limits.max_dict_stack {
+            return Err("Dict stack overflow".to_string());
+        }
+        self.dict_stack.push(dict);
+        Ok(())
+    }
+
+    /// Pops the current dictionary.
+    /// Fails with a dict stack underflow if only the permanent dictionaries are left.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::Context;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    ///
+    /// let mut context = Context::new(false);
+    /// context.begin(Rc::new(RefCell::new(HashMap::new()))).unwrap();
+    /// assert!(context.end().is_ok());
+    /// assert!(context.end().is_err());
+    /// assert_eq!(context.dict_count(), context.permanent_dicts());
+    /// ```
+    pub fn end(&mut self) -> Result<(), String> {
+        if self.dict_stack.len() <= self.dict_floor() {
+            return Err("Dict stack underflow".to_string());
+        }
+        self.dict_stack.pop();
+        Ok(())
+    }
+
+    /// Pops every dictionary above the permanent (and pinned) ones.
+    pub fn clear_dict_stack(&mut self) {
+        self.dict_stack.truncate(self.dict_floor());
+    }
+
+    /// Keeps `end` and `clear_dict_stack` from popping the bottom `count`
+    /// dictionaries, as they never pop the permanent ones; returns the previous
+    /// count, to put back when done. 0 leaves only the permanent dictionaries.
+    ///
+    /// `Interpreter::execute_isolated` pins its temporary dictionary this way, so the
+    /// code it runs cannot `end` its way into the session's dictionaries.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::Context;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    ///
+    /// let mut context = Context::new(false);
+    /// context.begin(Rc::new(RefCell::new(HashMap::new()))).unwrap();
+    /// let previous = context.set_pinned_dicts(context.dict_count());
+    /// assert!(context.end().is_err());
+    /// context.set_pinned_dicts(previous);
+    /// assert!(context.end().is_ok());
+    /// ```
+    pub fn set_pinned_dicts(&mut self, count: usize) -> usize {
+        std::mem::replace(&mut self.pinned_dicts, count)
+    }
+
+    /// Number of dictionaries that `end` and `clear_dict_stack` leave on the stack.
+    fn dict_floor(&self) -> usize {
+        self.permanent_dicts.max(self.pinned_dicts)
+    }
+
+    /// Replaces the whole dictionary stack, returning the previous one.
+    ///
+    /// Used to switch environments (closures, isolated execution). The new stack must
+    /// be one previously obtained from `dict_stack` on this Context, possibly with
+    /// dictionaries pushed on top; a stack without the permanent dictionaries is rejected.
+    pub fn replace_dict_stack(&mut self, dicts: DictStack) -> Result<DictStack, String> {
+        if dicts.len() < self.permanent_dicts {
+            return Err("Dict stack underflow".to_string());
+        }
+        Ok(std::mem::replace(&mut self.dict_stack, dicts))
+    }
+
+    /// Inserts a dictionary just below the user dictionary, where `end` cannot pop it.
+    ///
+    /// Its definitions are visible everywhere but can be shadowed by the program's own.
+    pub fn insert_permanent_dict(&mut self, dict: Rc<RefCell<HashMap<String, PostScriptValue>>>) {
+        self.dict_stack.insert(self.permanent_dicts - 1, dict);
+        self.permanent_dicts += 1;
+    }
+
+    /// Looks up a name in the dictionary stack.
+    ///
+    /// Searches from top to bottom (most recent to oldest dictionary).
+    /// Returns the first matching value found, or None if not found.
+    ///
+    /// This implements PostScript's hierarchical namespace:
+    /// - Local definitions (in top dictionaries) shadow global ones
+    /// - Built-in commands (in system dictionary at bottom) are always available
+    /// - The global dictionary is searched right below the user dictionary
+    pub fn lookup(&self, key: &str) -> Option<PostScriptValue> {
+        for dict in self.search_order() {
+            if let Some(val) = dict.borrow().get(key) {
+                return Some(val.clone());
+            }
+        }
+        None
+    }
+
+    /// Finds the dictionary that `lookup` would take a name's value from.
+    ///
+    /// Searches the dictionary stack from top to bottom like `lookup`, but returns
+    /// the topmost dictionary that defines the key instead of the value.
+    pub fn lookup_dict(&self, key: &str) -> Option<Rc<RefCell<HashMap<String, PostScriptValue>>>> {
+        self.search_order()
+            .find(|dict| dict.borrow().contains_key(key))
+            .cloned()
+    }
+
+    /// The dictionaries names are looked up in, first to last: the dictionary stack
+    /// from the top down to the user dictionary, the global dictionary, then the
+    /// permanent dictionaries below the user dictionary.
+    fn search_order(&self) -> impl Iterator<Item = &Rc<RefCell<HashMap<String, PostScriptValue>>>> {
+        let (below_user, from_user) = self.dict_stack.split_at(self.permanent_dicts - 1);
+        from_user.iter().rev()
+            .chain(std::iter::once(&self.global_dict))
+            .chain(below_user.iter().rev())
+    }
+
+    /// Whether definitions go into global VM (see `set_global_allocation`).
+    pub fn global_allocation(&self) -> bool {
+        self.global_allocation
+    }
+
+    /// Selects global or local allocation, as `setglobal` does.
+    ///
+    /// This is a simplified model of global VM: in global mode, definitions that
+    /// would go into the user dictionary go into the global dictionary instead,
+    /// where names still find them, `restore` does not roll them back and
+    /// `Interpreter::clear_user_definitions` does not remove them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    ///
+    /// let mut context = Context::new(false);
+    /// context.set_global_allocation(true);
+    /// context.define("shared".to_string(), PostScriptValue::Int(1));
+    /// context.set_global_allocation(false);
+    /// context.define("local".to_string(), PostScriptValue::Int(2));
+    ///
+    /// assert!(context.global_dict().borrow().contains_key("shared"));
+    /// assert!(!context.user_dict().borrow().contains_key("shared"));
+    /// assert_eq!(context.lookup("shared"), Some(PostScriptValue::Int(1)));
+    /// assert!(context.user_dict().borrow().contains_key("local"));
+    /// ```
+    pub fn set_global_allocation(&mut self, global: bool) {
+        self.global_allocation = global;
+    }
+
+    /// Replaces where `getenv` reads environment variables from; None hides every
+    /// variable, so `getenv` always pushes false.
+    ///
+    /// The default reads the process environment. Sandboxed embedders can give
+    /// scripts a synthetic environment instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    ///
+    /// let env = HashMap::from([("HOME".to_string(), "/sandbox".to_string())]);
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// context.set_env_provider(Some(Box::new(move |name| env.get(name).cloned())));
+    /// assert_eq!(context.getenv("HOME"), Some("/sandbox".to_string()));
+    ///
+    /// let mut interpreter = Interpreter::new(context);
+    /// let program = "(HOME) getenv (PATH) getenv";
+    /// interpreter.execute(parse(Tokenizer::new(program).tokenize().unwrap()).unwrap()).unwrap();
+    /// let context = interpreter.get_context_mut();
+    /// assert_eq!(context.pop(), Some(PostScriptValue::Bool(false)));
+    /// assert_eq!(context.pop(), Some(PostScriptValue::Bool(true)));
+    /// match context.pop() {
+    ///     Some(PostScriptValue::String(value)) => assert_eq!(*value.borrow(), "/sandbox"),
+    ///     other => panic!("expected a string, got {:?}", other),
+    /// }
+    ///
+    /// context.set_env_provider(None);
+    /// assert_eq!(context.getenv("HOME"), None);
+    /// ```
+    pub fn set_env_provider(&mut self, provider: Option<EnvProvider>) {
+        self.env_provider = provider;
+    }
+
+    /// Whether scripts may read environment variables (see `set_env_provider`).
+    pub fn has_env_provider(&self) -> bool {
+        self.env_provider.is_some()
+    }
+
+    /// Value of an environment variable, as `getenv` sees it (see `set_env_provider`).
+    pub fn getenv(&self, name: &str) -> Option<String> {
+        self.env_provider.as_ref().and_then(|provider| provider(name))
+    }
+
+    /// Registers a resource instance under key in a category, as `defineresource`
+    /// does, replacing any instance already registered there.
+    ///
+    /// Embedders use this to install resources (such as procedure sets) before
+    /// running a job. Font and Category instances must be dictionaries; defining a
+    /// Category instance creates that category if it does not exist yet.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    /// use postscript_interpreter::interpreter::Interpreter;
+    /// use postscript_interpreter::parser::{Tokenizer, parse};
+    /// use postscript_interpreter::commands::register_builtins;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    ///
+    /// let mut context = Context::new(false);
+    /// register_builtins(&mut context);
+    /// let procs = HashMap::from([("answer".to_string(), PostScriptValue::Int(42))]);
+    /// context.define_resource("ProcSet", "Prolog", PostScriptValue::Dict(Rc::new(RefCell::new(procs)))).unwrap();
+    /// assert!(context.define_resource("Shapes", "Star", PostScriptValue::Null).is_err());
+    ///
+    /// let mut interpreter = Interpreter::new(context);
+    /// let program = "/Prolog /ProcSet findresource begin answer end";
+    /// interpreter.execute(parse(Tokenizer::new(program).tokenize().unwrap()).unwrap()).unwrap();
+    /// assert_eq!(interpreter.get_context().operand_stack(), &[PostScriptValue::Int(42)]);
+    /// ```
+    pub fn define_resource(&mut self, category: &str, key: &str, instance: PostScriptValue) -> Result<(), String> {
+        if matches!(category, "Font" | "Category") && !matches!(instance, PostScriptValue::Dict(_)) {
+            return Err(format!("Type check error: {} resource must be a dicttype, got {}", category, instance.type_name()));
+        }
+        if !self.resources.contains_key(category) {
+            return Err(format!("Undefined resource category: {}", category));
+        }
+        if category == "Category" {
+            self.resources.entry(key.to_string()).or_default();
+        }
+        self.resources.get_mut(category).map(|instances| instances.insert(key.to_string(), instance));
+        Ok(())
+    }
+
+    /// Removes a resource instance, as `undefineresource` does; a key that is not
+    /// defined is not an error. Removing a Category instance removes the category
+    /// with all of its resources.
+    pub fn undefine_resource(&mut self, category: &str, key: &str) -> Result<(), String> {
+        let instances = self.resources.get_mut(category)
+            .ok_or(format!("Undefined resource category: {}", category))?;
+        if instances.remove(key).is_some() && category == "Category" {
+            self.resources.remove(key);
+        }
+        Ok(())
+    }
+
+    /// Takes a snapshot of every composite object reachable from the stacks (see
+    /// `SaveState`) and returns it.
+    pub fn save(&mut self) -> Rc<SaveState> {
+        let roots = self.operand_stack.iter().chain(&self.execution_stack);
+        let mut state = SaveState::capture(roots, &self.dict_stack, &self.global_dict);
+        state.vm_used = self.vm_used;
+        let save = Rc::new(state);
+        self.saves.push(save.clone());
+        save
+    }
+
+    /// Number of snapshots that can still be restored.
+    pub fn save_level(&self) -> usize {
+        self.saves.len()
+    }
+
+    /// Invalidates the snapshots taken after `save_level` reached `level`, without
+    /// rolling anything back.
+    ///
+    /// Used to drop the snapshots of isolated execution (see
+    /// `Interpreter::execute_isolated`).
+    pub fn discard_saves(&mut self, level: usize) {
+        self.saves.truncate(level);
+    }
+
+    /// Rolls every object recorded in a snapshot back to its recorded contents.
+    ///
+    /// The snapshot and all snapshots taken after it become invalid, and the memory
+    /// allocated since it counts as free again (see `vm_used`). Fails, changing
+    /// nothing, if the snapshot is no longer valid or if the operand or dictionary
+    /// stack holds an object created after it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    ///
+    /// let mut context = Context::new(false);
+    /// context.define("x".to_string(), PostScriptValue::Int(1));
+    /// let outer = context.save();
+    /// let inner = context.save();
+    /// context.define("x".to_string(), PostScriptValue::Int(2));
+    ///
+    /// context.restore(&outer).unwrap();
+    /// assert_eq!(context.lookup("x"), Some(PostScriptValue::Int(1)));
+    /// assert_eq!(context.save_level(), 0);
+    /// assert!(context.restore(&inner).is_err());
+    /// ```
+    pub fn restore(&mut self, save: &Rc<SaveState>) -> Result<(), String> {
+        let Some(index) = self.saves.iter().position(|s| Rc::ptr_eq(s, save)) else {
+            return Err("Invalid restore: the save object is no longer valid".to_string());
+        };
+        // Global objects may be newer than the snapshot
+        let global = SaveState::global_cells(&self.global_dict);
+        let known = |v: &PostScriptValue| save.contains(v) || cell_address(v).is_some_and(|addr| global.contains(&addr));
+        if !self.operand_stack.iter().all(known) {
+            return Err("Invalid restore: an object created after save is on the operand stack".to_string());
+        }
+        if !self.dict_stack.iter().all(|d| known(&PostScriptValue::Dict(d.clone()))) {
+            return Err("Invalid restore: a dictionary created after save is on the dictionary stack".to_string());
+        }
+        save.write_back();
+        self.saves.truncate(index);
+        self.vm_used = self.vm_used.min(save.vm_used);
+        Ok(())
+    }
+
+    /// Estimated memory, in bytes, taken by the strings, arrays and dictionaries
+    /// allocated so far. It only grows, except that `restore` gives back what was
+    /// allocated after the save.
+    pub fn vm_used(&self) -> usize {
+        self.vm_used
+    }
+
+    /// Accounts for a new composite object before it is created.
+    ///
+    /// Fails with a VM error, counting nothing, if the estimated memory in use would
+    /// exceed `Limits::max_vm`. Operators creating strings, arrays and dictionaries
+    /// call this, and so does the interpreter for the ones the scanner creates.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Allocation, Context};
+    ///
+    /// let mut context = Context::new(false);
+    /// context.limits.max_vm = 1000;
+    /// context.allocate(Allocation::String(600)).unwrap();
+    /// assert_eq!(context.vm_used(), 600);
+    /// assert!(context.allocate(Allocation::String(600)).is_err());
+    /// assert_eq!(context.vm_used(), 600);
+    /// ```
+    pub fn allocate(&mut self, allocation: Allocation) -> Result<(), String> {
+        let used = self.vm_used.saturating_add(allocation.bytes());
+        if used > self.limits.max_vm {
+            return Err(format!(
+                "VM error: {} bytes more would exceed MaxLocalVM ({} bytes, {} in use)",
+                allocation.bytes(), self.limits.max_vm, self.vm_used,
+            ));
+        }
+        self.vm_used = used;
+        Ok(())
+    }
+
+    /// Access attribute of a value: the one set with `restrict_access` for a
+    /// dictionary, array or string, `Access::Unlimited` for anything else.
+    pub fn access(&self, value: &PostScriptValue) -> Access {
+        cell_address(value)
+            .and_then(|addr| self.access.get(&addr))
+            .map_or(Access::Unlimited, |(_, access)| *access)
+    }
+
+    /// Reduces the access of a dictionary, array or string.
+    ///
+    /// Access is never increased, and other values (including procedures, which
+    /// are not shared objects) are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use postscript_interpreter::types::{Access, Context, PostScriptValue};
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// let mut context = Context::new(false);
+    /// let array = PostScriptValue::Array(Rc::new(RefCell::new(vec![PostScriptValue::Int(1)])));
+    /// let alias = array.clone();
+    /// context.restrict_access(&array, Access::ReadOnly);
+    /// assert_eq!(context.access(&alias), Access::ReadOnly);
+    /// assert!(context.check_access(&alias, Access::Unlimited, "put").is_err());
+    ///
+    /// context.restrict_access(&array, Access::Unlimited);
+    /// assert_eq!(context.access(&array), Access::ReadOnly);
+    /// ```
+    pub fn restrict_access(&mut self, value: &PostScriptValue, access: Access) {
+        let weak: Weak<dyn Any> = match value {
+            PostScriptValue::String(s) | PostScriptValue::ExecutableString(s) => Rc::downgrade(s) as Weak<dyn Any>,
+            PostScriptValue::Array(arr) => Rc::downgrade(arr) as Weak<dyn Any>,
+            PostScriptValue::Dict(dict) => Rc::downgrade(dict) as Weak<dyn Any>,
+            _ => return,
+        };
+        if access < self.access(value) {
+            // Forget the objects that no longer exist while we are at it
+            self.access.retain(|_, (weak, _)| weak.strong_count() > 0);
+            self.access.insert(weak.as_ptr() as *const () as usize, (weak, access));
+        }
+    }
+
+    /// Records the capacity a dictionary was created with, which `maxlength` reports.
+    pub fn set_dict_capacity(&mut self, dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>, capacity: usize) {
+        let weak = Rc::downgrade(dict) as Weak<dyn Any>;
+        // Forget the objects that no longer exist while we are at it
+        self.dict_capacities.retain(|_, (weak, _)| weak.strong_count() > 0);
+        self.dict_capacities.insert(Rc::as_ptr(dict) as usize, (weak, capacity));
+    }
+
+    /// Capacity of a dictionary, as `maxlength` reports it.
+    ///
+    /// This is the capacity requested from `dict`, or the length if more entries
+    /// were added since: dictionaries grow as needed, except at language level 1
+    /// (see `check_dict_room`). Dictionaries not made by `dict` report the capacity
+    /// of their table.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    /// use postscript_interpreter::types::{Context, PostScriptValue};
+    ///
+    /// let mut context = Context::new(false);
+    /// let dict = Rc::new(RefCell::new(HashMap::new()));
+    /// context.set_dict_capacity(&dict, 2);
+    /// assert_eq!(context.dict_capacity(&dict), 2);
+    /// for key in ["a", "b", "c"] {
+    ///     dict.borrow_mut().insert(key.to_string(), PostScriptValue::Null);
+    /// }
+    /// assert_eq!(context.dict_capacity(&dict), 3);
+    /// ```
+    pub fn dict_capacity(&self, dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>) -> usize {
+        let length = dict.borrow().len();
+        match self.dict_capacities.get(&(Rc::as_ptr(dict) as usize)) {
+            Some((_, capacity)) => (*capacity).max(length),
+            None => dict.borrow().capacity(),
+        }
+    }
+
+    /// Fails with a dictfull error if adding key would take a dictionary beyond
+    /// the capacity it was created with. Enforced at language level 1, where
+    /// dictionaries do not grow, and at every level in strict mode (see
+    /// `CompatibilityMode`); replacing the value of a key is always allowed.
+    pub fn check_dict_room(&self, dict: &Rc<RefCell<HashMap<String, PostScriptValue>>>, key: &str) -> Result<(), String> {
+        if self.language_level > 1 && !self.is_strict() {
+            return Ok(());
+        }
+        let Some((_, capacity)) = self.dict_capacities.get(&(Rc::as_ptr(dict) as usize)) else {
+            return Ok(());
+        };
+        let dict = dict.borrow();
+        if dict.len() >= *capacity && !dict.contains_key(key) {
+            return Err(format!("Dict full: dictionary of capacity {} cannot take /{}", capacity, key));
+        }
+        Ok(())
+    }
+
+    /// Fails with an invalidaccess error unless the value's access is at least `needed`.
+    ///
+    /// Operators call this with `Access::ReadOnly` before reading a composite object
+    /// and with `Access::Unlimited` before changing one.
+    pub fn check_access(&self, value: &PostScriptValue, needed: Access, operator: &str) -> Result<(), String> {
+        let access = self.access(value);
+        if access < needed {
+            return Err(format!("Invalid access: {} on a {} {}", operator, access.name(), value.type_name()));
+        }
+        Ok(())
+    }
+
+    /// Raw access to the operand stack, bypassing the Context's methods.
+    #[cfg(feature = "legacy-fields")]
+    #[deprecated(note = "use the operand stack methods of Context instead")]
+    pub fn operand_stack_mut(&mut self) -> &mut Vec<PostScriptValue> {
+        &mut self.operand_stack
+    }
+
+    /// Raw access to the dictionary stack, bypassing the Context's invariant checks.
+    #[cfg(feature = "legacy-fields")]
+    #[deprecated(note = "use begin, end, replace_dict_stack and the other dictionary methods of Context instead")]
+    pub fn dict_stack_mut(&mut self) -> &mut Vec<Rc<RefCell<HashMap<String, PostScriptValue>>>> {
+        &mut self.dict_stack
+    }
+
+    /// Raw access to the execution stack, bypassing the Context's methods.
+    #[cfg(feature = "legacy-fields")]
+    #[deprecated(note = "use schedule, schedule_block and the other execution stack methods of Context instead")]
+    pub fn execution_stack_mut(&mut self) -> &mut Vec<PostScriptValue> {
+        &mut self.execution_stack
     }
 }
 